@@ -0,0 +1,906 @@
+//! A client for the [External Editor API](https://api.tabletopsimulator.com/externaleditorapi/) of Tabletop Simulator.
+//!
+//! Tabletop Simulator listens for messages on port 39999, and sends its answers to port 39998.
+//! Every message is a json object containing a `messageID`, which determines the type of the message.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use derive_more::Display;
+use log::*;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Address Tabletop Simulator listens to for incoming messages.
+pub const SEND_ADDR: &str = "127.0.0.1:39999";
+
+/// Host of [`SEND_ADDR`] and [`LISTEN_ADDR`].
+pub const LOCALHOST: &str = "127.0.0.1";
+
+/// Port of [`SEND_ADDR`].
+pub const SEND_PORT: u16 = 39999;
+
+/// Address Tabletop Simulator sends its answers to.
+pub const LISTEN_ADDR: &str = "127.0.0.1:39998";
+
+/// Port of [`LISTEN_ADDR`].
+pub const LISTEN_PORT: u16 = 39998;
+
+/// The state of a script or ui element of an object, as sent by Tabletop Simulator.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ScriptState {
+    #[serde(default)]
+    pub name: String,
+    pub guid: String,
+    #[serde(default)]
+    pub script: String,
+    #[serde(default)]
+    pub ui: Option<String>,
+}
+
+/// Get a list containing the states for every object. Returns an [`AnswerReload`].
+#[derive(Serialize, Debug)]
+pub struct MessageGetScripts {
+    #[serde(rename = "messageID")]
+    message_id: u8,
+}
+
+/// Update the lua scripts and ui xml for any objects listed in the message,
+/// and then reload the save file. Objects not mentioned are not updated.
+/// Returns an [`AnswerReload`].
+#[derive(Serialize, Debug)]
+pub struct MessageReload {
+    #[serde(rename = "messageID")]
+    message_id: u8,
+    #[serde(rename = "scriptStates")]
+    pub script_states: Value,
+}
+
+/// Send a custom message to be forwarded to the `onExternalMessage` event handler in the currently loaded game.
+#[derive(Serialize, Debug)]
+pub struct MessageCustomMessage {
+    #[serde(rename = "messageID")]
+    message_id: u8,
+    #[serde(rename = "customMessage")]
+    pub custom_message: Value,
+}
+
+/// Executes a lua script globally or on a specific object. Returns an [`AnswerReturn`].
+#[derive(Serialize, Debug)]
+pub struct MessageExecute {
+    #[serde(rename = "messageID")]
+    message_id: u8,
+    #[serde(rename = "returnID")]
+    pub return_id: u64,
+    pub guid: String,
+    pub script: String,
+}
+
+impl MessageGetScripts {
+    pub const MESSAGE_ID: u8 = 0;
+
+    pub fn new() -> Self {
+        Self {
+            message_id: Self::MESSAGE_ID,
+        }
+    }
+}
+
+impl Default for MessageGetScripts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageReload {
+    pub const MESSAGE_ID: u8 = 1;
+
+    pub fn new(script_states: Value) -> Self {
+        Self {
+            message_id: Self::MESSAGE_ID,
+            script_states,
+        }
+    }
+}
+
+impl MessageCustomMessage {
+    pub const MESSAGE_ID: u8 = 2;
+
+    pub fn new(custom_message: Value) -> Self {
+        Self {
+            message_id: Self::MESSAGE_ID,
+            custom_message,
+        }
+    }
+}
+
+impl MessageExecute {
+    pub const MESSAGE_ID: u8 = 3;
+
+    /// Executes `script` globally.
+    /// Every message gets its own return id, so that answers to other messages are not mistaken for its answer.
+    pub fn new(script: String) -> Self {
+        static RETURN_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            message_id: Self::MESSAGE_ID,
+            return_id: RETURN_ID.fetch_add(1, Ordering::Relaxed),
+            guid: "-1".into(),
+            script,
+        }
+    }
+
+    /// Executes `script` on the object with the given `guid`.
+    pub fn on<T: Into<String>>(guid: T, script: String) -> Self {
+        Self::new(script).guid(guid)
+    }
+
+    /// Sets the guid of the object the script is executed on. Use `"-1"` to execute it globally.
+    pub fn guid<T: Into<String>>(mut self, guid: T) -> Self {
+        self.guid = guid.into();
+        self
+    }
+
+    /// Sets the id that is used to identify the [`AnswerReturn`] of this message.
+    pub fn return_id(mut self, return_id: u64) -> Self {
+        self.return_id = return_id;
+        self
+    }
+}
+
+/// When clicking on "Scripting Editor" in the right click contextual menu in TTS for an object that doesn't have a Lua Script yet,
+/// TTS will send a message with the object's script state.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerNewObject {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<0>,
+    #[serde(rename = "scriptStates")]
+    pub script_states: Vec<ScriptState>,
+}
+
+/// After loading a new game in TTS, TTS will send all the Lua scripts and UI XML from the new game.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerReload {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<1>,
+    #[serde(rename = "savePath")]
+    pub save_path: String,
+    #[serde(rename = "scriptStates", default)]
+    pub script_states: Vec<ScriptState>,
+}
+
+/// TTS sends all `print()` messages.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerPrint {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<2>,
+    pub message: String,
+}
+
+/// TTS sends all error messages.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerError {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<3>,
+    pub error: String,
+    pub guid: String,
+    #[serde(rename = "errorMessagePrefix")]
+    pub error_message_prefix: String,
+}
+
+/// Custom messages sent by `sendExternalMessage` in the currently loaded game.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerCustomMessage {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<4>,
+    #[serde(rename = "customMessage")]
+    pub custom_message: Value,
+}
+
+/// Return value of a [`MessageExecute`].
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerReturn {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<5>,
+    #[serde(rename = "returnID")]
+    pub return_id: u64,
+    #[serde(rename = "returnValue", default)]
+    pub return_value: Option<Value>,
+}
+
+/// Whenever the player saves the game in TTS.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerGameSaved {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<6>,
+    /// Path of the file the game has been saved to. Only sent by newer versions of the game.
+    #[serde(rename = "savePath", default)]
+    pub save_path: Option<String>,
+}
+
+/// Whenever the player creates an object in TTS.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnswerObjectCreated {
+    #[serde(rename = "messageID")]
+    pub message_id: MessageId<7>,
+    pub guid: String,
+}
+
+/// The `messageID` of an incoming message.
+///
+/// Deserializing fails if the `messageID` of the payload is not `ID`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessageId<const ID: u8>;
+
+impl<'de, const ID: u8> Deserialize<'de> for MessageId<ID> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            id if id == ID => Ok(MessageId),
+            id => Err(de::Error::custom(format!(
+                "protocol error: expected messageID {ID}, found {id}"
+            ))),
+        }
+    }
+}
+
+/// A message sent by Tabletop Simulator.
+///
+/// The variant is determined by the `messageID` of the message.
+#[derive(Clone, Debug, Display)]
+pub enum Answer {
+    #[display(fmt = "AnswerNewObject")]
+    AnswerNewObject(AnswerNewObject),
+    #[display(fmt = "AnswerReload")]
+    AnswerReload(AnswerReload),
+    #[display(fmt = "AnswerPrint")]
+    AnswerPrint(AnswerPrint),
+    #[display(fmt = "AnswerError")]
+    AnswerError(AnswerError),
+    #[display(fmt = "AnswerCustomMessage")]
+    AnswerCustomMessage(AnswerCustomMessage),
+    #[display(fmt = "AnswerReturn")]
+    AnswerReturn(AnswerReturn),
+    #[display(fmt = "AnswerGameSaved")]
+    AnswerGameSaved(AnswerGameSaved),
+    #[display(fmt = "AnswerObjectCreated")]
+    AnswerObjectCreated(AnswerObjectCreated),
+}
+
+impl<'de> Deserialize<'de> for Answer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let message_id = value
+            .get("messageID")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| de::Error::missing_field("messageID"))?;
+
+        fn parse<'de, T: Deserialize<'de>, E: de::Error>(
+            value: Value,
+        ) -> std::result::Result<T, E> {
+            T::deserialize(value).map_err(de::Error::custom)
+        }
+
+        match message_id {
+            0 => parse(value).map(Answer::AnswerNewObject),
+            1 => parse(value).map(Answer::AnswerReload),
+            2 => parse(value).map(Answer::AnswerPrint),
+            3 => parse(value).map(Answer::AnswerError),
+            4 => parse(value).map(Answer::AnswerCustomMessage),
+            5 => parse(value).map(Answer::AnswerReturn),
+            6 => parse(value).map(Answer::AnswerGameSaved),
+            7 => parse(value).map(Answer::AnswerObjectCreated),
+            id => Err(de::Error::custom(format!("unknown messageID {id}"))),
+        }
+    }
+}
+
+macro_rules! impl_try_from_answer {
+    ($($variant:ident),*) => {
+        $(
+            impl TryFrom<Answer> for $variant {
+                type Error = Answer;
+
+                fn try_from(answer: Answer) -> std::result::Result<Self, Self::Error> {
+                    match answer {
+                        Answer::$variant(answer) => Ok(answer),
+                        other => Err(other),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+#[rustfmt::skip]
+impl_try_from_answer!(
+    AnswerNewObject, AnswerReload, AnswerPrint, AnswerError,
+    AnswerCustomMessage, AnswerReturn, AnswerGameSaved, AnswerObjectCreated
+);
+
+/// How often waiting for an answer checks the [`CancellationToken`], if one is set.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often [`Progress::Waiting`] is reported while waiting for an answer.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Aborts the operations of the [`ExternalEditorApi`] it has been set on with [`Error::Cancelled`],
+/// see [`ExternalEditorApi::cancellation`]. Clones share the same token, so that it can be cancelled from another thread,
+/// e.g. by the cancel button of an application that embeds ttsst.
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operation that is running, and every operation that is started afterwards.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The progress of an operation of an [`ExternalEditorApi`], see [`ExternalEditorApi::on_progress`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    /// A message has been sent to the game. `attempt` is `0`, unless the message is sent again after a timeout.
+    Sent { attempt: u32 },
+    /// No answer has arrived yet, `elapsed` since the message has been sent.
+    Waiting { elapsed: Duration },
+    /// `done` of the `total` chunks of an [`ExecuteBatch`] have been executed.
+    Chunks { done: usize, total: usize },
+}
+
+/// A callback that gets notified about the [`Progress`] of operations.
+#[derive(Clone)]
+struct ProgressCallback(Arc<dyn Fn(&Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// A connection to the External Editor API of Tabletop Simulator.
+#[derive(Debug)]
+pub struct ExternalEditorApi {
+    listener: TcpListener,
+    send_host: String,
+    send_port: u16,
+    timeout: Option<Duration>,
+    retries: u32,
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressCallback>,
+}
+
+impl ExternalEditorApi {
+    /// Starts listening for messages sent by Tabletop Simulator.
+    ///
+    /// Returns [`Error::PortInUse`] if another program, like the official editor plugins, is already listening.
+    pub fn new() -> Result<Self> {
+        Self::with_port(LISTEN_PORT)
+    }
+
+    /// Starts listening for messages on a different `port` than [`LISTEN_PORT`].
+    /// Tabletop Simulator always sends its answers to [`LISTEN_PORT`], so they have to be forwarded to `port`.
+    pub fn with_port(port: u16) -> Result<Self> {
+        Self::with_ports(SEND_PORT, port)
+    }
+
+    /// Sends messages to `send_port` and listens for messages on `listen_port`, instead of the ports of the game.
+    /// Use `0` as the `listen_port` to listen on any free port, e.g. to talk to a [`MockServer`](crate::testing::mock::MockServer).
+    pub fn with_ports(send_port: u16, listen_port: u16) -> Result<Self> {
+        Self::with_hosts(LOCALHOST, send_port, LOCALHOST, listen_port)
+    }
+
+    /// Sends messages to `send_port` on `send_host`, and listens for messages on `listen_port` of the `bind_host` interface,
+    /// e.g. to run on a different machine than Tabletop Simulator. Hosts can be addresses or names, like `192.168.0.10` or `gaming-pc`.
+    /// Use `0.0.0.0` as the `bind_host` to listen on all interfaces.
+    pub fn with_hosts(
+        send_host: &str,
+        send_port: u16,
+        bind_host: &str,
+        listen_port: u16,
+    ) -> Result<Self> {
+        match TcpListener::bind((bind_host, listen_port)) {
+            Ok(listener) => Ok(Self {
+                listener,
+                send_host: send_host.into(),
+                send_port,
+                timeout: None,
+                retries: 0,
+                cancellation: None,
+                progress: None,
+            }),
+            #[rustfmt::skip]
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => Err(Error::PortInUse(listen_port)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Gives up waiting for an answer after `timeout` with [`Error::Timeout`], instead of waiting forever.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends a message up to `retries` more times if its answer times out, in case the message got lost.
+    /// Only has an effect if a [`timeout`](Self::timeout) is set, and only on messages that don't change the game,
+    /// see [`request_retrying`](Self::request_retrying).
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Aborts waiting for answers with [`Error::Cancelled`] once the `token` has been cancelled,
+    /// instead of waiting until the answer arrives or times out.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Calls `callback` with the [`Progress`] of the operations, e.g. to show how long a reload has been taking.
+    pub fn on_progress<F: Fn(&Progress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(ProgressCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Returns [`Error::Cancelled`] if the [`cancellation`](Self::cancellation) token has been cancelled.
+    fn check_cancelled(&self) -> Result<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&self, progress: Progress) {
+        if let Some(ProgressCallback(callback)) = &self.progress {
+            callback(&progress);
+        }
+    }
+
+    /// Returns the port messages are received on.
+    pub fn port(&self) -> Result<u16> {
+        Ok(self.local_addr()?.port())
+    }
+
+    /// Returns the address messages are received on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Sends a message to Tabletop Simulator.
+    pub fn send<T: Serialize>(&self, message: &T) -> Result<()> {
+        let mut stream = TcpStream::connect((self.send_host.as_str(), self.send_port))?;
+        stream.write_all(serde_json::to_string(message)?.as_bytes())?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Waits for the next message sent by Tabletop Simulator.
+    pub fn read(&self) -> Result<Answer> {
+        let (mut stream, _) = self.listener.accept()?;
+        let mut buffer = String::new();
+        stream.read_to_string(&mut buffer)?;
+        Ok(serde_json::from_str(&buffer)?)
+    }
+
+    /// Waits up to `timeout` for the next message sent by Tabletop Simulator.
+    /// Returns `None` if no message has been sent in time.
+    pub fn read_timeout(&self, timeout: Duration) -> Result<Option<Answer>> {
+        let deadline = Instant::now() + timeout;
+        self.listener.set_nonblocking(true)?;
+        let accepted = loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => break Ok(Some(stream)),
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        break Ok(None);
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => break Err(err),
+            }
+        };
+        self.listener.set_nonblocking(false)?;
+
+        let Some(mut stream) = accepted? else {
+            return Ok(None);
+        };
+        stream.set_nonblocking(false)?;
+        let mut buffer = String::new();
+        stream.read_to_string(&mut buffer)?;
+        Ok(Some(serde_json::from_str(&buffer)?))
+    }
+
+    /// Waits for a message of type `T`, discarding every other message.
+    pub fn wait<T: TryFrom<Answer>>(&self) -> Result<T> {
+        self.wait_for(|answer| T::try_from(answer).ok())
+    }
+
+    /// Waits until `f` returns a value for one of the incoming messages.
+    /// Returns [`Error::Timeout`] if that doesn't happen within the [`timeout`](Self::timeout),
+    /// or [`Error::Cancelled`] if the [`cancellation`](Self::cancellation) token gets cancelled meanwhile.
+    pub fn wait_for<T, F: FnMut(Answer) -> Option<T>>(&self, mut f: F) -> Result<T> {
+        let started = Instant::now();
+        let deadline = self.timeout.map(|timeout| (started + timeout, timeout));
+        // Waiting is split into short intervals, to check for cancellation and report the progress in between
+        let polling = self.cancellation.is_some() || self.progress.is_some();
+        let mut reported = started;
+        loop {
+            self.check_cancelled()?;
+            let answer = match (deadline, polling) {
+                (None, false) => self.read()?,
+                (deadline, _) => {
+                    let remaining = deadline
+                        .map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()));
+                    let interval = match (remaining, polling) {
+                        (Some(remaining), true) => remaining.min(POLL_INTERVAL),
+                        (Some(remaining), false) => remaining,
+                        (None, _) => POLL_INTERVAL,
+                    };
+                    match self.read_timeout(interval)? {
+                        Some(answer) => answer,
+                        None => {
+                            if let Some((deadline, timeout)) = deadline {
+                                if Instant::now() >= deadline {
+                                    return Err(Error::Timeout(timeout));
+                                }
+                            }
+                            if reported.elapsed() >= PROGRESS_INTERVAL {
+                                reported = Instant::now();
+                                self.report(Progress::Waiting {
+                                    elapsed: started.elapsed(),
+                                });
+                            }
+                            continue;
+                        }
+                    }
+                }
+            };
+            if let Some(value) = f(answer) {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Sends `message` and waits until `f` returns a value for one of the incoming messages, see [`wait_for`](Self::wait_for).
+    ///
+    /// The message is only sent once, since messages like reloads and executed scripts change the game,
+    /// and a slow game would handle them twice. Use [`request_retrying`](Self::request_retrying) for other messages.
+    pub fn request<M, T, F>(&self, message: &M, f: F) -> Result<T>
+    where
+        M: Serialize,
+        F: FnMut(Answer) -> Option<T>,
+    {
+        self.check_cancelled()?;
+        self.send(message)?;
+        self.report(Progress::Sent { attempt: 0 });
+        self.wait_for(f)
+    }
+
+    /// Sends `message` like [`request`](Self::request), but sends it again up to the number of [`retries`](Self::retries)
+    /// if the answer times out. Only use it for messages that don't change the game, like [`MessageGetScripts`].
+    pub fn request_retrying<M, T, F>(&self, message: &M, mut f: F) -> Result<T>
+    where
+        M: Serialize,
+        F: FnMut(Answer) -> Option<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            self.check_cancelled()?;
+            self.send(message)?;
+            self.report(Progress::Sent { attempt });
+            match self.wait_for(&mut f) {
+                Err(Error::Timeout(timeout)) if attempt < self.retries => {
+                    attempt += 1;
+                    #[rustfmt::skip]
+                    warn!("no answer within {:?}, sending the message again ({}/{})", timeout, attempt, self.retries);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get a list containing the states for every object.
+    pub fn get_scripts(&self) -> Result<AnswerReload> {
+        self.request_retrying(&MessageGetScripts::new(), |answer| answer.try_into().ok())
+    }
+
+    /// Update the lua scripts and ui xml for any objects listed in `script_states`, and then reload the save.
+    pub fn reload(&self, script_states: Value) -> Result<AnswerReload> {
+        self.request(&MessageReload::new(script_states), |answer| {
+            answer.try_into().ok()
+        })
+    }
+
+    /// Send a custom message to the `onExternalMessage` event handler in the currently loaded game.
+    pub fn custom_message(&self, message: Value) -> Result<()> {
+        self.send(&MessageCustomMessage::new(message))
+    }
+
+    /// Executes a lua script globally and returns its return value.
+    pub fn execute(&self, script: String) -> Result<AnswerReturn> {
+        self.execute_message(&MessageExecute::new(script))
+    }
+
+    /// Executes a lua script on the object with the given `guid` and returns its return value.
+    pub fn message_execute_on(&self, guid: &str, script: String) -> Result<AnswerReturn> {
+        self.execute_message(&MessageExecute::on(guid, script))
+    }
+
+    /// Sends `message` and waits for the [`AnswerReturn`] with its return id.
+    fn execute_message(&self, message: &MessageExecute) -> Result<AnswerReturn> {
+        self.request(message, |answer| match answer {
+            Answer::AnswerReturn(answer) if answer.return_id == message.return_id => Some(answer),
+            _ => None,
+        })
+    }
+
+    /// Executes the operations of the `batch` with one message per chunk, see [`ExecuteBatch::run`].
+    /// [`Progress::Chunks`] is reported after every chunk, and cancelling stops before the next one.
+    pub fn execute_batch(&self, batch: &ExecuteBatch) -> Result<Vec<BatchOutcome>> {
+        batch.run(
+            |chunk| Ok(self.execute(chunk.script.clone())?.return_value),
+            |done, total| {
+                self.report(Progress::Chunks { done, total });
+                self.check_cancelled()
+            },
+        )
+    }
+
+    /// Returns the guids of all objects that are inside of the zone with the given `guid`.
+    pub fn objects_in_zone(&self, guid: &str) -> Result<Vec<String>> {
+        let answer = self.execute(zone_objects_lua(guid))?;
+        match answer.return_value {
+            Some(Value::String(json)) => Ok(serde_json::from_str(&json)?),
+            _ => Err(format!("{guid} is not a zone").into()),
+        }
+    }
+}
+
+/// Generates a lua script that returns the guids of all objects inside of the zone with the given `guid`,
+/// encoded as a json array.
+pub fn zone_objects_lua(guid: &str) -> String {
+    format!(
+        r#"local zone = getObjectFromGUID("{guid}")
+if zone == nil or zone.getObjects == nil then return nil end
+local guids = {{}}
+for _, object in ipairs(zone.getObjects()) do
+    table.insert(guids, object.getGUID())
+end
+if #guids == 0 then return "[]" end
+return JSON.encode(guids)"#
+    )
+}
+
+/// Maximum number of operations that are executed in a single chunk of an [`ExecuteBatch`].
+pub const BATCH_OPERATIONS: usize = 50;
+
+/// Maximum length of the script of a single chunk of an [`ExecuteBatch`], in bytes.
+pub const BATCH_LENGTH: usize = 32 * 1024;
+
+/// Time that passes between the chunks of an [`ExecuteBatch`], so that the game isn't flooded with scripts.
+pub const BATCH_PACING: Duration = Duration::from_millis(50);
+
+/// Combines many small lua operations into as few executed scripts as possible,
+/// instead of sending a message and waiting for its answer for every one of them.
+///
+/// Every operation is loaded and called in protected mode, so that an error is attributed to the operation that caused it,
+/// and doesn't prevent the other operations of its chunk from running.
+#[derive(Debug, Clone)]
+pub struct ExecuteBatch {
+    operations: Vec<(String, String)>,
+    max_operations: usize,
+    max_length: usize,
+    pacing: Duration,
+}
+
+/// A script that executes some of the operations of an [`ExecuteBatch`], and returns their results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecuteChunk {
+    pub labels: Vec<String>,
+    pub script: String,
+}
+
+/// The result of a single operation of an [`ExecuteBatch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOutcome {
+    pub label: String,
+    /// The return value of the operation, or the error it raised.
+    pub result: std::result::Result<Option<Value>, String>,
+}
+
+#[derive(Deserialize)]
+struct BatchResult {
+    ok: bool,
+    value: Option<Value>,
+    error: Option<String>,
+}
+
+impl ExecuteBatch {
+    pub fn new() -> Self {
+        Self {
+            operations: Vec::new(),
+            max_operations: BATCH_OPERATIONS,
+            max_length: BATCH_LENGTH,
+            pacing: BATCH_PACING,
+        }
+    }
+
+    /// Limits the number of operations per chunk.
+    pub fn max_operations(mut self, max_operations: usize) -> Self {
+        self.max_operations = max_operations.max(1);
+        self
+    }
+
+    /// Limits the length of the script of a chunk. An operation that exceeds it on its own gets a chunk of its own.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Sets the time that passes between two chunks.
+    pub fn pacing(mut self, pacing: Duration) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Adds the lua `script` as an operation. Its result is reported using the `label`.
+    pub fn push<L: Into<String>, S: Into<String>>(&mut self, label: L, script: S) {
+        self.operations.push((label.into(), script.into()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Splits the operations into chunks that respect the limits of the batch.
+    pub fn chunks(&self) -> Vec<ExecuteChunk> {
+        let mut chunks = Vec::new();
+        let mut labels = Vec::new();
+        let mut calls = String::new();
+        for (label, script) in &self.operations {
+            let call = format!("run({})\n", long_string(script));
+            let full = labels.len() >= self.max_operations
+                || BATCH_HEADER.len() + calls.len() + call.len() + BATCH_FOOTER.len()
+                    > self.max_length;
+            if !labels.is_empty() && full {
+                chunks.push(ExecuteChunk::new(std::mem::take(&mut labels), &calls));
+                calls.clear();
+            }
+            labels.push(label.clone());
+            calls.push_str(&call);
+        }
+        if !labels.is_empty() {
+            chunks.push(ExecuteChunk::new(labels, &calls));
+        }
+        chunks
+    }
+
+    /// Executes the chunks one after another, and returns the outcome of every operation in the order they were added.
+    ///
+    /// `execute` sends the script of a chunk and returns its return value. If a chunk fails,
+    /// its error becomes the outcome of each of its operations, and the remaining chunks are executed anyway.
+    /// `progress` is called with the number of done and total chunks after every chunk, and stops the batch if it returns an error.
+    pub fn run<E, X, P>(
+        &self,
+        mut execute: X,
+        mut progress: P,
+    ) -> std::result::Result<Vec<BatchOutcome>, E>
+    where
+        E: fmt::Display,
+        X: FnMut(&ExecuteChunk) -> std::result::Result<Option<Value>, E>,
+        P: FnMut(usize, usize) -> std::result::Result<(), E>,
+    {
+        let mut outcomes = Vec::with_capacity(self.len());
+        let chunks = self.chunks();
+        for (i, chunk) in chunks.iter().enumerate() {
+            if i > 0 {
+                std::thread::sleep(self.pacing);
+            }
+            let result = execute(chunk).map_err(|err| format!("{err:#}"));
+            match result.and_then(|value| chunk.outcomes(value).map_err(|err| err.to_string())) {
+                Ok(chunk_outcomes) => outcomes.extend(chunk_outcomes),
+                Err(err) => outcomes.extend(chunk.failed(&err)),
+            }
+            progress(i + 1, chunks.len())?;
+        }
+        Ok(outcomes)
+    }
+}
+
+impl Default for ExecuteBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecuteChunk {
+    fn new(labels: Vec<String>, calls: &str) -> Self {
+        Self {
+            labels,
+            script: format!("{BATCH_HEADER}{calls}{BATCH_FOOTER}"),
+        }
+    }
+
+    /// Returns the `error` of the chunk as the outcome of each of its operations.
+    pub fn failed(&self, error: &str) -> Vec<BatchOutcome> {
+        let outcomes = self.labels.iter().map(|label| BatchOutcome {
+            label: label.clone(),
+            result: Err(error.to_string()),
+        });
+        outcomes.collect()
+    }
+
+    /// Attributes the results in the `return_value` of the chunk's script to its operations.
+    pub fn outcomes(&self, return_value: Option<Value>) -> Result<Vec<BatchOutcome>> {
+        let results: Vec<BatchResult> = match return_value {
+            Some(Value::String(json)) => serde_json::from_str(&json)?,
+            _ => return Err("the batch didn't return any results".into()),
+        };
+        if results.len() != self.labels.len() {
+            return Err(format!(
+                "the batch returned {} results for {} operations",
+                results.len(),
+                self.labels.len()
+            )
+            .into());
+        }
+
+        let outcomes = self
+            .labels
+            .iter()
+            .zip(results)
+            .map(|(label, result)| BatchOutcome {
+                label: label.clone(),
+                result: match result.ok {
+                    true => Ok(result.value),
+                    false => Err(result.error.unwrap_or_default()),
+                },
+            });
+        Ok(outcomes.collect())
+    }
+}
+
+/// Defines the `run` function, which calls an operation and collects its result.
+/// Values that can't be encoded as json are converted to strings.
+const BATCH_HEADER: &str = r#"local results = {}
+local function run(source)
+    local operation, err = load(source)
+    local ok, value = false, err
+    if operation ~= nil then ok, value = pcall(operation) end
+    if not ok then
+        table.insert(results, {ok = false, error = tostring(value)})
+    else
+        if type(value) == "userdata" or type(value) == "function" then value = tostring(value) end
+        table.insert(results, {ok = true, value = value})
+    end
+end
+"#;
+
+const BATCH_FOOTER: &str = "return JSON.encode(results)";
+
+/// Quotes `script` as a lua long string, using a level that doesn't occur in it.
+pub fn long_string(script: &str) -> String {
+    let level = (0..)
+        .map(|n| "=".repeat(n))
+        .find(|level| !format!("{script}]").contains(&format!("]{level}]")))
+        .unwrap();
+    // A newline directly after the opening bracket is skipped, so one is added to preserve a leading newline
+    format!("[{level}[\n{script}]{level}]")
+}
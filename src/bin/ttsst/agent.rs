@@ -0,0 +1,170 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+
+use crate::config::normalize;
+use crate::session::Session;
+use crate::{interrupt, port};
+
+/// How often the agent checks whether the game is running, and which save it has loaded.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the agent waits for the game to answer which save it has loaded.
+const GAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long `watch` gets to finish writing the save and stop, once it has been interrupted.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the agent checks whether `watch` has stopped, once it has been interrupted.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Directory ttsst has been started in, before changing to `--cwd` and the project root.
+static START_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Remembers the directory ttsst has been started in, so that `watch` can be started with the same relative paths.
+/// Has to be called before the directory is changed.
+pub fn remember_start_dir() {
+    if let Ok(dir) = std::env::current_dir() {
+        let _ = START_DIR.set(dir);
+    }
+}
+
+/// Waits in the background for the game to load the tracked save, see [`Session::tracked_save`],
+/// and then runs `ttsst watch` until the game exits, so that no terminal has to be kept open per session.
+///
+/// `watch` is started with the arguments in `watch`, which are the global options of the agent followed by its `watch` arguments,
+/// e.g. `ttsst -v agent -- src --exec make` runs `ttsst -v watch src --exec make`. The agent runs until it is interrupted.
+pub fn run(
+    host: &str,
+    send_port: u16,
+    bind: &str,
+    port: u16,
+    watch_args: Vec<OsString>,
+) -> Result<()> {
+    // Stop the agent and `watch` together, instead of exiting right away
+    interrupt::on_interrupt(|| ());
+    let tracked = normalize(&Session::tracked_save()?);
+    info!(
+        "waiting for Tabletop Simulator to load '{}'",
+        tracked.to_slash_lossy().yellow()
+    );
+
+    let mut watch: Option<Child> = None;
+    // The save the game had loaded when `watch` stopped or the save was ignored,
+    // so that nothing is started again until the game loads another save or restarts
+    let mut handled: Option<PathBuf> = None;
+    let mut reported = String::new();
+    let mut report = |message: String| {
+        if message != reported {
+            info!("{message}");
+            reported = message;
+        }
+    };
+
+    while !interrupt::interrupted() {
+        let running = port::game_running(host, send_port);
+        if let Some(child) = &mut watch {
+            if !running {
+                report("Tabletop Simulator has exited, stopping watch".into());
+                stop(child);
+                watch = None;
+            } else if let Some(status) = child.try_wait()? {
+                report(format!("watch has stopped ({status})"));
+                watch = None;
+            }
+        } else if !running {
+            handled = None;
+            report("waiting for Tabletop Simulator to start".into());
+        } else {
+            match loaded_save(host, send_port, bind, port) {
+                Ok(save) if handled.as_ref() == Some(&save) => (),
+                Ok(save) if save == tracked => {
+                    report(format!(
+                        "'{}' has been loaded, starting watch",
+                        save.to_slash_lossy().yellow()
+                    ));
+                    watch = Some(spawn_watch(&watch_args)?);
+                    handled = Some(save);
+                }
+                Ok(save) => {
+                    #[rustfmt::skip]
+                    report(format!("'{}' isn't the tracked save, waiting for another save to be loaded", save.to_slash_lossy().yellow()));
+                    handled = Some(save);
+                }
+                Err(err) => debug!("could not get the loaded save: {}", err),
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    if let Some(child) = &mut watch {
+        stop(child);
+    }
+    info!("stopped");
+    Ok(())
+}
+
+/// Asks the game which save it has loaded. The port is only bound while waiting for the answer,
+/// so that `watch` can bind it afterwards.
+fn loaded_save(host: &str, send_port: u16, bind: &str, port: u16) -> Result<PathBuf> {
+    let api = port::bind(host, send_port, bind, port)?.timeout(GAME_TIMEOUT);
+    Ok(normalize(&PathBuf::from(api.get_scripts()?.save_path)))
+}
+
+/// Starts `ttsst` with the `args` of `watch`, in the directory the agent has been started in.
+fn spawn_watch(args: &[OsString]) -> Result<Child> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command.args(args);
+    if let Some(dir) = START_DIR.get() {
+        command.current_dir(dir);
+    }
+    // Ctrl-C in the terminal only reaches the agent, which interrupts `watch` once, see [`stop`]
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    Ok(command.spawn()?)
+}
+
+/// Interrupts `watch` the same way as Ctrl-C does, so that it finishes writing the save before it stops,
+/// and waits up to [`STOP_TIMEOUT`] for it. It is killed if it doesn't stop in time, or can't be interrupted.
+fn stop(child: &mut Child) {
+    if interrupt_child(child) {
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(_)) => return,
+                Ok(None) => std::thread::sleep(STOP_POLL_INTERVAL),
+                Err(err) => {
+                    debug!("could not wait for watch: {}", err);
+                    break;
+                }
+            }
+        }
+        warn!("watch didn't stop within {:?}, killing it", STOP_TIMEOUT);
+    }
+    if let Err(err) = child.kill().and_then(|_| child.wait().map(|_| ())) {
+        debug!("could not stop watch: {}", err);
+    }
+}
+
+/// Sends SIGINT to `child`. Returns `false` if that failed, e.g. because it has already exited.
+#[cfg(unix)]
+fn interrupt_child(child: &Child) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+        return false;
+    };
+    // SAFETY: `kill` has no memory safety requirements, and `pid` belongs to a child that hasn't been waited for
+    unsafe { libc::kill(pid, libc::SIGINT) == 0 }
+}
+
+/// Windows has no signal that only interrupts a single process, so `watch` is killed right away.
+#[cfg(not(unix))]
+fn interrupt_child(_: &Child) -> bool {
+    false
+}
@@ -0,0 +1,140 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use ttsst::Save;
+
+use crate::config::{config, project, CONFIG_FILE};
+use crate::parser;
+
+/// Name of the table in the config that contains the aliases.
+const ALIASES_TABLE: &str = "aliases";
+
+/// Returns the GUID `name` refers to. `name` is either an alias defined in the config, or a GUID.
+/// Aliases take precedence, so that an alias can't be shadowed by a GUID that looks the same.
+pub fn resolve(name: &str) -> Result<String> {
+    if let Some(guid) = config().aliases.get(name) {
+        debug!("resolved alias '{}' to {}", name, guid);
+        return Ok(guid.clone());
+    }
+
+    match parser::is_guid(name) {
+        true => Ok(name.into()),
+        false => bail!(
+            "'{}' is neither a GUID nor an alias, use `ttsst alias add {} <GUID>` to define it",
+            name.yellow(),
+            name
+        ),
+    }
+}
+
+/// Warns about the aliases in `names` whose GUID no longer exists in the `save`,
+/// and suggests objects that have a similar name.
+pub fn check(save: &Save, names: &[String]) {
+    for name in names {
+        let Some(guid) = config().aliases.get(name) else {
+            continue;
+        };
+        if save.objects.find_object(guid).is_ok() {
+            continue;
+        }
+
+        warn!(
+            "alias '{}' refers to {}, which no longer exists in the save",
+            name.yellow(),
+            guid.yellow()
+        );
+        let suggestions = save
+            .objects
+            .iter()
+            .filter(|object| {
+                let (alias, nickname) = (normalize(name), normalize(&object.nickname));
+                !nickname.is_empty() && (nickname.contains(&alias) || alias.contains(&nickname))
+            })
+            .collect_vec();
+        for object in suggestions {
+            info!(
+                "did you mean {}? Use `ttsst alias add {} {}` to update the alias",
+                object, name, object.guid
+            );
+        }
+    }
+}
+
+/// Lowercases `name` and removes everything but letters and digits, so that e.g. `score_board` matches `Score Board`.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Prints the aliases defined in the config.
+pub fn list() -> Result<()> {
+    let aliases = &config().aliases;
+    if aliases.is_empty() {
+        info!("no aliases defined, use `ttsst alias add <NAME> <GUID>` to define one");
+    }
+    for (name, guid) in aliases {
+        println!("{} = {}", name.bright_white().bold(), guid.yellow());
+    }
+    Ok(())
+}
+
+/// Defines the alias `name` for `guid` in the config, replacing an existing alias with the same name.
+pub fn add(name: &str, guid: &str) -> Result<()> {
+    if !parser::is_alias(name) {
+        #[rustfmt::skip]
+        bail!("'{}' is not a valid alias, it has to start with a letter and only contain letters, digits, '_' and '-'", name.yellow());
+    }
+    if !parser::is_guid(guid) {
+        bail!("'{}' is not a valid GUID", guid.yellow());
+    }
+
+    edit(|aliases| {
+        aliases.insert(name, toml_edit::value(guid));
+        Ok(())
+    })?;
+    info!("added alias '{}' for {}", name.yellow(), guid.yellow());
+    Ok(())
+}
+
+/// Removes the alias `name` from the config.
+pub fn remove(name: &str) -> Result<()> {
+    edit(|aliases| match aliases.remove(name) {
+        Some(_) => Ok(()),
+        None => bail!("alias '{}' does not exist", name.yellow()),
+    })?;
+    info!("removed alias '{}'", name.yellow());
+    Ok(())
+}
+
+/// Edits the `[aliases]` table of the config file, keeping the formatting and comments of the rest of the file.
+fn edit<F>(f: F) -> Result<()>
+where
+    F: FnOnce(&mut toml_edit::Table) -> Result<()>,
+{
+    let path = project().root.join(CONFIG_FILE);
+    let mut document = match path.exists() {
+        true => fs::read_to_string(&path)?.parse::<toml_edit::DocumentMut>()?,
+        false => toml_edit::DocumentMut::new(),
+    };
+
+    let aliases = document
+        .entry(ALIASES_TABLE)
+        .or_insert(toml_edit::table())
+        .as_table_mut();
+    match aliases {
+        Some(aliases) => f(aliases)?,
+        None => bail!(
+            "'{}' in {} is not a table",
+            ALIASES_TABLE,
+            CONFIG_FILE.yellow()
+        ),
+    }
+
+    fs::write(&path, document.to_string())?;
+    Ok(())
+}
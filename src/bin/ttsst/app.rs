@@ -1,28 +1,104 @@
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::{fs, io};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use colored::Colorize;
-use derive_more::Display;
 use itertools::Itertools;
 use log::*;
 use path_slash::PathExt;
-use tts_external_api::ExternalEditorApi as Api;
-use ttsst::{Object, Objects, Save, Tag};
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::api::{Answer, BatchOutcome, ExecuteBatch, MessageReload, ScriptState};
+use ttsst::{Object, Objects, Prompter, Save, Tag, Translations};
 
-use crate::utils::Reduce;
-use crate::{Guids, ReloadArgs};
+use crate::config::{config, normalize, project, WriteConfig};
+use crate::progress::{self, Event, LoadError, Output, ReloadReport};
+use crate::session::Session;
+use crate::utils::{self, long_path, strip_colors, write_atomic, Reduce};
+use crate::{
+    dedup, freeze, guard, helper, history, include, interrupt, metadata, official, players, strict,
+    transform,
+};
+use crate::{CloneArgs, Guids, MetaArgs, ReloadArgs};
+
+/// Time errors sent by the game after it has reloaded the save are attributed to the reload,
+/// since errors in `onLoad` can arrive after the answer.
+pub const LOAD_ERROR_GRACE: Duration = Duration::from_millis(500);
+
+/// The Global files found by [`get_global_path`] by the file names they have been searched for,
+/// so that watching doesn't search the project, or ask which one to use, on every change.
+static GLOBAL_PATHS: Mutex<BTreeMap<Vec<String>, PathBuf>> = Mutex::new(BTreeMap::new());
+
+/// How often [`pick_objects`] checks whether Enter has been pressed.
+const PICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends the scripts of a save to the game and waits until the save has been reloaded.
+pub trait Reloader: Executor {
+    /// Returns the errors the game reported while loading the save, including the ones sent within [`LOAD_ERROR_GRACE`].
+    fn reload(&self, script_states: serde_json::Value) -> Result<Vec<LoadError>>;
+
+    /// Returns the current script states of the game.
+    fn get_scripts(&self) -> Result<Vec<ScriptState>>;
+
+    /// Called with the `save` that is about to be pushed, once it has been written.
+    fn pushing(&self, _save: &Save) {}
+}
+
+impl Reloader for Api {
+    fn reload(&self, script_states: serde_json::Value) -> Result<Vec<LoadError>> {
+        let mut errors = Vec::new();
+        self.request(&MessageReload::new(script_states), |answer| match answer {
+            Answer::AnswerReload(_) => Some(()),
+            Answer::AnswerError(answer) => {
+                errors.push(LoadError::from(&answer));
+                None
+            }
+            _ => None,
+        })?;
+
+        let deadline = Instant::now() + LOAD_ERROR_GRACE;
+        while let Some(answer) =
+            self.read_timeout(deadline.saturating_duration_since(Instant::now()))?
+        {
+            if let Answer::AnswerError(answer) = answer {
+                errors.push(LoadError::from(&answer));
+            }
+        }
+        Ok(errors)
+    }
+
+    fn get_scripts(&self) -> Result<Vec<ScriptState>> {
+        Ok(Api::get_scripts(self)?.script_states)
+    }
+}
+
+/// Executes lua scripts in the game, see [`ExecuteBatch`].
+pub trait Executor {
+    fn execute_batch(&self, batch: &ExecuteBatch) -> Result<Vec<BatchOutcome>>;
+}
+
+impl Executor for Api {
+    fn execute_batch(&self, batch: &ExecuteBatch) -> Result<Vec<BatchOutcome>> {
+        Ok(Api::execute_batch(self, batch)?)
+    }
+}
 
 enum Mode {
     Attach,
     Detach,
+    Pull,
 }
 
 #[derive(Debug)]
 pub struct SaveFile {
     pub save: Save,
     pub path: PathBuf,
+    /// GUIDs of the objects whose scripts are stubs in the save file, see [`dedup`].
+    shared: HashSet<String>,
 }
 
 impl SaveFile {
@@ -34,85 +110,549 @@ impl SaveFile {
 
     // Reads a save from a path and returns it as a `SaveFile`.
     pub fn read_from_path<P: AsRef<Path> + Into<PathBuf>>(save_path: P) -> Result<Self> {
-        let file = fs::File::open(&save_path)?;
+        let file = fs::File::open(long_path(&save_path)?)?;
         let reader = io::BufReader::new(file);
 
         debug!("trying to read save from {}", save_path.as_ref().display());
+        let mut save = serde_json::from_reader(reader)?;
+        helper::remove(&mut save);
         Ok(Self {
-            save: serde_json::from_reader(reader)?,
+            shared: dedup::expand(&mut save),
+            save,
             path: save_path.into(),
         })
     }
 
     /// Writes `self` to the save file that is currently loaded ingame.
+    /// If `normalize` is enabled in the `[write]` table of the config, the written save is normalized,
+    /// see [`Save::normalize`]. Shared scripts are replaced with stubs, see [`dedup::collapse`],
+    /// and the helper module is injected into the Global script, see [`helper::inject`].
     ///
     /// If `self` contains an empty `lua_script` or `xml_ui` string,
     /// the function will cause a connection error.
     pub fn write(&self) -> Result<()> {
-        let file = fs::File::create(&self.path)?;
-        let writer = io::BufWriter::new(file);
+        let mut save = helper::inject(dedup::collapse(&self.save)?);
+        if let WriteConfig {
+            normalize: true,
+            decimals,
+        } = &config().write
+        {
+            save.to_mut().normalize(*decimals);
+        }
 
         debug!("trying to write save to {}", self.path.display());
-        serde_json::to_writer_pretty(writer, &self.save).map_err(|err| err.into())
+        write_atomic(long_path(&self.path)?, serde_json::to_vec_pretty(&save)?)
     }
 }
 
 impl SaveFile {
-    /// Attaches the script to an object by adding the script tag and the script,
-    /// and then reloads the save.
-    pub fn attach<P: AsRef<Path>>(&mut self, api: &Api, path: P, guids: Guids) -> Result<()> {
-        let mut objects = get_objects(&self.save.objects, guids, Mode::Attach)?;
+    /// Writes the `changed` objects of the `pushed` save, and the fields of the save itself, to the save file.
+    /// The other objects are kept the way they are in the file, except for ones whose tags have changed.
+    ///
+    /// The whole save is written instead, see [`SaveFile::write`], if objects have been added or removed,
+    /// or if the save gets normalized.
+    fn write_changed(&self, pushed: &Save, changed: &[&Object]) -> Result<()> {
+        if config().write.normalize {
+            return self.write();
+        }
+        let file = fs::File::open(long_path(&self.path)?)?;
+        let mut json: serde_json::Value = serde_json::from_reader(io::BufReader::new(file))?;
+        let Some(states) = json["ObjectStates"].as_array_mut().filter(|states| {
+            states.len() == pushed.objects.len()
+                && (states.iter().zip(pushed.objects.iter()))
+                    .all(|(state, object)| state["GUID"] == object.guid.as_str())
+        }) else {
+            return self.write();
+        };
 
-        let tag = Tag::try_from(path.as_ref())?;
-        let file = read_file(path)?;
-        for object in objects.iter_mut() {
-            // Add lua tag to objects
-            if tag.is_lua() {
-                object.tags.retain(|tag| !tag.is_lua());
-                object.tags.push(tag.clone());
-                object.lua_script.clone_from(&file);
-                info!("attached script to {object}");
+        let mut written = 0;
+        for (state, object) in states.iter_mut().zip(pushed.objects.iter()) {
+            let tags = serde_json::to_value(&object.tags)?;
+            let has_changed = changed.iter().any(|changed| changed.guid == object.guid)
+                || state.get("Tags").unwrap_or(&serde_json::json!([])) != &tags;
+            if has_changed {
+                *state = serde_json::to_value(object)?;
+                written += 1;
             }
-            // Add xml tag to objects
-            if tag.is_xml() {
-                object.tags.retain(|tag| !tag.is_xml());
-                object.tags.push(tag.clone());
-                object.xml_ui.clone_from(&file);
-                info!("attached ui element to {object}");
+        }
+        // Fields of the save that ttsst changes, the ones that aren't set are removed like when serializing the save
+        let fields = [
+            ("SaveName", serde_json::json!(pushed.name)),
+            ("GameMode", serde_json::json!(pushed.game_mode)),
+            ("Description", serde_json::json!(pushed.description)),
+            ("Tags", serde_json::json!(pushed.save_tags)),
+            ("LuaScript", serde_json::json!(pushed.lua_script)),
+            ("XmlUI", serde_json::json!(pushed.xml_ui)),
+            ("ComponentTags", serde_json::to_value(&pushed.tags)?),
+        ];
+        for (key, value) in fields {
+            match (value, json.as_object_mut()) {
+                (serde_json::Value::Null, Some(save)) => {
+                    save.remove(key);
+                }
+                (value, _) => json[key] = value,
             }
         }
 
+        #[rustfmt::skip]
+        debug!("trying to write {} changed object(s) to {}", written, self.path.display());
+        write_atomic(long_path(&self.path)?, serde_json::to_vec_pretty(&json)?)
+    }
+
+    /// Attaches the script to an object by adding the script tag and the script,
+    /// and then reloads the save. If `pick` is set, the objects are picked in-game, see [`pick_objects`].
+    pub fn attach<P: AsRef<Path>>(
+        &mut self,
+        api: &Api,
+        prompter: &dyn Prompter,
+        paths: &[P],
+        guids: Guids,
+        pick: bool,
+    ) -> Result<()> {
+        let guids = match pick {
+            true => Guids {
+                guids: Some(
+                    pick_objects(api, &self.save.objects)?
+                        .guids()
+                        .into_iter()
+                        .collect(),
+                ),
+                all: false,
+                zones: false,
+            },
+            false => guids,
+        };
+        self.attach_to(api, prompter, paths, guids)
+    }
+
+    /// Attaches the files at `paths` to the objects with the `guids`, or the ones selected using the `prompter`,
+    /// and then reloads the save.
+    pub fn attach_to<P: AsRef<Path>>(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        paths: &[P],
+        guids: Guids,
+    ) -> Result<()> {
+        let files = attached_files(paths, prompter)?;
+        let mut objects = get_objects(&self.save.objects, guids, Mode::Attach, prompter)?;
+        for object in objects.iter_mut() {
+            attach_tags(object, &files)?;
+        }
+
         // Add objects to a new save state
         self.save.objects.replace(&mut objects);
 
-        self.update(api)?;
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Attaches the lua scripts and xml ui inside of `dir` to objects one by one, and then reloads the save.
+    /// Files with the same name, like `widget.lua` and `widget.xml`, are attached together.
+    ///
+    /// Files are attached to the object they are named after, see [`named_object`].
+    /// For other files, the user is asked to select the objects, or none to skip the file.
+    /// Global files and files that are already attached are skipped.
+    pub fn attach_dir(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        dir: &Path,
+        show_all: bool,
+    ) -> Result<()> {
+        let extensions = ttsst::tags::extensions();
+        let files = find_files(dir, &|path| {
+            extensions.is_lua(path) || extensions.is_xml(path)
+        })?;
+        let attached = self
+            .save
+            .objects
+            .iter()
+            .flat_map(|object| object.tags.iter())
+            .cloned()
+            .collect_vec();
+        let is_skipped = |path: &Path| -> Result<bool> {
+            let is_global = path.file_stem().is_some_and(|stem| stem == "Global");
+            Ok(is_global || attached.contains(&Tag::try_from(path)?))
+        };
+
+        // Lua scripts come first, so that they take the xml ui with the same name along
+        let (lua, xml): (Vec<_>, Vec<_>) = files.iter().partition(|path| extensions.is_lua(path));
+        let mut groups: Vec<Vec<&PathBuf>> = Vec::new();
+        for path in lua.into_iter().chain(xml) {
+            if groups.iter().flatten().any(|grouped| *grouped == path) {
+                continue;
+            }
+            if is_skipped(path)? {
+                #[rustfmt::skip]
+                debug!("skipped '{}', which is a Global file or already attached", path.to_slash_lossy());
+                continue;
+            }
+            let counterpart = extensions.counterpart(path);
+            let pair = match files
+                .iter()
+                .find(|other| Some(*other) == counterpart.as_ref())
+            {
+                Some(other) if !is_skipped(other)? => Some(other),
+                _ => None,
+            };
+            groups.push(std::iter::once(path).chain(pair).collect());
+        }
+        if groups.is_empty() {
+            #[rustfmt::skip]
+            info!("'{}' contains no files that aren't attached yet", dir.to_slash_lossy().yellow());
+            return Ok(());
+        }
+
+        let mut attached = 0;
+        for group in groups {
+            let names = group
+                .iter()
+                .map(|path| format!("'{}'", path.to_slash_lossy()))
+                .join(" and ");
+            let guids = match named_object(&self.save.objects, group[0]) {
+                Some(object) => {
+                    debug!("found {} by the name of {}", object.guid, names);
+                    vec![object.guid.clone()]
+                }
+                None => {
+                    let message =
+                        format!("Select the objects to attach {names} to, or none to skip:");
+                    let objects = visible(&self.save.objects, show_all);
+                    let selected = prompter.select_objects(&message, objects)?;
+                    selected.iter().map(|object| object.guid.clone()).collect()
+                }
+            };
+            if guids.is_empty() {
+                info!("skipped {}", names.yellow());
+                continue;
+            }
+
+            let files = attached_files(&group, prompter)?;
+            for guid in &guids {
+                attach_tags(self.save.objects.find_object_mut(guid)?, &files)?;
+            }
+            attached += 1;
+        }
+
+        if attached == 0 {
+            info!("no files have been attached");
+            return Ok(());
+        }
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Opens the lua script attached to the object with the `guid`, or its xml ui if `xml` is set, in the editor,
+    /// see [`utils::edit`]. Once the editor has been closed, the file is reloaded.
+    ///
+    /// If no file is attached, the file at `path` is attached first, offering to create it if it doesn't exist.
+    /// Without a `path`, the file is named after the object the same way as by the official plugins, e.g. `Board.a1b2c3.lua`.
+    pub fn edit(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        guid: &str,
+        xml: bool,
+        path: Option<PathBuf>,
+    ) -> Result<()> {
+        let object = self.save.objects.find_object(guid)?;
+        let tag = match xml {
+            true => object.valid_xml()?,
+            false => object.valid_lua()?,
+        };
+        let path = match tag {
+            Some(tag) => tag.path()?,
+            None => {
+                let extensions = ttsst::tags::extensions();
+                let ext = match xml {
+                    true => &extensions.xml[0],
+                    false => &extensions.lua[0],
+                };
+                let name = object.display_name();
+                let path = match path {
+                    Some(path) => path,
+                    None => project().relative(official::file_name(name, guid, ext))?,
+                };
+                let files = attached_files(&[&path], prompter)?;
+                attach_tags(self.save.objects.find_object_mut(guid)?, &files)?;
+                self.update(api, prompter)?;
+                files[0].0.path()?
+            }
+        };
+
+        info!("editing '{}'", path.to_slash_lossy().yellow());
+        utils::edit(&path)?;
+        let report = self.reload(api, prompter, &[&path], ReloadArgs::default())?;
+        report.print(Output::Text)
+    }
+
+    /// Adds `count` copies of the object with the `guid` to the save, each one moved by `offset` from the previous one,
+    /// and snapped to a grid if `snap` is set. Then reloads the save. See [`Object::duplicate`](ttsst::Object::duplicate).
+    ///
+    /// The copies keep the tags of the object, unless `files` are attached to them instead.
+    /// `{n}` in the path of a file is replaced with the number of the copy, so that every copy can get its own file.
+    pub fn clone_object(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        guid: &str,
+        args: CloneArgs,
+    ) -> Result<()> {
+        let CloneArgs {
+            count,
+            offset,
+            snap,
+            files,
+        } = args;
+        let object = self.save.objects.find_object(guid)?.clone();
+        let mut used = self.save.objects.guids();
+        for n in 1..=count {
+            let mut copy = object.duplicate(&mut used);
+            copy.offset(offset.map(|x| x * n as f64));
+            if let Some(size) = snap {
+                copy.snap_to_grid(size);
+            }
+
+            let paths = files
+                .iter()
+                .map(|path| PathBuf::from(path.to_string_lossy().replace("{n}", &n.to_string())))
+                .collect_vec();
+            if !paths.is_empty() {
+                attach_tags(&mut copy, &attached_files(&paths, prompter)?)?;
+            }
+            info!("cloned {} as {copy}", object.guid.yellow());
+            self.save.objects.push(copy);
+        }
+
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Copies the files of the official editor plugins in `dir` into the `into` directory,
+    /// attaches them to the objects with the GUID of their file names, and then reloads the save.
+    /// See [`official::import`].
+    pub fn import_official<P: AsRef<Path>>(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        dir: P,
+        into: P,
+    ) -> Result<()> {
+        let mut problems = Vec::new();
+        let contained = official::contained_guids(&self.save)?;
+        for (guid, path) in official::import(dir.as_ref(), into.as_ref())? {
+            let tag = Tag::try_from(path.as_path())?;
+            let file = match tag.is_lua() {
+                true => transform::read_lua(tag.path()?)?.script,
+                false => include::read_xml(tag.path()?)?.script,
+            };
+            if guid == official::GLOBAL_GUID {
+                match tag.is_lua() {
+                    true => self.save.lua_script = file,
+                    false => self.save.xml_ui = file,
+                }
+                continue;
+            }
+
+            match self.save.objects.find_object_mut(&guid) {
+                Ok(object) => attach_tags(object, &[(tag, file.into())])?,
+                #[rustfmt::skip]
+                Err(_) if contained.contains(&guid) => info!("{} is inside of a container, '{}' has been copied but not attached", guid.yellow(), path.to_slash_lossy().yellow()),
+                #[rustfmt::skip]
+                Err(_) => problems.push(format!("{} does not exist in the save, '{}' has not been attached", guid.yellow(), path.to_slash_lossy().yellow())),
+            }
+        }
+        strict::report(&problems)?;
+
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Attaches the files at `paths` to Global using tags of the save, see [`Save::set_global_tag`](ttsst::Save::set_global_tag),
+    /// so that they are used instead of the `Global.<Ext>` files. Then reloads the save.
+    pub fn attach_global<P: AsRef<Path>>(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        paths: &[P],
+    ) -> Result<()> {
+        for tag in attached_tags(paths, prompter)? {
+            if self.save.set_global_tag(tag.clone()) {
+                info!("attached {} to {}", tag, "Global".blue());
+            }
+        }
+
+        self.update_global_files(paths, None, prompter)?;
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Removes the tags attached to Global using [`SaveFile::attach_global`], so that the `Global.<Ext>` files
+    /// are used again on the next reload. Then reloads the save.
+    pub fn detach_global(&mut self, api: &impl Reloader, prompter: &dyn Prompter) -> Result<()> {
+        let tags = self.save.remove_global_tags();
+        if tags.is_empty() {
+            info!("{} has no tags attached", "Global".blue());
+            return Ok(());
+        }
+        for tag in tags {
+            info!("detached {} from {}", tag, "Global".blue());
+        }
+
+        self.update(api, prompter)?;
         Ok(())
     }
 
     // Detaches a script and removes all valid tags from an object.
-    pub fn detach(&mut self, api: &Api, guids: Guids) -> Result<()> {
-        let mut objects = get_objects(&self.save.objects, guids, Mode::Detach)?;
+    pub fn detach(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        guids: Guids,
+    ) -> Result<()> {
+        let mut objects = get_objects(&self.save.objects, guids, Mode::Detach, prompter)?;
 
         // Remove tags and script from objects
         for object in objects.iter_mut() {
             object.tags.retain(|tag| !tag.is_valid());
-            object.lua_script = String::new();
+            object.set_lua_script("");
         }
+        metadata::attached(objects.iter())?;
 
         // Add objects to a new save state
         self.save.objects.replace(&mut objects);
 
-        self.update(api)?;
+        self.update(api, prompter)?;
+        Ok(())
+    }
+
+    /// Writes the current lua script and xml ui of objects into the files of their tags, which is the opposite of
+    /// [`reload`](Self::reload). Tagged files that don't exist are created.
+    ///
+    /// The in-game version is used, or the one of the save for objects the game doesn't report.
+    /// Included files are turned back into `#include` directives and `<Include>` tags.
+    /// Xml files whose ui has been pushed with translations are skipped, unless they are up to date.
+    pub fn pull(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        guids: Guids,
+    ) -> Result<()> {
+        let objects = get_objects(&self.save.objects, guids, Mode::Pull, prompter)?;
+        let states = api.get_scripts()?;
+        let session = Session::load();
+
+        let mut pulled = Vec::new();
+        for object in objects.iter() {
+            let state = states.iter().find(|state| state.guid == object.guid);
+            let script = state.map_or(&*object.lua_script, |state| &state.script);
+            let ui = state.map_or(&*object.xml_ui, |state| {
+                state.ui.as_deref().unwrap_or_default()
+            });
+
+            let (lua_tag, xml_tag) = (object.valid_lua()?, object.valid_xml()?);
+            if lua_tag.is_none() && xml_tag.is_none() {
+                warn!("{} has no files attached", object);
+                continue;
+            }
+            if let Some(tag) = lua_tag {
+                let path = tag.path()?;
+                let current = transform::read_lua(&path).map(|expanded| expanded.script);
+                pull_file(object, &path, current, script, prompter)?;
+            }
+            if let Some(tag) = xml_tag {
+                let path = tag.path()?;
+                let lang = session.lang(&tag);
+                let translations = lang
+                    .map(|lang| Translations::load(&config().l10n_dir, lang))
+                    .transpose()?;
+                let current =
+                    read_xml(&path, translations.as_ref()).map(|expanded| expanded.script);
+                match lang {
+                    // The placeholders of the file would be replaced with the translated text
+                    Some(lang) if !current.as_deref().is_ok_and(|current| current == ui) => {
+                        #[rustfmt::skip]
+                        warn!("skipped '{}', since its ui has been pushed with the '{}' translations", path.to_slash_lossy().yellow(), lang.yellow());
+                    }
+                    _ => pull_file(object, &path, current, ui, prompter)?,
+                }
+            }
+
+            let object = self.save.objects.find_object_mut(&object.guid)?;
+            object.set_lua_script(script);
+            object.set_xml_ui(ui);
+            pulled.push(object.guid.clone());
+        }
+        if pulled.is_empty() {
+            return Ok(());
+        }
+
+        // The save and the game are in sync again, so the next reload doesn't report the objects as edited in-game
+        self.write()?;
+        guard::record(&self.save, &pulled)?;
         Ok(())
     }
 
     /// Updates the scripts for all objects that use a script from `path`,
-    /// and then reloads the save.
-    pub fn reload<P>(&mut self, api: &Api, paths: &[P], args: ReloadArgs) -> Result<()>
+    /// and then reloads the save. Returns a report whose `changed` field is `true` if the save has been updated.
+    /// The progress is emitted as [`progress::Event`]s.
+    pub fn reload<P>(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        paths: &[P],
+        args: ReloadArgs,
+    ) -> Result<ReloadReport>
     where
         P: AsRef<Path> + Clone,
     {
-        let mut has_changed = false;
+        progress::emit(Event::ReloadStarted {
+            paths: paths
+                .iter()
+                .map(|path| path.as_ref().to_slash_lossy().into())
+                .collect(),
+        });
+        let started = Instant::now();
+        let result = self
+            .reload_paths(api, prompter, paths, args)
+            .map(|report| ReloadReport {
+                duration: started.elapsed().as_millis() as u64,
+                ..report
+            });
+        progress::emit(match &result {
+            Ok(report) => Event::Completed(report.clone()),
+            Err(err) => Event::Failed {
+                error: strip_colors(&err.to_string()).into(),
+            },
+        });
+        result
+    }
+
+    fn reload_paths<P>(
+        &mut self,
+        api: &impl Reloader,
+        prompter: &dyn Prompter,
+        paths: &[P],
+        args: ReloadArgs,
+    ) -> Result<ReloadReport>
+    where
+        P: AsRef<Path> + Clone,
+    {
+        let translations = match &args.lang {
+            Some(lang) => Some(Translations::load(&config().l10n_dir, lang)?),
+            None => None,
+        };
+
+        let before = self.save.clone();
+        let mut session = Session::load();
+        // Global files are always read if the save gets updated
+        let mut has_changed = args.targets.iter().any(|target| target == "Global");
+        let is_target = |object: &Object| {
+            (args.targets.iter()).any(|target| {
+                *target == object.guid || object.tags.contains(&target.clone().into())
+            })
+        };
         for path in &paths.reduce::<Vec<_>>() {
             // If a guid is passed as an argument, reload only that object,
             // otherwise reload all objects in the save.
@@ -122,26 +662,211 @@ impl SaveFile {
             };
 
             for object in objects.iter_mut() {
-                has_changed |= reload_object(object, path)?;
+                let forced = is_target(object);
+                has_changed |= reload_object(
+                    object,
+                    path,
+                    forced,
+                    translations.as_ref(),
+                    &mut session,
+                    &self.path,
+                )?;
             }
         }
+        progress::emit(Event::FilesRead {
+            count: session.reads,
+        });
+        Session::update(|stored| stored.stamps = session.stamps)?;
 
         // The save only gets updated if an objects has changed to to avoid a loop
         // in which every reload triggers another reload while watching.
-        if has_changed {
-            self.update_global_files(paths)?;
-            self.update(api)?;
+        if !has_changed {
+            return Ok(ReloadReport::default());
         }
+        self.update_global_files(paths, translations.as_ref(), prompter)?;
+        let report = self.update(api, prompter)?;
+        history::record(history::Entry::new(&before, &self.save))?;
+        Ok(report)
+    }
 
+    /// Renames the file at `old` to `new`, replaces the tag on every object that used it,
+    /// and then reloads the save.
+    pub fn rename<P: AsRef<Path>>(
+        &mut self,
+        api: &Api,
+        prompter: &dyn Prompter,
+        old: P,
+        new: P,
+    ) -> Result<()> {
+        let old_tag = Tag::try_from(old.as_ref())?;
+        let new_tag = Tag::try_from(new.as_ref())?;
+        if old_tag.is_lua() != new_tag.is_lua() {
+            return Err(anyhow!(
+                "{} and {} must be the same file type",
+                old_tag,
+                new_tag
+            ));
+        }
+        if new.as_ref().exists() {
+            #[rustfmt::skip]
+            return Err(anyhow!("'{}' already exists", new.as_ref().to_slash_lossy().yellow()));
+        }
+
+        // Move the file on disk
+        if let Some(dir) = new.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::rename(&old, &new)?;
+        #[rustfmt::skip]
+        info!("moved '{}' to '{}'", old.as_ref().to_slash_lossy().yellow(), new.as_ref().to_slash_lossy().yellow());
+
+        // Replace the tags of all objects that use the file
+        match self.save.rename_tag(&old_tag, &new_tag) {
+            0 => info!("no objects use {}", old_tag),
+            count => info!(
+                "replaced {} with {} on {} object(s)",
+                old_tag, new_tag, count
+            ),
+        }
+
+        self.update(api, prompter)?;
         Ok(())
     }
 
+    /// Prints the zones of the save, or the objects inside of the zone with the `guid`.
+    pub fn zones(&self, api: &Api, guid: Option<String>) -> Result<()> {
+        let Some(guid) = guid else {
+            let zones = self.save.objects.zones();
+            match zones.is_empty() {
+                true => info!("{} has no zones", self.save.name.blue()),
+                false => zones.iter().for_each(|zone| println!("{zone}")),
+            }
+            return Ok(());
+        };
+
+        let zone = self.save.objects.find_object(&guid)?;
+        if !zone.is_zone() {
+            return Err(anyhow!("{} is not a zone", zone));
+        }
+        let guids = api.objects_in_zone(&guid)?;
+        match guids.is_empty() {
+            true => info!("{} is empty", zone),
+            false => {
+                for guid in guids {
+                    match self.save.objects.find_object(&guid) {
+                        Ok(object) => println!("{object}"),
+                        Err(_) => println!("{}", guid.yellow()),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the GUIDs of the objects with the GUIDs that have been frozen using [`freeze::freeze`].
+    /// For every frozen object that no longer carries its tags, the user is asked to move the tags back to it,
+    /// or to update the manifest to the GUID of the object that carries them now.
+    /// Fails if a change remains unresolved.
+    pub fn check_guids(&mut self, api: &Api, prompter: &dyn Prompter) -> Result<()> {
+        let mut frozen = freeze::read()?;
+        let mut rebinds = Vec::new();
+        let mut updates = Vec::new();
+        let mut unresolved = 0;
+
+        let changes = freeze::changes(&self.save, &frozen);
+        if changes.is_empty() {
+            info!(
+                "all {} frozen object(s) still carry their tags",
+                frozen.len()
+            );
+            return Ok(());
+        }
+        for change in &changes {
+            let guid = change.frozen.guid.yellow();
+            let name = change.frozen.name.bright_white().bold();
+            let tags = change.frozen.tags.iter().map(|tag| tag.yellow()).join(", ");
+            match change.candidates.as_slice() {
+                [] => warn!("{guid} ({name}) no longer carries {tags}, and no other object does"),
+                [candidate] => {
+                    warn!("{guid} ({name}) no longer carries {tags}, {candidate} does instead")
+                }
+                candidates => warn!(
+                    "{guid} ({name}) no longer carries {tags}, {} objects do instead",
+                    candidates.len()
+                ),
+            }
+            let references = freeze::references(&self.save, &change.frozen.guid);
+            if !references.is_empty() {
+                warn!("{guid} is hard-coded in {}", references.iter().join(", "));
+            }
+
+            match (change.object, change.candidates.as_slice()) {
+                #[rustfmt::skip]
+                (Some(object), [candidate]) if prompter.confirm(&format!("Move the tags of {} back to {}?", candidate.guid, object.guid), false)? => {
+                    rebinds.push((candidate.guid.clone(), object.guid.clone(), change.frozen.tags.clone()));
+                }
+                #[rustfmt::skip]
+                (_, [candidate]) if prompter.confirm(&format!("Freeze {} instead of {}?", candidate.guid, change.frozen.guid), false)? => {
+                    updates.push((change.frozen.guid.clone(), (*candidate).clone()));
+                }
+                _ => unresolved += 1,
+            }
+        }
+
+        for (from, to, tags) in &rebinds {
+            let mut moved = Vec::new();
+            let candidate = self.save.objects.find_object_mut(from)?;
+            for tag in tags.iter().map(|tag| Tag::from(tag.clone())) {
+                // The scripts of the candidate are removed, so that they are removed in-game as well
+                let file = match tag.is_lua() {
+                    true => candidate.lua_script.clone(),
+                    false => candidate.xml_ui.clone(),
+                };
+                match tag.is_lua() {
+                    true => candidate.set_lua_script(""),
+                    false => candidate.set_xml_ui(""),
+                };
+                candidate.tags.retain(|other| *other != tag);
+                moved.push((tag, file));
+            }
+
+            let object = self.save.objects.find_object_mut(to)?;
+            attach_tags(object, &moved)?;
+        }
+        if !rebinds.is_empty() {
+            self.update(api, prompter)?;
+        }
+
+        if !updates.is_empty() {
+            for (guid, object) in &updates {
+                if let Some(entry) = frozen.iter_mut().find(|entry| entry.guid == *guid) {
+                    entry.guid = object.guid.clone();
+                }
+            }
+            freeze::write(&frozen)?;
+            info!(
+                "updated {} GUID(s) in '{}'",
+                updates.len(),
+                freeze::MANIFEST_FILE.yellow()
+            );
+        }
+
+        match unresolved {
+            0 => Ok(()),
+            #[rustfmt::skip]
+            _ => Err(anyhow!("the GUIDs of {} frozen object(s) have changed, use `ttsst freeze-guids` to freeze the current GUIDs", unresolved)),
+        }
+    }
+
     /// Backup current save as file
     pub fn backup<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::copy(&self.path, &path)?;
+        fs::copy(long_path(&self.path)?, long_path(&path)?)?;
 
         // Print information about the file
-        let save_name = Path::new(&self.path).file_name().unwrap().to_str().unwrap();
+        let save_name = match self.path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return Err(anyhow!("'{}' is not a file", self.path.to_slash_lossy())),
+        };
         let path_display = path.as_ref().to_slash_lossy();
         #[rustfmt::skip]
         info!("save '{}' as '{}'", save_name.yellow(), path_display.yellow());
@@ -149,51 +874,156 @@ impl SaveFile {
         Ok(())
     }
 
-    /// Overwrite the save file and reload the current save,
-    /// the same way it get reloaded when pressing “Save & Play” within the in-game editor.
-    fn update(&mut self, api: &Api) -> Result<()> {
-        // Warning if tag an lua script or xml ui are mismatched
-        for object in self.save.objects.iter() {
-            if let (None, false) = (object.valid_lua()?, object.lua_script.is_empty()) {
-                warn!("{} has a lua script but no valid lua tag", object);
+    /// Sets the metadata of the save provided in `args`, and then reloads the save.
+    /// If no metadata is provided, the current metadata is printed instead.
+    pub fn meta(&mut self, api: &Api, prompter: &dyn Prompter, args: MetaArgs) -> Result<()> {
+        let save = &mut self.save;
+        let mut has_changed = false;
+        if let Some(name) = args.name {
+            info!("set the name to '{}'", name.yellow());
+            save.name = name;
+            has_changed = true;
+        }
+        if let Some(game_mode) = args.game_mode {
+            info!("set the game mode to '{}'", game_mode.yellow());
+            save.game_mode = Some(game_mode);
+            has_changed = true;
+        }
+        if let Some(tags) = args.tags {
+            info!(
+                "set the tags to {}",
+                tags.iter().map(|t| t.yellow()).join(", ")
+            );
+            save.save_tags = Some(tags);
+            has_changed = true;
+        }
+        if let Some(description) = args.description {
+            info!("set the description");
+            save.description = Some(description);
+            has_changed = true;
+        }
+
+        // The thumbnail is stored as a png file next to the save file
+        if let Some(thumbnail) = &args.thumbnail {
+            let path = self.path.with_extension("png");
+            fs::copy(thumbnail, &path)?;
+            #[rustfmt::skip]
+            info!("copied '{}' to '{}'", thumbnail.to_slash_lossy().yellow(), path.to_slash_lossy().yellow());
+        }
+
+        match has_changed {
+            true => self.update(api, prompter).map(|_| ()),
+            false if args.thumbnail.is_some() => Ok(()),
+            false => {
+                let none = || "-".bright_black().to_string();
+                let save = &self.save;
+                println!("{:<12} {}", "Name:", save.name.bright_white().bold());
                 #[rustfmt::skip]
-                warn!("If you manually removed the tag, use the detach command to remove the lua script");
-            }
-            if let (None, false) = (object.valid_xml()?, object.xml_ui.is_empty()) {
-                warn!("{} has a xml ui but no valid xml tag", object);
+                println!("{:<12} {}", "Game mode:", save.game_mode.clone().unwrap_or_else(none));
+                #[rustfmt::skip]
+                println!("{:<12} {}", "Tags:", save.save_tags.as_ref().map(|tags| tags.join(", ")).unwrap_or_else(none));
+                #[rustfmt::skip]
+                println!("{:<12} {}", "Description:", save.description.clone().unwrap_or_else(none));
+                let thumbnail = self.path.with_extension("png");
                 #[rustfmt::skip]
-                warn!("If you manually removed the tag, use the detach command to remove the xml ui");
+                println!("{:<12} {}", "Thumbnail:", match thumbnail.exists() {
+                    true => thumbnail.to_slash_lossy().into_owned(),
+                    false => none(),
+                });
+                Ok(())
             }
         }
+    }
+
+    /// Overwrite the save file and reload the current save,
+    /// the same way it get reloaded when pressing “Save & Play” within the in-game editor.
+    ///
+    /// Mismatched tags and scripts are reported first, before anything is written, see [`strict::violations`].
+    /// Scripts that have been edited in-game are resolved afterwards. See [`guard::check`].
+    /// Returns what has been sent to the game, and the errors it reported, without the duration of the reload.
+    fn update(&mut self, api: &impl Reloader, prompter: &dyn Prompter) -> Result<ReloadReport> {
+        strict::violations(&self.save)?;
 
         // Remove component tags, if they exist as object tags
         self.save.remove_object_tags();
 
-        // Overwrite the save file with the modified objects
-        self.write()?;
+        // Make sure that scripts which have been edited in-game don't get overwritten
+        let guids = self
+            .save
+            .objects
+            .iter()
+            .filter(|object| object.is_dirty())
+            .map(|object| object.guid.clone())
+            .chain(["-1".to_string()])
+            .collect_vec();
+        guard::check(&mut self.save, &guids, &api.get_scripts()?, prompter)?;
+        // Reloading resets the scripting state, which other players would notice in the middle of a game
+        players::check(api, prompter)?;
 
-        // Add global lua_script and xml_ui to save
-        let mut objects = self.save.objects.to_values();
+        // Writing and pushing the save is completed as a whole, even if the process gets interrupted meanwhile
+        let _deferred = interrupt::defer();
+
+        // Only send and write the objects that have changed, and the global lua_script and xml_ui.
+        // Objects that are or have been sharing a script are sent as well, since the table of shared scripts is rebuilt.
+        let pushed = helper::inject(dedup::collapse(&self.save)?);
+        let stubs = dedup::stubs(&pushed);
+        let changed = pushed
+            .objects
+            .iter()
+            .filter(|o| o.is_dirty() || stubs.contains(&o.guid) || self.shared.contains(&o.guid))
+            .collect_vec();
+        self.write_changed(&pushed, &changed)?;
+        api.pushing(&pushed);
+
+        let mut objects = changed.into_iter().map(Object::to_value).collect_vec();
         objects.push(serde_json::json!({
             "guid": "-1",
-            "script": self.save.lua_script,
-            "ui": self.save.xml_ui,
+            "script": pushed.lua_script,
+            "ui": pushed.xml_ui,
         }));
 
+        let script_states = serde_json::json!(objects);
+        let report = ReloadReport {
+            changed: true,
+            objects: objects
+                .iter()
+                .filter_map(|object| object["guid"].as_str().map(String::from))
+                .collect(),
+            bytes: script_states.to_string().len(),
+            duration: 0,
+            errors: Vec::new(),
+        };
+
         // Reload save
-        api.reload(serde_json::json!(objects))?;
+        progress::emit(Event::WaitingForGame);
+        let errors = api.reload(script_states)?;
+        guard::record(&pushed, &report.objects)?;
+        Session::pushed(&self.path, &self.save)?;
+        metadata::pushed(&self.save)?;
+        self.shared = stubs;
+        self.save.objects.clear_dirty();
         info!("reloading {}", self.save.name.blue());
-        Ok(())
+        Ok(ReloadReport { errors, ..report })
     }
 
-    /// Set the lua script of the save to either `Global.lua` or `Global.ttslua`, if one of them exists in the `path` directory.
-    /// Set the xml ui of the save to `Global.xml`, if it exists in the `path` directory.
+    /// Set the lua script of the save to `Global.<Ext>`, e.g. `Global.lua` or `Global.ttslua`, if one of them exists in the `path` directory.
+    /// Set the xml ui of the save to `Global.<Ext>`, e.g. `Global.xml`, if it exists in the `path` directory.
+    /// The extensions are the ones set by [`set_extensions`](ttsst::tags::set_extensions).
+    /// Subdirectories are searched as well, unless the Global files are attached using tags of the save,
+    /// see [`Save::set_global_tag`](ttsst::Save::set_global_tag), or set in the config.
     ///
     /// If the file is empty, this function will use a placeholder text to avoid writing an empty string.
     /// See [`Save::write`].
-    fn update_global_files<P: AsRef<Path>>(&mut self, paths: &[P]) -> Result<()> {
-        const GLOBAL_LUA: &[&str] = &["Global.lua", "Global.ttslua"];
-        const GLOBAL_XML: &[&str] = &["Global.xml"];
+    fn update_global_files<P: AsRef<Path>>(
+        &mut self,
+        paths: &[P],
+        translations: Option<&Translations>,
+        prompter: &dyn Prompter,
+    ) -> Result<()> {
+        let extensions = ttsst::tags::extensions();
+        let global = |ext: &String| format!("Global.{ext}");
+        let global_lua = extensions.lua.iter().map(global).collect_vec();
+        let global_xml = extensions.xml.iter().map(global).collect_vec();
 
         // Filter out duplicates
         let unique_paths = paths
@@ -201,8 +1031,20 @@ impl SaveFile {
             .unique_by(|path| path.as_ref().to_owned())
             .collect_vec();
 
-        if let Some(path) = get_global_path(&unique_paths, GLOBAL_LUA)? {
-            let file = read_file(&path)?;
+        // Files attached to Global using tags of the save take precedence over the config
+        let pinned = &config().global;
+        let pinned_lua = match self.save.global_lua()? {
+            Some(tag) => Some(tag.path()?),
+            None => pinned.lua.clone(),
+        };
+        let pinned_xml = match self.save.global_xml()? {
+            Some(tag) => Some(tag.path()?),
+            None => pinned.xml.clone(),
+        };
+
+        #[rustfmt::skip]
+        if let Some(path) = get_global_path(&unique_paths, &global_lua, pinned_lua.as_deref(), prompter)? {
+            let file = transform::read_lua(&path)?.script;
             let lua_script = match file.is_empty() {
                 #[rustfmt::skip]
                 true => "--[[ Lua code. See documentation: https://api.tabletopsimulator.com/ --]]".into(),
@@ -216,8 +1058,9 @@ impl SaveFile {
         };
 
         // Update xml_ui
-        if let Some(path) = get_global_path(&unique_paths, GLOBAL_XML)? {
-            let file: String = read_file(&path)?;
+        #[rustfmt::skip]
+        if let Some(path) = get_global_path(&unique_paths, &global_xml, pinned_xml.as_deref(), prompter)? {
+            let file = read_xml(&path, translations)?.script;
             let xml_ui = match file.is_empty() {
                 #[rustfmt::skip]
                 true => "<!-- Xml UI. See documentation: https://api.tabletopsimulator.com/ui/introUI/ -->".into(),
@@ -234,24 +1077,107 @@ impl SaveFile {
     }
 }
 
+/// Returns the tags of the files at `paths`, and offers to create the files that don't exist yet.
+/// Returns an error if two paths are passed, which aren't a lua and a xml file.
+fn attached_tags<P: AsRef<Path>>(paths: &[P], prompter: &dyn Prompter) -> Result<Vec<Tag>> {
+    let tags = paths
+        .iter()
+        .map(|path| Tag::try_from(path.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Objects can only have one lua and one xml tag
+    if let [first, second] = tags.as_slice() {
+        if first.is_lua() == second.is_lua() {
+            #[rustfmt::skip]
+            bail!("'{}' and '{}' can't be attached together, one of them has to be a Lua script and the other a XML UI", first.path()?.to_slash_lossy().yellow(), second.path()?.to_slash_lossy().yellow());
+        }
+    }
+    for tag in &tags {
+        if !tag.path()?.exists() {
+            create_file(&tag.path()?, prompter)?;
+        }
+    }
+    Ok(tags)
+}
+
+/// Returns the tags and contents of the files at `paths`, see [`attached_tags`], with the lua script first.
+/// The files are shared between all objects they get attached to, instead of being copied for each of them.
+fn attached_files<P: AsRef<Path>>(
+    paths: &[P],
+    prompter: &dyn Prompter,
+) -> Result<Vec<(Tag, Arc<str>)>> {
+    let tags = attached_tags(paths, prompter)?;
+    let mut files = Vec::new();
+    for tag in tags {
+        // The file is read at the path of the tag, which is the same on every platform
+        let file: Arc<str> = match tag.is_lua() {
+            true => transform::read_lua(tag.path()?)?.script.into(),
+            false => include::read_xml(tag.path()?)?.script.into(),
+        };
+        files.push((tag, file));
+    }
+    files.sort_by_key(|(tag, _)| !tag.is_lua());
+    Ok(files)
+}
+
+/// Replaces the lua or xml tag of `object` with the tag of each of `files`, and sets its script or ui to the file.
+/// The attached files are recorded in the metadata, see [`metadata::attached`].
+fn attach_tags(object: &mut Object, files: &[(Tag, Arc<str>)]) -> Result<()> {
+    let mut attached = Vec::new();
+    for (tag, file) in files {
+        // Add lua tag to objects
+        if tag.is_lua() {
+            object.tags.retain(|tag| !tag.is_lua());
+            object.tags.push(tag.clone());
+            object.set_lua_script(file.clone());
+            attached.push("script");
+        }
+        // Add xml tag to objects
+        if tag.is_xml() {
+            object.tags.retain(|tag| !tag.is_xml());
+            object.tags.push(tag.clone());
+            object.set_xml_ui(file.clone());
+            attached.push("ui element");
+        }
+    }
+    if !attached.is_empty() {
+        info!("attached {} to {object}", attached.join(" and "));
+        metadata::attached([&*object])?;
+    }
+    Ok(())
+}
+
 /// Reload the lua script and xml ui of an `object`, if its tag matches the `path`.
+/// If `forced` is set, its files are read regardless of the `path` and whether they have changed.
 /// Returns `true` if the object has changed.
-fn reload_object<P: AsRef<Path>>(object: &mut Object, path: P) -> Result<bool> {
+fn reload_object<P: AsRef<Path>>(
+    object: &mut Object,
+    path: P,
+    forced: bool,
+    translations: Option<&Translations>,
+    session: &mut Session,
+    save_path: &Path,
+) -> Result<bool> {
+    let lang = translations.map(|translations| translations.lang.as_str());
     // Update lua scripts if the path is a lua file
     let lua_change = match object.valid_lua()? {
-        Some(tag) if tag.starts_with(&path) => {
-            let file = read_file(tag.path()?)?;
-            if object.lua_script != file {
-                object.lua_script = file;
+        // Scripts are reloaded if a file they include has changed as well
+        Some(tag) if !forced && !tag.starts_with(&path) && !session.includes(&tag, &path) => false,
+        // Files that haven't changed since they have been pushed don't have to be read
+        Some(tag) if !forced && session.is_unchanged(save_path, &tag, None, &object.lua_script) => {
+            false
+        }
+        Some(tag) => {
+            let expanded = transform::read_lua(tag.path()?)?;
+            session.stamp(&tag, None, &expanded.script, &expanded.includes);
+            let changed = object.set_lua_script(expanded.script);
+            if changed {
                 info!("updated {object}");
-                true
-            } else {
-                false
             }
+            changed
         }
         // Remove lua script if the objects has no valid tag
         None if !object.lua_script.is_empty() => {
-            object.lua_script = "".into();
+            object.set_lua_script("");
             info!("removed lua script from {}", object);
             true
         }
@@ -259,19 +1185,23 @@ fn reload_object<P: AsRef<Path>>(object: &mut Object, path: P) -> Result<bool> {
     };
     // Update xml ui if the path is a xml file
     let xml_change = match object.valid_xml()? {
-        Some(tag) if tag.starts_with(&path) => {
-            let file = read_file(tag.path()?)?;
-            if object.xml_ui != file {
-                object.xml_ui = file;
+        // Ui is reloaded if a file it includes has changed as well
+        Some(tag) if !forced && !tag.starts_with(&path) && !session.includes(&tag, &path) => false,
+        Some(tag) if !forced && session.is_unchanged(save_path, &tag, lang, &object.xml_ui) => {
+            false
+        }
+        Some(tag) => {
+            let expanded = read_xml(tag.path()?, translations)?;
+            session.stamp(&tag, lang, &expanded.script, &expanded.includes);
+            let changed = object.set_xml_ui(expanded.script);
+            if changed {
                 info!("updated {object}");
-                true
-            } else {
-                false
             }
+            changed
         }
         // Remove xml ui if the objects has no valid tag
         None if !object.xml_ui.is_empty() => {
-            object.xml_ui = "".into();
+            object.set_xml_ui("");
             info!("removed xml ui from {}", object);
             true
         }
@@ -283,84 +1213,497 @@ fn reload_object<P: AsRef<Path>>(object: &mut Object, path: P) -> Result<bool> {
 
 /// If no guids are provided show a selection of objects in the current savestate.
 /// Otherwise ensure that the guids provided exist.
-fn get_objects(objects: &Objects, guids: Guids, mode: Mode) -> Result<Objects> {
+fn get_objects(
+    objects: &Objects,
+    guids: Guids,
+    mode: Mode,
+    prompter: &dyn Prompter,
+) -> Result<Objects> {
     let message = match mode {
         Mode::Attach => "Select the object to attach the script or ui element to:",
         Mode::Detach => "Select the object to detach the script and ui element from:",
+        Mode::Pull => "Select the object to pull the script and ui element of:",
     };
 
     match guids.guids {
         Some(guids) => objects.find_objects(&guids).map_err(|err| err.into()),
-        None => select_objects(objects, message, guids.all),
+        None if guids.zones => Ok(prompter.select_objects(message, objects.scripting_zones())?),
+        None => Ok(prompter.select_objects(message, visible(objects, guids.all))?),
     }
 }
 
-/// Shows a multi selection prompt of objects loaded in the current save
-fn select_objects(objects: &Objects, message: &str, show_all: bool) -> Result<Objects> {
-    let objects = match show_all {
+/// Waits for objects to be picked in-game until Enter is pressed, and returns them.
+/// Right-clicking an object and selecting "Scripting Editor" makes the game send it, if it has no lua script yet.
+fn pick_objects(api: &Api, objects: &Objects) -> Result<Objects> {
+    #[rustfmt::skip]
+    info!("right-click objects in-game and select {} to pick them, then press Enter", "Scripting Editor".bright_white().bold());
+    let (tx, done) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = io::stdin().read_line(&mut String::new());
+        let _ = tx.send(());
+    });
+
+    let mut picked: Vec<String> = Vec::new();
+    while done.try_recv() == Err(TryRecvError::Empty) {
+        let answer = match api.read_timeout(PICK_INTERVAL) {
+            Ok(Some(answer)) => answer,
+            Ok(None) | Err(ttsst::error::Error::SerdeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let Answer::AnswerNewObject(answer) = answer else {
+            continue;
+        };
+        for state in answer.script_states {
+            match objects.find_object(&state.guid) {
+                Ok(_) if picked.contains(&state.guid) => (),
+                Ok(object) => {
+                    info!("picked {object}");
+                    picked.push(state.guid);
+                }
+                #[rustfmt::skip]
+                Err(_) => warn!("'{}' is not in the save file yet, save the game and pick it again", state.guid.yellow()),
+            }
+        }
+    }
+
+    if picked.is_empty() {
+        bail!("no object has been picked");
+    }
+    Ok(objects.find_objects(&picked)?)
+}
+
+/// Returns the objects that are shown in selection prompts.
+fn visible(objects: &Objects, show_all: bool) -> Objects {
+    match show_all {
         true => objects.clone(),
-        false => objects.clone().filter_hidden(),
+        false => objects.clone().filter_names(&config().prompt.hidden),
+    }
+}
+
+/// Returns the object the file at `path` is named after: the object with the GUID of a file named
+/// like the files of the official plugins, e.g. `Board.a1b2c3.lua`, or the only object with the name of the file.
+fn named_object<'a>(objects: &'a Objects, path: &Path) -> Option<&'a Object> {
+    let stem = path.file_stem()?.to_str()?;
+    if let Some((_, guid)) = stem.rsplit_once('.') {
+        if let Ok(object) = objects.find_object(guid) {
+            return Some(object);
+        }
+    }
+    objects
+        .iter()
+        .filter(|object| object.display_name().eq_ignore_ascii_case(stem))
+        .exactly_one()
+        .ok()
+}
+
+/// Asks whether the missing file at `path` should be created, and creates it.
+/// If the templates directory contains files of the same type, one of them can be used as its content.
+fn create_file(path: &Path, prompter: &dyn Prompter) -> Result<()> {
+    let display = path.to_slash_lossy();
+    let message = format!(
+        "'{}' does not exist. Do you want to create it?",
+        display.yellow()
+    );
+    if !prompter.confirm(&message, true)? {
+        return Err(anyhow!("'{}' does not exist", display.yellow()));
+    }
+
+    // Templates with the same extension as the new file
+    let templates_dir = &config().templates_dir;
+    let templates = match templates_dir.is_dir() {
+        true => fs::read_dir(templates_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|template| template.is_file() && template.extension() == path.extension())
+            .sorted()
+            .collect_vec(),
+        false => Vec::new(),
     };
 
-    match inquire::MultiSelect::new(message, objects.into_inner()).prompt() {
-        Ok(obj) => Ok(obj.into()),
-        Err(err) => Err(err.into()),
+    let content = match templates.is_empty() {
+        true => String::new(),
+        false => {
+            const EMPTY: &str = "Empty file";
+            let options = std::iter::once(EMPTY.to_string())
+                .chain(templates.iter().map(|t| t.to_slash_lossy().into_owned()))
+                .collect_vec();
+            match prompter.select("Select a template:", options)?.as_str() {
+                EMPTY => String::new(),
+                template => read_file(template)?,
+            }
+        }
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
     }
+    fs::write(path, content)?;
+    info!("created '{}'", display.yellow());
+    Ok(())
 }
 
-/// Returns a path to a global script, by joining `paths` and `files`.
+/// Writes the `script` of the `object`, which has been pulled from the game, into the file at `path`, see [`SaveFile::pull`].
+/// `current` is the file's content as it would be pushed, so that files which are up to date aren't touched.
+///
+/// Files that have been changed are only overwritten if confirmed, or if `--force` is passed,
+/// and never if a transformer changes them in a way that can't be reverted.
+fn pull_file(
+    object: &Object,
+    path: &Path,
+    current: Result<String>,
+    script: &str,
+    prompter: &dyn Prompter,
+) -> Result<()> {
+    let display = path.to_slash_lossy();
+    if current.as_deref().is_ok_and(|current| current == script) {
+        info!("'{}' is up to date", display.yellow());
+        return Ok(());
+    }
+
+    if path.exists() {
+        if let Some(name) = transform::irreversible(path)? {
+            #[rustfmt::skip]
+            warn!("skipped '{}', since the '{}' transformer can't be reverted", display.yellow(), name.yellow());
+            return Ok(());
+        }
+        let message = format!(
+            "'{}' differs from the in-game version. Do you want to overwrite it?",
+            display.yellow()
+        );
+        if !config().force && !prompter.confirm(&message, false)? {
+            info!("kept '{}'", display.yellow());
+            return Ok(());
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, include::unbundle_file(path, script))?;
+    info!("pulled {} into '{}'", object, display.yellow());
+    Ok(())
+}
+
+/// Shows a selection prompt of objects loaded in the current save
+pub fn select_object(
+    prompter: &dyn Prompter,
+    objects: &Objects,
+    message: &str,
+    show_all: bool,
+) -> Result<Object> {
+    Ok(prompter.select_object(message, visible(objects, show_all))?)
+}
+
+/// Returns the file the Global lua script of the `save` is read from: the file attached using a tag of the save,
+/// the file set in the config, or the file that has been found by [`get_global_path`] most recently.
+pub fn global_lua_path(save: &Save) -> Option<PathBuf> {
+    if let Some(tag) = save.global_lua().ok().flatten() {
+        return tag.path().ok();
+    }
+    if let Some(path) = &config().global.lua {
+        return Some(path.clone());
+    }
+    let extensions = &ttsst::tags::extensions().lua;
+    let key = extensions
+        .iter()
+        .map(|ext| format!("Global.{ext}"))
+        .collect_vec();
+    GLOBAL_PATHS.lock().unwrap().get(&key).cloned()
+}
+
+/// Returns the path of a Global file. If the file is `pinned` in the config, it is used if it is inside of `paths`.
+/// Otherwise `paths` and their subdirectories are searched for files named like one of `files`,
+/// and the user is asked to select one if multiple files have been found.
+///
+/// The file that has been found is used for the rest of the session, as long as it exists and is inside of `paths`.
 fn get_global_path<P: AsRef<Path>, T: AsRef<str>>(
     paths: &[P],
     files: &[T],
+    pinned: Option<&Path>,
+    prompter: &dyn Prompter,
 ) -> Result<Option<PathBuf>> {
-    // Returns a list of joined `paths` and `files` that exist
-    let joined_paths = paths
+    let is_reloaded = |file: &Path| {
+        paths
+            .iter()
+            .any(|path| normalize(file).starts_with(normalize(path.as_ref())))
+    };
+    if let Some(pinned) = pinned {
+        return match is_reloaded(pinned) {
+            true if !long_path(pinned)?.is_file() => Err(anyhow!(
+                "'{}' is set as a Global file in the config, but does not exist",
+                pinned.to_slash_lossy().yellow()
+            )),
+            true => Ok(Some(pinned.into())),
+            false => Ok(None),
+        };
+    }
+
+    let key = files
         .iter()
-        .flat_map(|path| {
-            files
-                .iter()
-                .filter_map(|file| {
-                    let path = path.as_ref();
-                    let file = file.as_ref();
-                    match path.is_dir() {
-                        // If path is a dir, join `file`
-                        true => Some(path.join(file)),
-                        // If path ends with `file`, it is a global file
-                        false if path.file_name() == Some(OsStr::new(file)) => Some(path.into()),
-                        // if path is a file that doesn't end with `file`, ignore it
-                        false => None,
-                    }
-                })
-                .filter(|path| path.exists())
-                .collect_vec()
-        })
+        .map(|file| file.as_ref().to_string())
         .collect_vec();
+    let cached = GLOBAL_PATHS.lock().unwrap().get(&key).cloned();
+    if let Some(cached) = cached.filter(|cached| is_reloaded(cached) && cached.is_file()) {
+        return Ok(Some(cached));
+    }
 
-    match joined_paths.len() {
-        0 | 1 => Ok(joined_paths.first().map(Into::into)),
-        _ => inquire_select(paths).map(Option::Some),
+    let mut found = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        match path.is_dir() {
+            // If path is a dir, search it and its subdirectories
+            true => found.extend(find_files(path, &|path| is_named(path, files))?),
+            // If path ends with `file`, it is a global file
+            false if is_named(path, files) && path.exists() => found.push(path.into()),
+            // if path is a file that doesn't end with `file`, ignore it
+            false => (),
+        }
     }
-}
+    // Nested paths can contain the same file
+    let found = found
+        .into_iter()
+        .unique_by(|path| normalize(path))
+        .collect_vec();
 
-/// Shows a multi selection prompt of `paths`
-fn inquire_select<P: AsRef<Path>>(paths: &[P]) -> Result<PathBuf> {
-    #[derive(Display)]
-    #[display(fmt = "'{}'", "self.0.as_ref().to_slash_lossy().yellow()")]
-    struct DisplayPath<P: AsRef<Path>>(P);
+    let path = match found.len() {
+        0 | 1 => found.first().map(Into::into),
+        _ => Some(prompter.select_path("Select a Global file to use:", found)?),
+    };
+    if let Some(path) = &path {
+        GLOBAL_PATHS.lock().unwrap().insert(key, path.clone());
+    }
+    Ok(path)
+}
 
-    // Wrap `paths` in `DisplayPath` so they can be displayed by the inquire prompt
-    let display_paths = paths.iter().map(DisplayPath).collect_vec();
+/// Returns `true` if the file name of `path` is one of `files`.
+pub fn is_named<T: AsRef<str>>(path: &Path, files: &[T]) -> bool {
+    files
+        .iter()
+        .any(|file| path.file_name() == Some(OsStr::new(file.as_ref())))
+}
 
-    match inquire::Select::new("Select a Global file to use:", display_paths).prompt() {
-        Ok(path) => Ok(path.0.as_ref().into()),
-        Err(err) => Err(err.into()),
+/// Returns the files in `dir` and its subdirectories that match the `predicate`.
+/// Hidden directories, like `.git` or `.ttsst`, are skipped.
+pub fn find_files(dir: &Path, predicate: &dyn Fn(&Path) -> bool) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let entries = fs::read_dir(long_path(dir)?)?.collect::<Result<Vec<_>, _>>()?;
+    for entry in entries.iter().sorted_by_key(|entry| entry.file_name()) {
+        let path = dir.join(entry.file_name());
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        match entry.file_type()?.is_dir() {
+            true if !is_hidden => found.extend(find_files(&path, predicate)?),
+            false if predicate(&path) => found.push(path),
+            _ => (),
+        }
     }
+    Ok(found)
 }
 
 /// Reads a file from the path and replaces every occurrence of `\t` with spaces.
-fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
-    match fs::read_to_string(path) {
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    match fs::read_to_string(long_path(path)?) {
         Ok(content) => Ok(content.replace('\t', "    ")),
         Err(err) => Err(err.into()),
     }
 }
+
+/// Reads a xml file using [`include::read_xml`] and substitutes its placeholders, if `translations` are provided.
+/// The translation file is added to the includes, so that the ui is read again once the translations change.
+fn read_xml<P: AsRef<Path>>(
+    path: P,
+    translations: Option<&Translations>,
+) -> Result<include::Expanded> {
+    let mut expanded = include::read_xml(path)?;
+    if let Some(translations) = translations {
+        // Missing translations are only reported once by `substitute`, unless they are errors
+        if config().strict {
+            let missing = ttsst::l10n::extract_keys(&expanded.script)
+                .into_iter()
+                .filter(|key| translations.get(key).is_none())
+                .map(|key| {
+                    format!(
+                        "missing '{}' translation for {}",
+                        translations.lang,
+                        key.yellow()
+                    )
+                })
+                .collect_vec();
+            strict::report(&missing)?;
+        }
+        expanded.script = translations.substitute(&expanded.script);
+        expanded.includes.push(translations.path.clone());
+    }
+    Ok(expanded)
+}
+
+/// Collects the `%key%` placeholders of all xml files in `paths` and prints them.
+/// If `lang` is provided, missing keys get added to the translation file of that language.
+pub fn extract_l10n<P: AsRef<Path> + Clone>(paths: &[P], lang: Option<String>) -> Result<()> {
+    let mut keys = Vec::new();
+    for path in paths.reduce::<Vec<_>>() {
+        for file in xml_files(path.as_ref())? {
+            keys.extend(ttsst::l10n::extract_keys(&read_file(file)?));
+        }
+    }
+    let keys = keys.into_iter().unique().collect_vec();
+
+    match lang {
+        Some(lang) => {
+            let _deferred = interrupt::defer();
+            let added = Translations::append_missing(&config().l10n_dir, &lang, &keys)?;
+            #[rustfmt::skip]
+            info!("added {} of {} keys to the '{}' translations", added.len(), keys.len(), lang.yellow());
+        }
+        None => keys.iter().for_each(|key| println!("{key}")),
+    }
+
+    Ok(())
+}
+
+/// Returns `path` if it is a xml file, or all xml files inside of `path` if it is a directory.
+fn xml_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(match ttsst::tags::extensions().is_xml(path) {
+            true => vec![path.into()],
+            false => vec![],
+        });
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        files.extend(xml_files(&entry?.path())?);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use ttsst::prompt::NonInteractive;
+    use ttsst::testing::{self, fixtures, mock::MockServer};
+
+    use super::*;
+    use crate::config::test_project;
+
+    /// Writes the scripted fixture to a save in the project, and starts a mock server serving it.
+    fn serve(name: &str) -> (Api, SaveFile) {
+        let path = test_project().root.join(format!("{name}.json"));
+        fs::write(&path, fixtures::SCRIPTED).unwrap();
+
+        let mock = MockServer::bind(0, &path).unwrap();
+        let api = Api::with_ports(mock.port().unwrap(), 0).unwrap();
+        let mock = mock.reply_to(api.port().unwrap());
+        std::thread::spawn(move || mock.run());
+        (api, SaveFile::read_from_path(path).unwrap())
+    }
+
+    fn guids(guids: &[&str]) -> Guids {
+        Guids {
+            guids: Some(guids.iter().map(|guid| guid.to_string()).collect()),
+            all: false,
+            zones: false,
+        }
+    }
+
+    /// Returns the lua script of the object with the `guid` in the save the mock server has written.
+    fn game_script(save_file: &SaveFile, guid: &str) -> String {
+        let game = SaveFile::read_from_path(&save_file.path).unwrap();
+        game.save
+            .objects
+            .find_object(guid)
+            .unwrap()
+            .lua_script
+            .to_string()
+    }
+
+    #[test]
+    fn attach_reload_and_detach_update_the_game() {
+        let (api, mut save_file) = serve("attach");
+        let path = test_project().root.join("attach_deck.lua");
+        fs::write(&path, "print('deck')").unwrap();
+
+        save_file
+            .attach_to(&api, &NonInteractive, &[&path], guids(&["d4e5f6"]))
+            .unwrap();
+        let deck = save_file.save.objects.find_object("d4e5f6").unwrap();
+        assert_eq!(
+            deck.valid_lua().unwrap(),
+            Some(testing::tag("attach_deck.lua"))
+        );
+        assert_eq!(game_script(&save_file, "d4e5f6"), "print('deck')");
+
+        fs::write(&path, "print('reloaded')").unwrap();
+        let report = save_file
+            .reload(
+                &api,
+                &NonInteractive,
+                &["./attach_deck.lua"],
+                ReloadArgs::default(),
+            )
+            .unwrap();
+        assert!(report.objects.contains(&"d4e5f6".to_string()));
+        assert_eq!(game_script(&save_file, "d4e5f6"), "print('reloaded')");
+
+        save_file
+            .detach(&api, &NonInteractive, guids(&["d4e5f6"]))
+            .unwrap();
+        let deck = save_file.save.objects.find_object("d4e5f6").unwrap();
+        assert_eq!(deck.valid_lua().unwrap(), None);
+        assert_eq!(game_script(&save_file, "d4e5f6"), "");
+    }
+
+    #[test]
+    fn unchanged_objects_are_not_rewritten() {
+        let (api, mut save_file) = serve("unchanged");
+        let path = test_project().root.join("unchanged_deck.lua");
+        fs::write(&path, "print('deck')").unwrap();
+
+        // The board has been moved in-game since the save has been read
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&save_file.path).unwrap()).unwrap();
+        json["ObjectStates"][0]["Nickname"] = "Moved".into();
+        fs::write(&save_file.path, json.to_string()).unwrap();
+
+        save_file
+            .attach_to(&api, &NonInteractive, &[&path], guids(&["d4e5f6"]))
+            .unwrap();
+        let game = SaveFile::read_from_path(&save_file.path).unwrap();
+        assert_eq!(
+            game.save.objects.find_object("a1b2c3").unwrap().nickname,
+            "Moved"
+        );
+        assert_eq!(game_script(&save_file, "d4e5f6"), "print('deck')");
+    }
+
+    #[test]
+    fn changed_translations_are_reloaded() {
+        let (api, mut save_file) = serve("translated");
+        let path = test_project().root.join("translated_deck.xml");
+        fs::write(&path, "<Text>%deck.title%</Text>").unwrap();
+        let l10n = test_project().root.join("l10n");
+        fs::create_dir_all(&l10n).unwrap();
+        fs::write(l10n.join("translated.toml"), "deck.title = \"Deck\"").unwrap();
+
+        save_file
+            .attach_to(&api, &NonInteractive, &[&path], guids(&["d4e5f6"]))
+            .unwrap();
+        let args = || ReloadArgs {
+            lang: Some("translated".into()),
+            ..Default::default()
+        };
+        save_file
+            .reload(&api, &NonInteractive, &["./translated_deck.xml"], args())
+            .unwrap();
+        let game = SaveFile::read_from_path(&save_file.path).unwrap();
+        let deck = game.save.objects.find_object("d4e5f6").unwrap();
+        assert_eq!(&*deck.xml_ui, "<Text>Deck</Text>");
+
+        // Only the translation file has changed
+        fs::write(l10n.join("translated.toml"), "deck.title = \"New Deck\"").unwrap();
+        let report = save_file
+            .reload(&api, &NonInteractive, &["./l10n/translated.toml"], args())
+            .unwrap();
+        assert!(report.objects.contains(&"d4e5f6".to_string()));
+        let game = SaveFile::read_from_path(&save_file.path).unwrap();
+        let deck = game.save.objects.find_object("d4e5f6").unwrap();
+        assert_eq!(&*deck.xml_ui, "<Text>New Deck</Text>");
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use ttsst::{Prompter, Save};
+
+use crate::app::SaveFile;
+use crate::config::{project, CONFIG_FILE};
+use crate::history;
+use crate::session::Session;
+use crate::utils::line_count;
+
+/// Prefix of the autosaves Tabletop Simulator writes, e.g. `TS_AutoSave.json`.
+const AUTOSAVE_PREFIX: &str = "TS_AutoSave";
+
+/// Name of the directory Tabletop Simulator keeps its saves in.
+const SAVES_DIR: &str = "Saves";
+
+const KEEP: &str = "Keep the tracked save";
+const REBIND: &str = "Track the autosave instead";
+const ADOPT: &str = "Copy the autosave over the tracked save";
+
+/// Finds autosaves that are newer than the tracked save, shows how they differ from it,
+/// and offers to track the autosave instead, or to copy it over the tracked save.
+///
+/// The tracked save is the save the project is bound to, or the save that has been pushed to most recently.
+pub fn check(prompter: &dyn Prompter) -> Result<()> {
+    let tracked = Session::tracked_save()?;
+    let modified = modified(&tracked)?;
+
+    let autosaves = autosaves(&tracked)?
+        .into_iter()
+        .filter(|path| modified_or_epoch(path) > modified)
+        .collect_vec();
+    if autosaves.is_empty() {
+        info!(
+            "no autosave is newer than '{}'",
+            tracked.to_slash_lossy().yellow()
+        );
+        return Ok(());
+    }
+
+    let save = SaveFile::read_from_path(&tracked)?.save;
+    for autosave in autosaves {
+        #[rustfmt::skip]
+        warn!("'{}' is newer than '{}'", autosave.to_slash_lossy().yellow(), tracked.to_slash_lossy().yellow());
+        print_diff(&save, &SaveFile::read_from_path(&autosave)?.save);
+
+        let message = format!(
+            "What do you want to do with '{}'?",
+            autosave.to_slash_lossy()
+        );
+        let options = vec![KEEP.into(), REBIND.into(), ADOPT.into()];
+        match prompter.select(&message, options)?.as_str() {
+            REBIND => {
+                rebind(&autosave)?;
+                info!(
+                    "the project tracks '{}' now",
+                    autosave.to_slash_lossy().yellow()
+                );
+                return Ok(());
+            }
+            ADOPT => {
+                fs::copy(&autosave, &tracked)?;
+                #[rustfmt::skip]
+                info!("copied '{}' to '{}'", autosave.to_slash_lossy().yellow(), tracked.to_slash_lossy().yellow());
+                return Ok(());
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the autosaves in the directory of the `tracked` save and the directories above it,
+/// up to the directory Tabletop Simulator keeps its saves in.
+/// Returns an error if the `tracked` save isn't inside of that directory.
+fn autosaves(tracked: &Path) -> Result<Vec<PathBuf>> {
+    let is_saves_dir = |dir: &Path| dir.file_name().is_some_and(|name| name == SAVES_DIR);
+    let Some(saves_dir) = tracked.ancestors().skip(1).find(|dir| is_saves_dir(dir)) else {
+        #[rustfmt::skip]
+        bail!("'{}' is not inside of a '{}' directory, which Tabletop Simulator writes its autosaves to", tracked.to_slash_lossy().yellow(), SAVES_DIR);
+    };
+
+    let mut autosaves = Vec::new();
+    for dir in tracked.ancestors().skip(1) {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let is_autosave = name.starts_with(AUTOSAVE_PREFIX) && name.ends_with(".json");
+            if is_autosave && path != tracked {
+                autosaves.push(path);
+            }
+        }
+        if dir == saves_dir {
+            break;
+        }
+    }
+    Ok(autosaves)
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+fn modified_or_epoch(path: &Path) -> SystemTime {
+    modified(path).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Prints the objects that only exist in one of the saves, and the scripts that differ.
+fn print_diff(tracked: &Save, autosave: &Save) {
+    let guids = |save: &Save| save.objects.iter().map(|o| o.guid.clone()).collect_vec();
+    let (tracked_guids, autosave_guids) = (guids(tracked), guids(autosave));
+    let added = autosave_guids
+        .iter()
+        .filter(|guid| !tracked_guids.contains(guid));
+    let removed = tracked_guids
+        .iter()
+        .filter(|guid| !autosave_guids.contains(guid));
+    println!(
+        "{} object(s) added, {} object(s) removed",
+        added.count(),
+        removed.count()
+    );
+
+    for change in history::Entry::new(tracked, autosave).changes {
+        let (insertions, deletions) = line_count(&change.before, &change.after);
+        println!(
+            "{} ({}) {} {}, {}",
+            change.guid.yellow(),
+            change.name.bright_white().bold(),
+            change.file.yellow(),
+            format!("+{insertions}").green(),
+            format!("-{deletions}").red(),
+        );
+    }
+}
+
+/// Binds the project to the save at `path`, keeping the formatting and comments of the rest of the config file.
+pub fn rebind(path: &Path) -> Result<()> {
+    let config_path = project().root.join(CONFIG_FILE);
+    let mut document = match config_path.exists() {
+        true => fs::read_to_string(&config_path)?.parse::<toml_edit::DocumentMut>()?,
+        false => toml_edit::DocumentMut::new(),
+    };
+    document["save"] = toml_edit::value(path.to_slash_lossy().to_string());
+    fs::write(&config_path, document.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autosaves_are_searched_up_to_the_saves_dir() {
+        let root = std::env::temp_dir().join(format!("ttsst-autosave-{}", std::process::id()));
+        let saves = root.join(SAVES_DIR);
+        fs::create_dir_all(saves.join("Mods")).unwrap();
+        let tracked = saves.join("Mods").join("TS_Save_1.json");
+        for path in [
+            &tracked,
+            &saves.join("TS_AutoSave.json"),
+            &root.join("TS_AutoSave.json"),
+        ] {
+            fs::write(path, "{}").unwrap();
+        }
+
+        assert_eq!(
+            autosaves(&tracked).unwrap(),
+            [saves.join("TS_AutoSave.json")]
+        );
+        assert!(autosaves(&root.join("TS_Save_2.json")).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+
+use crate::utils;
+
+/// Expands every pattern of `patterns` into the save files it matches.
+/// A pattern can be a path to a save file, or contain `*` and `?` wildcards in its file name,
+/// e.g. `Saves/TS_Save_*.json`.
+pub fn saves(patterns: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut saves = Vec::new();
+    for pattern in patterns {
+        let file_name = pattern
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if !file_name.contains(['*', '?']) {
+            match pattern.is_file() {
+                true => saves.push(pattern.clone()),
+                #[rustfmt::skip]
+                false => bail!("'{}' is not a save file", pattern.to_slash_lossy().yellow()),
+            }
+            continue;
+        }
+
+        let exprs = utils::glob(&file_name)?;
+        let dir = match pattern.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let matches = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| exprs.is_match(&name.to_string_lossy()))
+            })
+            .sorted()
+            .collect_vec();
+        if matches.is_empty() {
+            warn!(
+                "'{}' matches no save files",
+                pattern.to_slash_lossy().yellow()
+            );
+        }
+        saves.extend(matches);
+    }
+
+    match saves.is_empty() {
+        true => Err(anyhow!("no save files found")),
+        false => Ok(saves.into_iter().unique().collect()),
+    }
+}
+
+/// Runs `f` for every save in `saves`, and prints the summary it returns.
+/// Saves that fail don't stop the other saves from being processed.
+/// Returns an error if any of the saves failed.
+pub fn run<F>(saves: &[PathBuf], mut f: F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<String>,
+{
+    let mut failed = 0;
+    for save in saves {
+        let name = save.to_slash_lossy();
+        match f(save) {
+            Ok(summary) => info!("{}: {}", name.yellow(), summary),
+            Err(err) => {
+                error!("{}: {}", name.yellow(), err);
+                failed += 1;
+            }
+        }
+    }
+
+    match (failed, saves.len()) {
+        (0, _) => Ok(()),
+        (failed, total) => Err(anyhow!("{} of {} save(s) failed", failed, total)),
+    }
+}
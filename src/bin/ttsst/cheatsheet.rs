@@ -0,0 +1,236 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::Save;
+
+use crate::exec;
+use crate::stubs;
+
+/// Matches a global function definition like `function name(a, b)` on a single line, see [`stubs`].
+const FUNCTION: &str = r"^\s*function\s+([A-Za-z_][A-Za-z0-9_]*)\s*\(([^)]*)\)";
+
+/// Replaces the body of the Notebook tab with the `title`, or adds a new tab.
+const NOTEBOOK: &str = r#"for _, tab in ipairs(Notes.getNotebookTabs()) do
+    if tab.title == title then
+        return Notes.editNotebookTab({ index = tab.index, title = title, body = body, color = tab.color })
+    end
+end
+return Notes.addNotebookTab({ title = title, body = body })"#;
+
+/// A global function with a doc comment.
+struct Function {
+    name: String,
+    params: Vec<String>,
+    description: Vec<String>,
+    /// `@param name type description` annotations.
+    param_docs: Vec<Annotation>,
+    /// `@return type description` annotations, which have no name.
+    return_docs: Vec<Annotation>,
+}
+
+/// An `@param` or `@return` annotation of a doc comment.
+struct Annotation {
+    name: String,
+    kind: String,
+    description: String,
+}
+
+impl Annotation {
+    /// Parses the words following `@param`, or following `@return` if `named` is `false`.
+    fn parse(text: &str, named: bool) -> Self {
+        let mut words = text
+            .trim()
+            .splitn(if named { 3 } else { 2 }, char::is_whitespace);
+        let mut next = || words.next().unwrap_or_default().trim().to_string();
+        Self {
+            name: if named { next() } else { String::new() },
+            kind: next(),
+            description: next(),
+        }
+    }
+}
+
+/// The documented functions of a script.
+struct Section {
+    title: String,
+    functions: Vec<Function>,
+}
+
+/// Generates a cheatsheet of the documented global functions of the Global script and the scripts of the objects
+/// with a lua script attached, and writes it to `path` as Markdown.
+///
+/// If `notebook` is set, the cheatsheet is written into the Notebook tab with that title in-game as well,
+/// which is created if it doesn't exist.
+pub fn generate(save: &Save, api: &Api, path: &Path, notebook: Option<&str>) -> Result<()> {
+    let mut sections = vec![Section {
+        title: "Global".into(),
+        functions: functions(&save.lua_script),
+    }];
+    sections.extend(
+        save.objects
+            .iter()
+            .filter(|object| matches!(object.valid_lua(), Ok(Some(_))))
+            .map(|object| Section {
+                title: format!("{} ({})", stubs::name(object), object.guid),
+                functions: functions(&object.lua_script),
+            }),
+    );
+    sections.retain(|section| !section.functions.is_empty());
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, markdown(&sections)?)?;
+    let count = sections.iter().map(|s| s.functions.len()).sum::<usize>();
+    #[rustfmt::skip]
+    info!("generated a cheatsheet of {} function(s) in '{}'", count, path.to_slash_lossy().yellow());
+
+    if let Some(title) = notebook {
+        let script = format!(
+            "local title, body = {}, {}\n{NOTEBOOK}",
+            long_string(title),
+            long_string(&notebook_text(&sections)?)
+        );
+        exec::execute(api, script)?;
+        info!(
+            "wrote the cheatsheet into the '{}' notebook tab",
+            title.yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the global functions of `script` that are preceded by a comment.
+fn functions(script: &str) -> Vec<Function> {
+    let exprs = Regex::new(FUNCTION).unwrap();
+    let mut functions: Vec<Function> = Vec::new();
+    let mut comment = Vec::new();
+    for line in script.lines() {
+        let trimmed = line.trim();
+        // Files that have been included aren't documented by the comments that enclose them
+        if trimmed.starts_with("----#include") {
+            comment.clear();
+            continue;
+        }
+        if let Some(text) = trimmed.strip_prefix("--") {
+            comment.push(text.trim_start_matches('-').trim());
+            continue;
+        }
+        if let Some(captures) = exprs.captures(line) {
+            let name = &captures[1];
+            if !comment.is_empty() && functions.iter().all(|f| f.name != name) {
+                functions.push(function(name, &captures[2], &comment));
+            }
+        }
+        comment.clear();
+    }
+    functions
+}
+
+/// Parses the lines of the doc `comment` of the function `name`, using the `@param` and `@return` annotations of EmmyLua.
+fn function(name: &str, params: &str, comment: &[&str]) -> Function {
+    let mut function = Function {
+        name: name.into(),
+        params: params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(String::from)
+            .collect(),
+        description: Vec::new(),
+        param_docs: Vec::new(),
+        return_docs: Vec::new(),
+    };
+    for line in comment {
+        if let Some(param) = line.strip_prefix("@param ") {
+            function.param_docs.push(Annotation::parse(param, true));
+        } else if let Some(ret) = line.strip_prefix("@return ") {
+            function.return_docs.push(Annotation::parse(ret, false));
+        } else if !line.starts_with('@') && !line.is_empty() {
+            function.description.push(line.to_string());
+        }
+    }
+    function
+}
+
+/// Formats the cheatsheet as Markdown.
+fn markdown(sections: &[Section]) -> Result<String> {
+    let mut md = String::new();
+    writeln!(md, "# Cheatsheet")?;
+    writeln!(md)?;
+    writeln!(md, "Generated by `ttsst cheatsheet`, do not edit.")?;
+    for section in sections {
+        writeln!(md)?;
+        writeln!(md, "## {}", section.title)?;
+        for function in &section.functions {
+            writeln!(md)?;
+            writeln!(
+                md,
+                "### `{}({})`",
+                function.name,
+                function.params.join(", ")
+            )?;
+            if !function.description.is_empty() {
+                writeln!(md)?;
+                writeln!(md, "{}", function.description.join(" "))?;
+            }
+            if !function.param_docs.is_empty() || !function.return_docs.is_empty() {
+                writeln!(md)?;
+            }
+            for param in &function.param_docs {
+                let line = format!(
+                    "- `{}` (`{}`) {}",
+                    param.name, param.kind, param.description
+                );
+                writeln!(md, "{}", line.trim_end())?;
+            }
+            for ret in &function.return_docs {
+                let line = format!("- Returns `{}` {}", ret.kind, ret.description);
+                writeln!(md, "{}", line.trim_end())?;
+            }
+        }
+    }
+    Ok(md)
+}
+
+/// Formats the cheatsheet for a Notebook tab, which supports BBCode instead of Markdown.
+fn notebook_text(sections: &[Section]) -> Result<String> {
+    let mut text = String::new();
+    for section in sections {
+        writeln!(text, "[b]{}[/b]", section.title)?;
+        for function in &section.functions {
+            #[rustfmt::skip]
+            writeln!(text, "  [i]{}({})[/i]", function.name, function.params.join(", "))?;
+            if !function.description.is_empty() {
+                writeln!(text, "    {}", function.description.join(" "))?;
+            }
+            for param in &function.param_docs {
+                let line = format!("    {}: {} {}", param.name, param.kind, param.description);
+                writeln!(text, "{}", line.trim_end())?;
+            }
+            for ret in &function.return_docs {
+                let line = format!("    returns {} {}", ret.kind, ret.description);
+                writeln!(text, "{}", line.trim_end())?;
+            }
+        }
+        writeln!(text)?;
+    }
+    Ok(text.trim_end().into())
+}
+
+/// Returns `text` as a lua long string, e.g. `[==[text]==]`, with a level that doesn't occur in `text`.
+fn long_string(text: &str) -> String {
+    let level = (0..)
+        .map(|level| "=".repeat(level))
+        .find(|level| !text.contains(&format!("]{level}]")))
+        .unwrap();
+    // A newline right after the opening bracket is skipped by lua
+    format!("[{level}[\n{text}]{level}]")
+}
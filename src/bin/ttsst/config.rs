@@ -0,0 +1,477 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use ttsst::prompt::Layout;
+use ttsst::tags::Extensions;
+
+use crate::console::{PrintLevel, RemovePolicy};
+use crate::utils;
+
+/// Name of the config file that marks the root of a project.
+pub const CONFIG_FILE: &str = "ttsst.toml";
+
+static PROJECT: OnceLock<Project> = OnceLock::new();
+
+/// Settings read from the `ttsst.toml` file in the project root.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Directory containing the `<lang>.toml` or `<lang>.csv` translation files.
+    pub l10n_dir: PathBuf,
+    /// Directory containing templates for new lua scripts and xml ui files.
+    pub templates_dir: PathBuf,
+    /// Save file the project belongs to.
+    pub save: Option<PathBuf>,
+    /// Prefix of the tags of this project, so that multiple projects can share a save.
+    pub namespace: Option<String>,
+    pub extensions: ExtensionsConfig,
+    pub global: GlobalConfig,
+    /// Directories that are searched for files included using `#include`.
+    pub include_dirs: Vec<PathBuf>,
+    pub console: ConsoleConfig,
+    pub log: LogConfig,
+    pub write: WriteConfig,
+    pub prompt: PromptConfig,
+    /// Named lua snippets that can be executed using `@<name>`.
+    pub snippets: BTreeMap<String, String>,
+    /// Names that can be used instead of the GUID of an object.
+    pub aliases: BTreeMap<String, String>,
+    /// Overwrite scripts that have been edited in-game without asking.
+    pub force: bool,
+    /// Fail instead of warning about problems that would leave the save inconsistent, see [`strict`](crate::strict).
+    pub strict: bool,
+    /// Paths that are watched in addition to the paths passed to `watch`.
+    pub watch: Vec<WatchRoot>,
+    /// Objects that are reloaded while watching if a file matching a pattern changes, e.g. a file bundled into theirs.
+    /// Targets are `Global`, GUIDs, aliases or tags.
+    pub reload_targets: BTreeMap<String, Vec<String>>,
+    /// Named lua expressions that are evaluated after every reload while watching.
+    pub probes: BTreeMap<String, String>,
+    /// Append the values of the probes to this csv file.
+    pub probe_log: Option<PathBuf>,
+    /// Keep metadata of the objects in `.ttsst/objects.toml`, see [`metadata`](crate::metadata).
+    pub metadata: bool,
+    /// Inject the helper module into the Global script, see [`helper`](crate::helper).
+    pub helper: bool,
+    /// Seconds to wait for an answer of the game, before giving up. Waits forever if unset.
+    pub timeout: Option<u64>,
+    /// How often a message is sent again, if its answer times out.
+    pub retries: u32,
+    pub dedup: DedupConfig,
+    pub transform: TransformConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            l10n_dir: PathBuf::from("l10n"),
+            templates_dir: PathBuf::from("templates"),
+            save: None,
+            namespace: None,
+            extensions: ExtensionsConfig::default(),
+            global: GlobalConfig::default(),
+            include_dirs: vec![PathBuf::from(".")],
+            console: ConsoleConfig::default(),
+            log: LogConfig::default(),
+            write: WriteConfig::default(),
+            prompt: PromptConfig::default(),
+            snippets: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            force: false,
+            strict: false,
+            watch: Vec::new(),
+            reload_targets: BTreeMap::new(),
+            probes: BTreeMap::new(),
+            probe_log: None,
+            metadata: false,
+            helper: false,
+            timeout: None,
+            retries: 0,
+            dedup: DedupConfig::default(),
+            transform: TransformConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the [`timeout`](Self::timeout) as a duration.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+}
+
+/// Settings of the `[extensions]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ExtensionsConfig {
+    /// File extensions of lua scripts, without the leading dot.
+    pub lua: Vec<String>,
+    /// File extensions of xml ui files, without the leading dot, e.g. `xml.jinja`.
+    pub xml: Vec<String>,
+}
+
+impl Default for ExtensionsConfig {
+    fn default() -> Self {
+        let Extensions { lua, xml } = Extensions::default();
+        Self { lua, xml }
+    }
+}
+
+/// Settings of the `[global]` table.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GlobalConfig {
+    /// Lua script of the save, instead of searching for `Global.lua` or `Global.ttslua`.
+    pub lua: Option<PathBuf>,
+    /// Xml ui of the save, instead of searching for `Global.xml`.
+    pub xml: Option<PathBuf>,
+}
+
+/// Settings of the `[log]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogConfig {
+    /// Write log messages to this file in addition to the console.
+    pub file: Option<PathBuf>,
+    /// Rotate the log file once it exceeds this size in bytes.
+    pub max_size: Option<u64>,
+    /// Rotate the log file once a day.
+    pub daily: bool,
+    /// Number of rotated log files that are kept.
+    pub retention: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            max_size: Some(10 * 1024 * 1024),
+            daily: false,
+            retention: 5,
+        }
+    }
+}
+
+/// Settings of the `[write]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WriteConfig {
+    /// Sort the objects by GUID and round floating point numbers when writing the save.
+    pub normalize: bool,
+    /// Number of decimal places floating point numbers are rounded to.
+    pub decimals: Option<u32>,
+}
+
+impl Default for WriteConfig {
+    fn default() -> Self {
+        Self {
+            normalize: false,
+            decimals: Some(4),
+        }
+    }
+}
+
+/// Settings of the `[dedup]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DedupConfig {
+    /// Replace the scripts of objects sharing the same file with a stub that loads it from Global, see [`dedup`](crate::dedup).
+    pub enabled: bool,
+    /// Minimum number of objects that have to share a script.
+    pub min_objects: usize,
+    /// Minimum size of a shared script in bytes.
+    pub min_size: usize,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_objects: 3,
+            min_size: 1024,
+        }
+    }
+}
+
+/// Settings of the `[transform]` table.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TransformConfig {
+    /// Names of the transformers lua scripts pass through when they are read, in this order, see [`transform`](crate::transform).
+    pub pipeline: Vec<String>,
+    /// Values the `constants` transformer defines as locals at the start of every script.
+    pub constants: BTreeMap<String, toml::Value>,
+    /// External commands that can be used in the pipeline by their name.
+    pub exec: BTreeMap<String, ExecTransformer>,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        Self {
+            pipeline: vec!["bundle".into()],
+            constants: BTreeMap::new(),
+            exec: BTreeMap::new(),
+        }
+    }
+}
+
+/// Settings of an external command in the `[transform.exec]` table.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecTransformer {
+    /// Command that is run by the shell, with `{file}` replaced by the path of the file.
+    /// The script is written to its stdin, and the transformed script is read from its stdout.
+    pub command: String,
+    /// Patterns of the files the command is applied to, e.g. `*.fnl`. Applies to every file if empty.
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// Settings of the `[console]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ConsoleConfig {
+    /// Format of the timestamp printed in front of every message.
+    /// See <https://docs.rs/chrono/latest/chrono/format/strftime/index.html>.
+    pub timestamp_format: String,
+    /// Use UTC instead of the local time.
+    pub utc: bool,
+    /// What to do with objects whose file gets deleted or renamed while watching.
+    pub on_remove: RemovePolicy,
+    /// Template of the links added to errors, e.g. `vscode://file/{path}:{line}:{col}`.
+    pub link: Option<String>,
+    /// Only show messages printed by scripts at this level or above.
+    pub level: Option<PrintLevel>,
+    /// Milliseconds a changed path has to wait after it caused a reload, before it causes another one while watching.
+    pub cooldown: u64,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        Self {
+            timestamp_format: "%H:%M:%S".into(),
+            utc: false,
+            on_remove: RemovePolicy::default(),
+            link: None,
+            level: None,
+            cooldown: 1000,
+        }
+    }
+}
+
+/// Settings of the `[prompt]` table.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PromptConfig {
+    /// Display objects as `<GUID> (<Name>) using <Tags>`, or in aligned columns.
+    pub layout: Layout,
+    /// Truncate objects to this many columns instead of the width of the terminal.
+    pub width: Option<usize>,
+    /// Names of the objects that are hidden in selection prompts, unless `--all` is used.
+    pub hidden: Vec<String>,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            layout: Layout::default(),
+            width: None,
+            hidden: ttsst::objects::HIDDEN
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// A path that is watched for changes, set by a `[[watch]]` table or `--path <PATH>[:<OPTIONS>]`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct WatchRoot {
+    pub path: PathBuf,
+    /// Watch the subdirectories of `path` as well.
+    #[serde(default = "default_recursive")]
+    pub recursive: bool,
+    /// Files matching one of these patterns are not reloaded, e.g. `*.bak` or `vendor/*`.
+    #[serde(default)]
+    pub ignore: Vec<Glob>,
+    /// Shell command that runs after a reload caused by a file in `path`.
+    pub exec: Option<String>,
+}
+
+fn default_recursive() -> bool {
+    true
+}
+
+impl WatchRoot {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            recursive: true,
+            ignore: Vec::new(),
+            exec: None,
+        }
+    }
+
+    /// Returns true if `path` is inside of this root and not ignored.
+    /// Patterns are matched against the path relative to the root and the file name.
+    pub fn contains(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.path) else {
+            return false;
+        };
+        if !self.recursive && relative.components().count() > 1 {
+            return false;
+        }
+
+        let relative = relative.to_slash_lossy();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        !(self.ignore.iter()).any(|glob| glob.0.is_match(&relative) || glob.0.is_match(&file_name))
+    }
+}
+
+/// A pattern with `*` and `?` wildcards, which is compiled once when it is read, see [`utils::glob`].
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "String")]
+pub struct Glob(Regex);
+
+impl TryFrom<String> for Glob {
+    type Error = anyhow::Error;
+
+    fn try_from(pattern: String) -> Result<Self> {
+        Ok(Self(utils::glob(&pattern)?))
+    }
+}
+
+/// The project ttsst is running in.
+#[derive(Debug)]
+pub struct Project {
+    /// Directory containing the `ttsst.toml` file, or the current directory if none exists.
+    pub root: PathBuf,
+    pub config: Config,
+}
+
+impl Project {
+    /// Searches for a `ttsst.toml` file in the current directory and its parents.
+    /// If none exists, the current directory is used as the project root.
+    pub fn discover() -> Result<Self> {
+        let current_dir = std::env::current_dir()?;
+        let root = current_dir
+            .ancestors()
+            .find(|dir| dir.join(CONFIG_FILE).is_file())
+            .map(Path::to_path_buf);
+
+        match root {
+            Some(root) => {
+                let config = toml::from_str(&fs::read_to_string(root.join(CONFIG_FILE))?)?;
+                debug!("using project root {}", root.display());
+                Ok(Self { root, config })
+            }
+            None => Ok(Self {
+                root: current_dir,
+                config: Config::default(),
+            }),
+        }
+    }
+
+    /// Converts `path`, which is relative to the current directory, into a path relative to the project root.
+    /// The returned path uses the `.\<FilePath>` form that tags are created from.
+    pub fn relative<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = normalize(&std::env::current_dir()?.join(path));
+        match path.strip_prefix(&self.root) {
+            Ok(path) => Ok(PathBuf::from(".\\").join(path)),
+            #[rustfmt::skip]
+            Err(_) => Err(anyhow!("'{}' is outside of the project root '{}'", path.to_slash_lossy().yellow(), self.root.to_slash_lossy().yellow())),
+        }
+    }
+
+    /// Converts `path`, which is relative to the current directory, into an absolute path.
+    pub fn absolute<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        Ok(normalize(&std::env::current_dir()?.join(path)))
+    }
+
+    /// Changes the current directory to the project root,
+    /// so that tags get resolved relative to the root.
+    pub fn enter(self) -> Result<&'static Project> {
+        std::env::set_current_dir(&self.root)?;
+        ttsst::tags::set_root(&self.root)?;
+        Ok(PROJECT.get_or_init(|| self))
+    }
+}
+
+/// Returns the project that has been entered using [`Project::enter`].
+pub fn project() -> &'static Project {
+    PROJECT.get().expect("project has not been entered")
+}
+
+/// Returns the config of the current project.
+pub fn config() -> &'static Config {
+    &project().config
+}
+
+/// Enters a project with the default config in a temporary directory, which the tests of the binary share.
+#[cfg(test)]
+pub fn test_project() -> &'static Project {
+    if let Some(project) = PROJECT.get() {
+        return project;
+    }
+    let root = std::env::temp_dir().join(format!("ttsst-test-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let config = Config::default();
+    Project { root, config }.enter().unwrap()
+}
+
+/// Removes `.` and `..` components from `path` without accessing the file system.
+/// The `.\` of paths in the `.\<FilePath>` form is removed on every platform,
+/// but other `\` are only separators on Windows, and verbatim paths like `\\?\C:\<Path>` are returned unchanged.
+pub fn normalize(path: &Path) -> PathBuf {
+    if let Some(Component::Prefix(prefix)) = path.components().next() {
+        if prefix.kind().is_verbatim() {
+            return path.into();
+        }
+    }
+    let path = path.strip_prefix(".\\").unwrap_or(path);
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_normalized_without_rewriting_file_names() {
+        assert_eq!(
+            normalize(Path::new("./lua/../Global.lua")),
+            Path::new("Global.lua")
+        );
+        let relative = PathBuf::from(".\\").join("lua/deck.lua");
+        assert_eq!(normalize(&relative), Path::new("lua/deck.lua"));
+        if cfg!(windows) {
+            let verbatim = Path::new(r"\\?\C:\lua\..\deck.lua");
+            assert_eq!(normalize(verbatim), verbatim);
+        } else {
+            assert_eq!(
+                normalize(Path::new("lua/a\\b.lua")),
+                Path::new("lua/a\\b.lua")
+            );
+        }
+    }
+}
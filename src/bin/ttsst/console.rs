@@ -1,110 +1,924 @@
-use std::path::Path;
-use std::time::Duration;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::IsTerminal;
+use std::net::{Ipv4Addr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use clap::ValueEnum;
 use colored::*;
 use itertools::Itertools;
 use log::*;
-use notify::RecursiveMode;
+use notify::{PollWatcher, RecursiveMode, Watcher};
 use notify_debouncer_mini::{self as debouncer};
-use serde_json::json;
-use tts_external_api::messages::{Answer, MessageReload};
-use tts_external_api::ExternalEditorApi as Api;
-use ttsst::Tag;
+use path_slash::PathExt;
+use regex::Regex;
+use serde::Deserialize;
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::api::{Answer, AnswerError, AnswerGameSaved, AnswerReload};
+use ttsst::{Objects, Prompter, Save, Tag};
 
-use crate::app::SaveFile;
-use crate::utils::StripCurrentDir;
-use crate::ReloadArgs;
+use crate::app::{self, find_files, SaveFile};
+use crate::config::{config, normalize, ConsoleConfig, WatchRoot};
+use crate::dispatch::{DispatchedApi, Dispatcher};
+use crate::progress::ReloadReport;
+use crate::session::Session;
+use crate::utils::{self, line_count, StripCurrentDir};
+use crate::{autosave, guard, include, interrupt, metadata, probe, status};
+use crate::{ConsoleArgs, ReloadArgs, WatchArgs};
+
+/// How often the watcher thread checks whether it has to stop.
+const STOP_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The format used to print error messages.
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum ErrorFormat {
+    /// Print errors the same way as they are shown in-game
+    #[default]
+    Default,
+    /// Print errors as `path:line:col: error: message` to stderr
+    Ide,
+}
+
+/// What to do with objects whose file gets deleted or renamed while watching.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemovePolicy {
+    /// Ask whether the missing file should be detached from the objects
+    #[default]
+    Prompt,
+    /// Warn about objects that use a missing file
+    Warn,
+    /// Replace the tag, if the file has been renamed
+    Rename,
+    /// Detach the missing file from the objects
+    Detach,
+}
+
+/// Level of a message printed by a script, set using a prefix like `[debug] ...`.
+/// Messages without a prefix are printed at the `info` level.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrintLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl PrintLevel {
+    /// Returns the level of a printed `message`, using its prefix.
+    fn of(message: &str) -> Self {
+        let prefix = message
+            .strip_prefix('[')
+            .and_then(|message| message.split_once(']'))
+            .map(|(prefix, _)| prefix.to_ascii_lowercase());
+        match prefix.as_deref() {
+            Some("trace") => PrintLevel::Trace,
+            Some("debug") => PrintLevel::Debug,
+            Some("warn" | "warning") => PrintLevel::Warn,
+            Some("error") => PrintLevel::Error,
+            _ => PrintLevel::Info,
+        }
+    }
+
+    /// Colors a `message` according to the level.
+    fn paint(&self, message: &str) -> String {
+        match self {
+            PrintLevel::Trace => message.dimmed().to_string(),
+            PrintLevel::Debug => message.blue().to_string(),
+            PrintLevel::Info => message.bright_white().to_string(),
+            PrintLevel::Warn => message.yellow().to_string(),
+            PrintLevel::Error => message.red().to_string(),
+        }
+    }
+}
 
 /// Show print, log and error messages in the console.
 /// If `--watch` mode is enabled, files in that directory will we watched and reloaded on change.
-pub fn start<P>(save_file: &SaveFile, api: &Api, paths: Option<&[P]>) -> Result<!>
-where
-    P: AsRef<Path> + Clone + Sync,
-{
-    std::thread::scope(|scope| match paths {
-        Some(paths) => scope.spawn(|| watch(save_file, api, paths)).join().unwrap(),
-        None => scope.spawn(|| read(save_file, api, paths)).join().unwrap(),
-    })
-}
-
-/// Spawns a new thread that listens to the print, log and error messages in the console.
-/// All messages get forwarded to port 39997 so that they can be used again.
-fn read<P>(save_file: &SaveFile, api: &Api, paths: Option<&[P]>) -> Result<!>
-where
-    P: AsRef<Path> + Clone,
-{
-    loop {
-        let message = api.read();
+///
+/// Incoming messages are read by a single [`Dispatcher`] thread,
+/// and then forwarded to the threads that print and reload.
+/// The threads share the path of the active save, which changes if the user switches to a different save,
+/// and its most recent state, which the reload thread updates whenever it pushes or reads the save.
+pub fn start(
+    save_file: &SaveFile,
+    api: &Api,
+    prompter: &dyn Prompter,
+    watch_args: Option<WatchArgs>,
+    args: ConsoleArgs,
+) -> Result<()> {
+    let dispatcher = Dispatcher::new();
+    let messages = dispatcher.subscribe(|_| true);
+    let active = &RwLock::new(save_file.path.clone());
+    let latest = &RwLock::new(save_file.save.clone());
+    let roots = &watch_args
+        .as_ref()
+        .map(WatchArgs::roots)
+        .unwrap_or_default();
+    let changed = &Mutex::new(Changes::default());
+    // The first error of a thread, which stops the other threads
+    let failure = &Mutex::new(None);
+    let stop_on_error = |result: Result<()>| {
+        if let Err(err) = result {
+            failure.lock().unwrap().get_or_insert(err);
+            interrupt::request();
+        }
+    };
+
+    // Wake the dispatcher up when the process gets interrupted, so that it stops
+    let mut addr = api.local_addr()?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+    interrupt::on_interrupt(move || {
+        let _ = TcpStream::connect(addr);
+    });
+
+    if watch_args.as_ref().is_some_and(|args| !args.no_status) {
+        status::init(&save_file.save.name, watched_files(roots).len());
+    }
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| stop_on_error(dispatcher.run(api)));
+
+        if let Some(watch_args) = &watch_args {
+            let reloads = dispatcher.subscribe(|answer| {
+                matches!(answer, Answer::AnswerReload(_) | Answer::AnswerGameSaved(_))
+            });
+            let dispatched = DispatchedApi {
+                api,
+                dispatcher: &dispatcher,
+                script_states: Default::default(),
+                latest,
+            };
+            #[rustfmt::skip]
+            scope.spawn(move || stop_on_error(reload(active, &dispatched, prompter, watch_args, roots, changed, reloads)));
+            #[rustfmt::skip]
+            scope.spawn(|| stop_on_error(watch(active, api, &dispatcher, prompter, watch_args, roots, changed)));
+
+            if let Err(err) = resume(save_file, api, &dispatcher) {
+                warn!("could not resume the previous session: {}", err);
+            }
+        }
+
+        stop_on_error(print(
+            active,
+            latest,
+            &dispatcher,
+            messages,
+            watch_args.is_some(),
+            &args,
+        ));
+    });
+    status::stop();
+
+    let failure = failure.lock().unwrap().take();
+    match failure {
+        Some(err) => Err(err),
+        None => {
+            info!("stopped");
+            Ok(())
+        }
+    }
+}
+
+/// Reloads the save if the previous session has been stopped while reloading,
+/// or if attached files have changed since they have been pushed.
+fn resume(save_file: &SaveFile, api: &Api, dispatcher: &Dispatcher) -> Result<()> {
+    let session = Session::load();
+    if let Some(previous) = session
+        .save
+        .as_ref()
+        .filter(|save| **save != save_file.path)
+    {
+        #[rustfmt::skip]
+        info!("the previous session pushed to '{}', changes made since then are not detected", previous.to_slash_lossy().yellow());
+        return Ok(());
+    }
+
+    let changed = session.changed_tags(&save_file.path, &save_file.save);
+    if !changed.is_empty() {
+        #[rustfmt::skip]
+        info!("{} file(s) have changed since they have been pushed, reloading", changed.len());
+        dispatcher.queue_reload(api)?;
+    } else if session.pending {
+        info!("the previous session has been stopped while reloading, reloading again");
+        dispatcher.queue_reload(api)?;
+    }
+    Ok(())
+}
+
+/// Reload changes if the save gets reloaded while in watch mode.
+///
+/// If a different save has been loaded, the user is asked whether to switch to it.
+/// Otherwise scripts are not pushed into that save, until the active save is loaded again.
+///
+/// After a reload that updated the save, the `--exec` command and the commands of the `changed` roots are run,
+/// and the probes of the config are evaluated.
+///
+/// If the game has been saved to a different file, the active save follows it, see [`saved`].
+fn reload(
+    active: &RwLock<PathBuf>,
+    api: &DispatchedApi,
+    prompter: &dyn Prompter,
+    args: &WatchArgs,
+    roots: &[WatchRoot],
+    changed: &Mutex<Changes>,
+    reloads: Receiver<Arc<Answer>>,
+) -> Result<()> {
+    let paths = roots.iter().map(|root| root.path.clone()).collect_vec();
+    // Saves the user has declined to switch to
+    let mut declined = Vec::new();
+    // The dispatcher disconnects when it stops
+    while let Ok(message) = reloads.recv() {
+        let answer = match message.as_ref() {
+            Answer::AnswerReload(answer) => answer,
+            Answer::AnswerGameSaved(answer) => {
+                saved(active, answer, prompter)?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        // Check if the save file of the incoming answer is still the same save file
+        let answer_path = PathBuf::from(&answer.save_path);
+        let active_path = active.read().unwrap().clone();
+        if answer_path != active_path {
+            let (from, to) = (active_path.to_slash_lossy(), answer_path.to_slash_lossy());
+            warn!("a different save has been loaded: '{}'", to.yellow());
+
+            let message = format!("Do you want to switch from '{}' to '{}'?", from, to);
+            if declined.contains(&answer_path) || !prompter.confirm(&message, false)? {
+                #[rustfmt::skip]
+                warn!("not reloading '{}', scripts are only pushed to '{}'", to.yellow(), from.yellow());
+                if !declined.contains(&answer_path) {
+                    declined.push(answer_path);
+                }
+                continue;
+            }
+
+            info!("switched to '{}'", to.yellow());
+            *active.write().unwrap() = answer_path.clone();
+        }
+
+        *api.script_states.lock().unwrap() = answer.script_states.clone();
+        let mut answer_save_file = SaveFile::read_from_path(&answer_path)?;
+        *api.latest.write().unwrap() = answer_save_file.save.clone();
+
+        let changes = std::mem::take(&mut *changed.lock().unwrap());
+        let reload_args = ReloadArgs {
+            targets: changes.targets.into_iter().collect(),
+            ..Default::default()
+        };
+        let result = answer_save_file.reload(api, prompter, &paths, reload_args);
+        status::set_save(&answer_save_file.save.name);
+        status::set_changed(metadata::changed(&answer_save_file.save));
+        status::reloaded(&result);
+        let report = result?;
+        if report.changed {
+            let commands = changes.roots.iter().filter_map(|&i| roots[i].exec.as_ref());
+            for command in args.exec.iter().chain(commands).unique() {
+                run_command(command, &report);
+            }
+            if let Err(err) = probe::run(api) {
+                warn!("could not log the probes: {}", err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Makes the file the game has been saved to the active save, if it differs from it, e.g. after using "Save As",
+/// since the game keeps that file loaded. Older versions of the game don't send the path, so nothing is done.
+///
+/// The session moves to the new save, and if the project is bound to the previous save in the config,
+/// the user is asked whether to bind it to the new save instead.
+fn saved(
+    active: &RwLock<PathBuf>,
+    answer: &AnswerGameSaved,
+    prompter: &dyn Prompter,
+) -> Result<()> {
+    let Some(path) = answer.save_path.as_ref().map(PathBuf::from) else {
+        return Ok(());
+    };
+    let previous = active.read().unwrap().clone();
+    if normalize(&path) == normalize(&previous) {
+        return Ok(());
+    }
+
+    info!(
+        "the game has been saved to '{}', scripts are pushed to it from now on",
+        path.to_slash_lossy().yellow()
+    );
+    *active.write().unwrap() = path.clone();
+    Session::moved(&previous, &path)?;
+
+    let bound = config().save.as_deref().map(normalize);
+    if bound.is_some_and(|bound| bound == normalize(&previous)) {
+        let message = format!(
+            "Do you want to bind the project to '{}'?",
+            path.to_slash_lossy()
+        );
+        if prompter.confirm(&message, false)? {
+            autosave::rebind(&path)?;
+            info!(
+                "the project tracks '{}' now",
+                path.to_slash_lossy().yellow()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Does what a reload triggered by a change while watching would do, but only once:
+/// Pushes the files of the `roots`, runs the `--exec` command and the commands of the roots,
+/// and evaluates the probes of the config.
+///
+/// Returns an error if the reload or one of the commands failed, so that it can be used as a build step.
+pub fn run_once(
+    save_file: &mut SaveFile,
+    api: &Api,
+    prompter: &dyn Prompter,
+    args: &WatchArgs,
+) -> Result<()> {
+    let roots = args.roots();
+    let paths = roots.iter().map(|root| root.path.clone()).collect_vec();
+    let report = save_file.reload(api, prompter, &paths, ReloadArgs::default())?;
+    report.print(args.output)?;
+    if !report.changed {
+        info!("all files are up to date");
+        return Ok(());
+    }
+
+    let commands = roots.iter().filter_map(|root| root.exec.as_ref());
+    let failed = (args.exec.iter().chain(commands).unique())
+        .filter(|command| !run_command(command, &report))
+        .count();
+    if let Err(err) = probe::run(api) {
+        warn!("could not log the probes: {}", err);
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(anyhow!("{} command(s) failed", failed)),
+    }
+}
+
+/// Runs `command` using the shell of the platform, waits for it to finish, and returns whether it succeeded.
+/// A failing command is only logged, so that watching continues.
+///
+/// The `report` of the reload that preceded the command is passed as JSON in the `TTSST_RELOAD` environment variable.
+fn run_command(command: &str, report: &ReloadReport) -> bool {
+    let (shell, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+
+    info!("running '{}'", command.yellow());
+    match std::process::Command::new(shell)
+        .args([flag, command])
+        .env(
+            "TTSST_RELOAD",
+            serde_json::to_string(report).unwrap_or_default(),
+        )
+        .status()
+    {
+        Ok(status) if status.success() => return true,
+        Ok(status) => warn!("'{}' failed with {}", command.yellow(), status),
+        Err(err) => warn!("could not run '{}': {}", command.yellow(), err),
+    }
+    false
+}
+
+/// Prints the print, log and error messages received from the dispatcher.
+/// Objects are looked up in the `latest` state of the save, which is read again after every reload unless `watching`,
+/// since the reload thread keeps it up to date otherwise.
+fn print(
+    active: &RwLock<PathBuf>,
+    latest: &RwLock<Save>,
+    dispatcher: &Dispatcher,
+    messages: Receiver<Arc<Answer>>,
+    watching: bool,
+    args: &ConsoleArgs,
+) -> Result<()> {
+    let mut clock = Clock::new(args);
+    let link = args.link.as_ref().or(config().console.link.as_ref());
+    let level = args
+        .level
+        .or(config().console.level)
+        .unwrap_or(PrintLevel::Trace);
+    // The dispatcher disconnects when it stops
+    while let Ok(received) = messages.recv() {
+        let message = received.as_ref();
 
-        // Reload changes if the save gets reloaded while in watch mode
-        if let (Answer::AnswerReload(answer), Some(paths)) = (&message, &paths) {
-            // Check if the save file of the incoming answer is still the same save file
-            let mut answer_save_file = SaveFile::read_from_path(&answer.save_path)?;
-            if answer_save_file.path != save_file.path {
-                error!("Different save file has been loaded!");
+        // Skip messages printed by scripts below the level
+        if let Answer::AnswerPrint(answer) = message {
+            if PrintLevel::of(&answer.message) < level {
+                continue;
             }
+        }
+
+        // Show in-game changes if the save gets reloaded
+        if let Answer::AnswerReload(answer) = message {
+            if watching && std::io::stdout().is_terminal() {
+                // Clear screen and put the cursor at the first row and first column of the screen
+                print!("\x1B[2J\x1B[1;1H");
+                status::redraw();
+            }
+            let active = active.read().unwrap().clone();
+            if !watching && Path::new(&answer.save_path) == active {
+                if let Ok(save_file) = SaveFile::read_from_path(active) {
+                    *latest.write().unwrap() = save_file.save;
+                }
+            }
+            // Reloads sent by ttsst only contain the scripts that have just been pushed
+            if !dispatcher.is_own_reload(&received) {
+                print_diverged(answer, &latest.read().unwrap());
+            }
+        }
+
+        // Use the most recent state of the save to look up the object that caused an error
+        let save = &*latest.read().unwrap();
 
-            // Clear screen and put the cursor at the first row and first column of the screen
-            print!("\x1B[2J\x1B[1;1H");
-            answer_save_file.reload(api, paths, ReloadArgs { guid: None })?;
+        // Print errors in a format that can be parsed by editors
+        if let (Answer::AnswerError(answer), ErrorFormat::Ide) = (message, args.error_format) {
+            eprintln!("{}", ide_error(answer, save));
+            continue;
         }
 
         // Print messages
-        if let Some(msg) = message.message() {
-            let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        if let Some(mut msg) = message.message(save) {
+            if let (Answer::AnswerError(answer), Some(template)) = (message, link) {
+                if let Some(url) = Location::of(answer, save).link(template) {
+                    msg = hyperlink(msg, &url);
+                }
+            }
+            let time = clock.now();
             println!("[{}] {}", time.bright_white(), msg);
         }
     }
+    Ok(())
+}
+
+/// Formats the timestamps printed in front of messages.
+struct Clock {
+    format: String,
+    utc: bool,
+    last_date: Option<NaiveDate>,
+}
+
+impl Clock {
+    /// Creates a new clock using the console args, or the project config if they aren't provided.
+    fn new(args: &ConsoleArgs) -> Self {
+        let config = &config().console;
+        let format = match &args.timestamp_format {
+            Some(format) => format.clone(),
+            None => config.timestamp_format.clone(),
+        };
+
+        // Invalid format strings would cause a panic when formatting the time
+        let format = match StrftimeItems::new(&format).any(|item| item == Item::Error) {
+            true => {
+                warn!("'{}' is not a valid timestamp format", format.yellow());
+                ConsoleConfig::default().timestamp_format
+            }
+            false => format,
+        };
+
+        Self {
+            format,
+            utc: args.utc || config.utc,
+            last_date: None,
+        }
+    }
+
+    /// Returns the current time as a formatted string.
+    /// Prints a marker with the new date, if the date has changed since the last call.
+    fn now(&mut self) -> String {
+        let (date, time) = match self.utc {
+            true => {
+                let now = chrono::Utc::now();
+                (now.date_naive(), now.format(&self.format).to_string())
+            }
+            false => {
+                let now = chrono::Local::now();
+                (now.date_naive(), now.format(&self.format).to_string())
+            }
+        };
+
+        if self.last_date.is_some_and(|last_date| last_date != date) {
+            println!(
+                "{}",
+                format!("--- {} ---", date.format("%A, %Y-%m-%d")).bright_white()
+            );
+        }
+        self.last_date = Some(date);
+        time
+    }
+}
+
+/// Compares the script states of a reloaded save with the scripts that have been pushed most recently,
+/// and prints every object whose lua script or xml ui has been edited in-game since.
+/// The pushed scripts are compared, since the files are bundled, transformed and translated when they are pushed.
+fn print_diverged(answer: &AnswerReload, save: &Save) {
+    let Ok(pushed) = guard::read() else {
+        return;
+    };
+    for state in &answer.script_states {
+        let Some(pushed) = pushed.get(&state.guid) else {
+            continue;
+        };
+        let ui = state.ui.as_deref().unwrap_or_default();
+        if pushed.script == state.script && pushed.ui == ui {
+            continue;
+        }
+
+        let name = match save.objects.find_object(&state.guid) {
+            Ok(object) => object.to_string(),
+            Err(_) if state.guid == "-1" => "Global".bright_white().bold().to_string(),
+            Err(_) => state.guid.clone(),
+        };
+        let (script_insertions, script_deletions) = line_count(&pushed.script, &state.script);
+        let (ui_insertions, ui_deletions) = line_count(&pushed.ui, ui);
+        let insertions = format!("+{}", script_insertions + ui_insertions);
+        let deletions = format!("-{}", script_deletions + ui_deletions);
+        #[rustfmt::skip]
+        warn!("{} has been edited in-game since it was last pushed ({}, {})", name, insertions.green(), deletions.red());
+    }
+}
+
+/// Formats an error as `path:line:col: error: message`, using the tag of the object that caused the error.
+/// Falls back to the guid of the object, if it has no valid lua tag.
+fn ide_error(answer: &AnswerError, save: &Save) -> String {
+    let Location {
+        path,
+        line,
+        col,
+        message,
+    } = Location::of(answer, save);
+    match path {
+        Some(path) => format!("{}:{line}:{col}: error: {message}", path.to_slash_lossy()),
+        None => format!("{}:{line}:{col}: error: {message}", answer.guid),
+    }
+}
+
+/// The file, line and column an error occurred at.
+struct Location {
+    path: Option<PathBuf>,
+    line: String,
+    col: String,
+    message: String,
+}
+
+impl Location {
+    /// Looks up the file of the object that caused the error, and the position in the error message.
+    /// Positions inside of included files are mapped to the included file, see [`include::locate`].
+    fn of(answer: &AnswerError, save: &Save) -> Self {
+        let exprs = Regex::new(r"\((\d+),(\d+)(?:-\d+)?\):\s*(.*)$").unwrap();
+        let (line, col, message) = match exprs.captures(&answer.error) {
+            Some(caps) => (
+                caps[1].to_string(),
+                caps[2].to_string(),
+                caps[3].to_string(),
+            ),
+            None => ("1".into(), "1".into(), answer.error.clone()),
+        };
+
+        let (path, script) = match answer.guid.as_str() {
+            // Use the file the Global script has been read from,
+            // or the Global script that exists, e.g. `Global.ttslua`, or `Global.lua` if none exists
+            "-1" => {
+                let path = app::global_lua_path(save).or_else(|| {
+                    let extensions = &ttsst::tags::extensions().lua;
+                    let mut paths = extensions
+                        .iter()
+                        .map(|ext| PathBuf::from(format!("Global.{ext}")));
+                    let path = paths.clone().find(|path| path.exists());
+                    path.or_else(|| paths.next())
+                });
+                (path, &*save.lua_script)
+            }
+            guid => match save.objects.find_object(guid) {
+                Ok(object) => {
+                    let tag = object.valid_lua().ok().flatten();
+                    (tag.and_then(|tag| tag.path().ok()), &*object.lua_script)
+                }
+                Err(_) => (None, ""),
+            },
+        };
+        let (path, line) = match (path, line.parse()) {
+            (Some(path), Ok(number)) => {
+                let (path, number) = include::locate(script, &path, number);
+                (Some(path), number.to_string())
+            }
+            (path, _) => (path, line),
+        };
+
+        Self {
+            path,
+            line,
+            col,
+            message,
+        }
+    }
+
+    /// Fills in the `{path}`, `{line}` and `{col}` of a link `template`, using the absolute path of the file.
+    /// Returns `None` if the file of the error is unknown.
+    fn link(&self, template: &str) -> Option<String> {
+        let path = std::path::absolute(self.path.as_ref()?).ok()?;
+        let link = template
+            .replace("{path}", &path.to_slash_lossy())
+            .replace("{line}", &self.line)
+            .replace("{col}", &self.col);
+        Some(link)
+    }
+}
+
+/// Turns `text` into a link to `url`, using an OSC 8 hyperlink if stdout is a terminal,
+/// or by appending the url otherwise.
+fn hyperlink(text: String, url: &str) -> String {
+    match std::io::stdout().is_terminal() {
+        true => format!("\x1B]8;;{url}\x1B\\{text}\x1B]8;;\x1B\\"),
+        false => format!("{text} {url}"),
+    }
+}
+
+/// Formats an error as `Error in 'Name' (guid) [tag]: message`, using the object that caused the error.
+/// Falls back to the error message prefix sent by the game, if the object doesn't exist in `save`.
+fn object_error(answer: &AnswerError, save: &Save) -> String {
+    let object = match answer.guid.as_str() {
+        "-1" => return format!("{} {}", "Error in Global:".red(), answer.error.red()),
+        guid => match save.objects.find_object(guid) {
+            Ok(object) => object,
+            Err(_) => return answer.error_message_prefix.red().to_string(),
+        },
+    };
+
+    let name = object.display_name();
+    let tag = match object.valid_lua() {
+        Ok(Some(tag)) => format!(" [{}]", tag),
+        _ => String::new(),
+    };
+
+    format!(
+        "{} ({}){}{} {}",
+        format!("Error in '{}'", name).red(),
+        object.guid.yellow(),
+        tag,
+        ":".red(),
+        answer.error.red()
+    )
 }
 
 trait Message {
-    fn message(&self) -> Option<ColoredString>;
+    fn message(&self, save: &Save) -> Option<String>;
 }
 
 impl Message for Answer {
-    fn message(&self) -> Option<ColoredString> {
+    fn message(&self, save: &Save) -> Option<String> {
         match self {
-            Answer::AnswerPrint(answer) => Some(answer.message.bright_white()),
-            Answer::AnswerError(answer) => Some(answer.error_message_prefix.red()),
-            Answer::AnswerReload(_) => Some("Loading complete.".green()),
+            Answer::AnswerPrint(answer) => {
+                Some(PrintLevel::of(&answer.message).paint(&answer.message))
+            }
+            Answer::AnswerError(answer) => Some(object_error(answer, save)),
+            Answer::AnswerReload(_) => Some("Loading complete.".green().to_string()),
             _ => None,
         }
     }
 }
 
+/// What has changed since the last reload while watching.
+#[derive(Default)]
+struct Changes {
+    /// Indices of the roots that contain changed files.
+    roots: BTreeSet<usize>,
+    /// Objects that are reloaded because of a changed file, see [`reload_targets`].
+    targets: BTreeSet<String>,
+}
+
+/// Delays reloads caused by paths that have caused a reload recently, in addition to the debouncer,
+/// e.g. if an editor saves a file by writing a temporary file and renaming it, or a generator rewrites files repeatedly.
+/// Changes made during the cooldown are merged into a single reload once it has passed.
+struct Cooldown {
+    duration: Duration,
+    /// When each path has caused a reload most recently.
+    reloaded: HashMap<PathBuf, Instant>,
+    /// When the delayed reload is due, if changes have been suppressed.
+    due: Option<Instant>,
+    /// Paths whose changes have been suppressed since the last reload, and how often.
+    suppressed: HashMap<PathBuf, usize>,
+    /// Number of changes that have been suppressed since watching started.
+    total: usize,
+}
+
+impl Cooldown {
+    fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            reloaded: HashMap::new(),
+            due: None,
+            suppressed: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Returns `true` if the changed `paths` can be reloaded right away, which is the case unless all of them are cooling down.
+    /// Otherwise the reload is delayed until their cooldown has passed, see [`Cooldown::is_due`].
+    fn admit(&mut self, paths: &[PathBuf]) -> bool {
+        let now = Instant::now();
+        let ends = paths
+            .iter()
+            .filter_map(|path| self.reloaded.get(path))
+            .map(|reloaded| *reloaded + self.duration)
+            .filter(|end| *end > now)
+            .collect_vec();
+        if ends.len() < paths.len() {
+            // The reload includes the changes that have been suppressed
+            self.due = None;
+            self.suppressed.clear();
+            self.record(paths, now);
+            return true;
+        }
+
+        for path in paths {
+            *self.suppressed.entry(path.clone()).or_default() += 1;
+            self.total += 1;
+            trace!(
+                "suppressed a reload of '{}' ({} since the last reload, {} in total)",
+                path.to_slash_lossy(),
+                self.suppressed[path],
+                self.total
+            );
+        }
+        self.due = self.due.max(ends.into_iter().max());
+        false
+    }
+
+    /// Returns `true` once the cooldown of the suppressed changes has passed, so that they get reloaded.
+    fn is_due(&mut self) -> bool {
+        match self.due {
+            Some(due) if due <= Instant::now() => (),
+            _ => return false,
+        }
+        self.due = None;
+        let suppressed = std::mem::take(&mut self.suppressed);
+        #[rustfmt::skip]
+        trace!("reloading {} path(s) after their cooldown, merging {} change(s)", suppressed.len(), suppressed.values().sum::<usize>());
+        self.record(suppressed.keys(), Instant::now());
+        true
+    }
+
+    fn record<'a>(&mut self, paths: impl IntoIterator<Item = &'a PathBuf>, now: Instant) {
+        for path in paths {
+            self.reloaded.insert(path.clone(), now);
+        }
+        self.reloaded
+            .retain(|_, reloaded| now - *reloaded < self.duration);
+    }
+}
+
+/// Returns the objects that have to be reloaded because one of the changed `paths` matches a pattern of the
+/// `reload-targets` table of the config, even though it isn't attached to them, e.g. because it is bundled into their files.
+/// Aliases are replaced with the GUIDs they refer to.
+fn reload_targets(paths: &[PathBuf]) -> BTreeSet<String> {
+    let mut targets = BTreeSet::new();
+    for (pattern, names) in &config().reload_targets {
+        let exprs = match utils::glob(pattern) {
+            Ok(exprs) => exprs,
+            Err(err) => {
+                warn!("'{}' is not a valid pattern: {}", pattern.yellow(), err);
+                continue;
+            }
+        };
+        if paths
+            .iter()
+            .any(|path| exprs.is_match(&normalize(path).to_slash_lossy()))
+        {
+            let resolved = names
+                .iter()
+                .map(|name| config().aliases.get(name).unwrap_or(name).clone());
+            targets.extend(resolved);
+        }
+    }
+    targets
+}
+
 /// Spawns a new thread that listens to file changes in the `watch` directory.
 /// This thread uses its own `ExternalEditorApi` listening to port 39997.
-fn watch<P: AsRef<Path>>(save_file: &SaveFile, api: &Api, paths: &[P]) -> Result<!> {
+///
+/// If `--poll` is used, the files are polled for changes instead of relying on file system events,
+/// which don't arrive on network shares, WSL mounts or Docker volumes.
+///
+/// Every root is watched with its own options. Changes to ignored files are dropped.
+/// Paths that change again shortly after they have caused a reload are reloaded once their cooldown has passed, see [`Cooldown`].
+fn watch(
+    active: &RwLock<PathBuf>,
+    api: &Api,
+    dispatcher: &Dispatcher,
+    prompter: &dyn Prompter,
+    args: &WatchArgs,
+    roots: &[WatchRoot],
+    changed: &Mutex<Changes>,
+) -> Result<()> {
     // Create notify watcher
     let (tx, rx) = std::sync::mpsc::channel();
-    let mut watcher = debouncer::new_debouncer(Duration::from_millis(500), tx)?;
+    let timeout = Duration::from_millis(500);
+    let (mut native, mut poll);
+    let watcher: &mut dyn Watcher = match args.poll {
+        None => {
+            native = debouncer::new_debouncer(timeout, tx)?;
+            native.watcher()
+        }
+        Some(interval) => {
+            let config = debouncer::Config::default()
+                .with_timeout(timeout)
+                .with_notify_config(
+                    notify::Config::default().with_poll_interval(Duration::from_millis(interval)),
+                );
+            poll = debouncer::new_debouncer_opt::<_, PollWatcher>(config, tx)?;
+            debug!("polling for changes every {}ms", interval);
+            poll.watcher()
+        }
+    };
+
+    let cooldown = args.cooldown.unwrap_or(config().console.cooldown);
+    let mut cooldown = Cooldown::new(Duration::from_millis(cooldown));
 
-    for path in paths {
-        watcher
-            .watcher()
-            .watch(path.as_ref(), RecursiveMode::Recursive)?;
+    // Files are only searched once, and then counted using the events
+    let mut watched = watched_files(roots);
+
+    for root in roots {
+        let mode = match root.recursive {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        };
+        watcher.watch(root.path.as_ref(), mode)?;
     }
 
     loop {
-        match rx.recv()? {
+        // The watcher doesn't disconnect, so the thread checks regularly whether it has to stop
+        let events = match rx.recv_timeout(STOP_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) if interrupt::interrupted() => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {
+                if cooldown.is_due() {
+                    dispatcher.queue_reload(api)?;
+                }
+                status::refresh();
+                continue;
+            }
+            result => result?,
+        };
+        match events {
             Ok(events) => {
                 let paths = events
                     .iter()
                     .filter(|event| event.kind == debouncer::DebouncedEventKind::Any)
                     .filter_map(|event| event.path.strip_current_dir().ok())
+                    .filter(|path| roots.iter().any(|root| root.contains(path)))
                     .collect_vec();
+                let mut changes = changed.lock().unwrap();
+                changes.roots.extend(
+                    roots
+                        .iter()
+                        .positions(|root| paths.iter().any(|path| root.contains(path))),
+                );
+                changes.targets.extend(reload_targets(&paths));
+                drop(changes);
+
+                // Files that no longer exist have been deleted or renamed
+                let (existing, removed): (Vec<_>, Vec<_>) =
+                    paths.iter().cloned().partition(|path| path.exists());
+                let save_path = active.read().unwrap().clone();
+                if !removed.is_empty() {
+                    let policy = args.on_remove.unwrap_or(config().console.on_remove);
+                    handle_removed(&save_path, &removed, &existing, policy, prompter)?;
+                }
 
                 if !paths.is_empty() {
-                    // Send ReloadMessage using `api.send` instead of `api.reload`,
-                    // because waiting for an answer would block the thread since the TCP socket is already in use.
-                    api.send(MessageReload::new(json!([])).as_message())?;
+                    for path in &paths {
+                        match path.exists() {
+                            true if is_watched(path) => watched.insert(normalize(path)),
+                            _ => watched.remove(&normalize(path)),
+                        };
+                    }
+                    status::set_files(watched.len());
+
+                    // Reloads are queued while the game is still busy with a previous reload
+                    if cooldown.admit(&paths) {
+                        dispatcher.queue_reload(api)?;
+                    }
 
                     // Add the paths as a component tag, so that reloaded paths will show up as tags.
                     // Then update the save file.
-                    for path in paths {
+                    for path in existing {
                         if let Ok(tag) = Tag::try_from(path.as_ref()) {
-                            let mut save_file = SaveFile::read_from_path(&save_file.path)?;
+                            let mut save_file = SaveFile::read_from_path(&save_path)?;
                             if save_file.save.push_object_tag(tag) {
                                 save_file.write()?;
                             }
@@ -114,5 +928,122 @@ fn watch<P: AsRef<Path>>(save_file: &SaveFile, api: &Api, paths: &[P]) -> Result
             }
             Err(err) => error!("{}", err),
         }
+        // Files that keep changing more often than the receive timeout would otherwise delay the suppressed reload forever
+        if cooldown.is_due() {
+            dispatcher.queue_reload(api)?;
+        }
+    }
+}
+
+/// Returns the lua and xml files in the `roots`.
+fn watched_files(roots: &[WatchRoot]) -> HashSet<PathBuf> {
+    roots
+        .iter()
+        .flat_map(|root| match root.path.is_dir() {
+            true => find_files(&root.path, &|path| root.contains(path) && is_watched(path))
+                .unwrap_or_default(),
+            false => vec![root.path.clone()],
+        })
+        .map(|path| normalize(&path))
+        .collect()
+}
+
+/// Whether the file at `path` is a lua or xml file.
+fn is_watched(path: &Path) -> bool {
+    let extensions = ttsst::tags::extensions();
+    extensions.is_lua(path) || extensions.is_xml(path)
+}
+
+/// Handles objects that use a file in `removed`, according to the `policy`.
+/// Files in `created` are used to find the new path of a renamed file.
+///
+/// Detached objects keep their script or ui until the next reload, which removes it
+/// since the objects no longer have a valid tag.
+fn handle_removed(
+    save_path: &Path,
+    removed: &[PathBuf],
+    created: &[PathBuf],
+    policy: RemovePolicy,
+    prompter: &dyn Prompter,
+) -> Result<()> {
+    let mut save_file = SaveFile::read_from_path(save_path)?;
+    let mut has_changed = false;
+
+    for path in removed {
+        let Ok(tag) = Tag::try_from(path.as_path()) else {
+            continue;
+        };
+        let objects = save_file
+            .save
+            .objects
+            .iter()
+            .filter(|object| object.tags.contains(&tag))
+            .cloned()
+            .collect::<Objects>();
+        if objects.is_empty() {
+            continue;
+        }
+
+        warn!("{} has been removed, but is still used by {}", tag, objects);
+        let detach = || {
+            let message = format!(
+                "Do you want to detach {tag} from {} object(s)?",
+                objects.len()
+            );
+            prompter.confirm(&message, false)
+        };
+        match policy {
+            RemovePolicy::Prompt if detach()? => {
+                detach_tag(&mut save_file.save, &tag);
+                has_changed = true;
+            }
+            RemovePolicy::Warn | RemovePolicy::Prompt => {
+                #[rustfmt::skip]
+                warn!("Use the mv command to update the tag, or the detach command to remove it");
+            }
+            RemovePolicy::Rename => match renamed_to(&save_file.save, &tag, created) {
+                Some(new_tag) => {
+                    let count = save_file.save.rename_tag(&tag, &new_tag);
+                    info!("replaced {} with {} on {} object(s)", tag, new_tag, count);
+                    has_changed = true;
+                }
+                None => warn!("could not determine the new path of {}", tag),
+            },
+            RemovePolicy::Detach => {
+                detach_tag(&mut save_file.save, &tag);
+                has_changed = true;
+            }
+        }
+    }
+
+    if has_changed {
+        save_file.write()?;
+    }
+    Ok(())
+}
+
+/// Removes `tag` from every object in `save` that carries it.
+fn detach_tag(save: &mut Save, tag: &Tag) {
+    for object in save.objects.iter_mut() {
+        if object.tags.contains(tag) {
+            object.tags.retain(|t| t != tag);
+            info!("detached {} from {}", tag, object);
+        }
+    }
+}
+
+/// Returns the tag of the file in `created` that `tag` has most likely been renamed to.
+/// A file is only considered, if it has the same file type and isn't used by any object yet.
+fn renamed_to(save: &Save, tag: &Tag, created: &[PathBuf]) -> Option<Tag> {
+    let candidates = created
+        .iter()
+        .filter_map(|path| Tag::try_from(path.as_path()).ok())
+        .filter(|new_tag| new_tag.is_lua() == tag.is_lua())
+        .filter(|new_tag| !save.objects.iter().any(|o| o.tags.contains(new_tag)))
+        .collect_vec();
+
+    match candidates.len() {
+        1 => candidates.into_iter().next(),
+        _ => None,
     }
 }
@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use path_slash::PathBufExt;
+use ttsst::api::long_string;
+use ttsst::Save;
+
+use crate::config::{config, DedupConfig};
+
+/// Name of the table in the Global script that holds the shared scripts by the path of their file.
+const MODULES: &str = "TTSST_SHARED";
+
+/// Marks the start of the generated part of the Global script.
+const BEGIN: &str = "-- ttsst:shared begin";
+/// Marks the end of the generated part of the Global script.
+const END: &str = "-- ttsst:shared end";
+
+/// First line of the stub that replaces a shared script, followed by the path of its file.
+const STUB: &str = "-- ttsst:shared ";
+
+/// Returns the `save` as it gets written and pushed, if `[dedup]` is enabled in the config:
+/// objects that share the same attached file get a stub, which loads the script from a table appended to the Global script.
+///
+/// Only scripts of at least `min-size` bytes, that are shared by at least `min-objects` objects, are replaced.
+/// Objects whose scripts differ, even if only in whitespace, keep them, since the stub would load the same script for all of them.
+/// Objects inside of containers keep their scripts.
+pub fn collapse(save: &Save) -> Result<Cow<'_, Save>> {
+    collapse_with(save, &config().dedup)
+}
+
+/// Collapses the `save` using the `dedup` settings, see [`collapse`].
+fn collapse_with<'a>(save: &'a Save, dedup: &DedupConfig) -> Result<Cow<'a, Save>> {
+    if !dedup.enabled {
+        return Ok(Cow::Borrowed(save));
+    }
+
+    let mut shared = BTreeMap::new();
+    for object in save.objects.iter() {
+        if object.lua_script.len() < dedup.min_size {
+            continue;
+        }
+        if let Some(tag) = object.valid_lua()? {
+            let key = tag.path()?.to_slash_lossy().to_string();
+            shared.entry(key).or_insert_with(Vec::new).push(object);
+        }
+    }
+    // Keys are quoted as lua strings without escapes, and objects whose scripts differ from their file can't share one
+    shared.retain(|key, objects| {
+        objects.len() >= dedup.min_objects
+            && !key.contains(['"', '\\', '\n'])
+            && objects
+                .iter()
+                .all(|o| o.lua_script == objects[0].lua_script)
+    });
+    if shared.is_empty() {
+        return Ok(Cow::Borrowed(save));
+    }
+
+    let mut collapsed = save.clone();
+    let mut modules = String::new();
+    for (key, objects) in shared {
+        modules += &format!("[\"{key}\"] = {},\n", long_string(&objects[0].lua_script));
+        for object in objects {
+            collapsed.objects.find_object_mut(&object.guid)?.lua_script = stub(&key).into();
+        }
+    }
+    collapsed.lua_script = format!(
+        "{}\n\n{BEGIN}\n{MODULES} = {{\n{modules}}}\n{END}\n",
+        strip(&save.lua_script)
+    );
+    Ok(Cow::Owned(collapsed))
+}
+
+/// Replaces the stubs in the `save` with the scripts they load, and removes the table of shared scripts from the Global script.
+/// This reverses [`collapse`], so that the scripts can be compared with their files.
+///
+/// Returns the GUIDs of the objects that had a stub, which have to be pushed again if they aren't shared anymore.
+pub fn expand(save: &mut Save) -> HashSet<String> {
+    let Some((_, block)) = save.lua_script.split_once(&format!("\n\n{BEGIN}\n")) else {
+        return HashSet::new();
+    };
+    let modules = modules(block);
+    save.lua_script = strip(&save.lua_script).into();
+
+    let mut stubs = HashSet::new();
+    for object in save.objects.iter_mut() {
+        let key = object
+            .lua_script
+            .strip_prefix(STUB)
+            .and_then(|stub| stub.lines().next());
+        if let Some(script) = key.and_then(|key| modules.get(key)) {
+            object.lua_script = script.as_str().into();
+            stubs.insert(object.guid.clone());
+        }
+    }
+    stubs
+}
+
+/// Returns the GUIDs of the objects in the `save` whose scripts are stubs.
+pub fn stubs(save: &Save) -> HashSet<String> {
+    save.objects
+        .iter()
+        .filter(|object| object.lua_script.starts_with(STUB))
+        .map(|object| object.guid.clone())
+        .collect()
+}
+
+/// Returns the stub that loads the shared script of the file at `key`.
+/// The script runs in the environment of the object, since `load` uses the globals of the calling script.
+fn stub(key: &str) -> String {
+    format!("{STUB}{key}\nload(Global.getTable(\"{MODULES}\")[\"{key}\"])()\n")
+}
+
+/// Returns the Global `script` without the table of shared scripts.
+fn strip(script: &str) -> &str {
+    match script.split_once(&format!("\n\n{BEGIN}\n")) {
+        Some((script, _)) => script,
+        None => script,
+    }
+}
+
+/// Parses the table of shared scripts written by [`collapse`].
+fn modules(block: &str) -> BTreeMap<String, String> {
+    let mut modules = BTreeMap::new();
+    let mut rest = block;
+    while let Some((_, entry)) = rest.split_once("[\"") {
+        let Some((key, entry)) = entry.split_once("\"] = [") else {
+            break;
+        };
+        let level = entry.chars().take_while(|c| *c == '=').count();
+        let Some(entry) = entry[level..].strip_prefix("[\n") else {
+            break;
+        };
+        let close = format!("]{}]", "=".repeat(level));
+        let Some((script, entry)) = entry.split_once(&close) else {
+            break;
+        };
+        modules.insert(key.to_string(), script.to_string());
+        rest = entry;
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use ttsst::testing::{self, ObjectBuilder, SaveBuilder};
+
+    use super::*;
+
+    const DEDUP: DedupConfig = DedupConfig {
+        enabled: true,
+        min_objects: 2,
+        min_size: 0,
+    };
+
+    fn shared(path: &str, scripts: &[&str]) -> Save {
+        let objects = scripts.iter().enumerate().map(|(i, script)| {
+            ObjectBuilder::new(&format!("{i:06}"))
+                .lua_script(script)
+                .tag(testing::tag(path))
+                .build()
+        });
+        objects
+            .fold(SaveBuilder::new("Dedup"), SaveBuilder::object)
+            .lua_script("print(\"global\")")
+            .build()
+    }
+
+    /// Collapses and expands the `save`, and returns whether any scripts were replaced with stubs.
+    fn roundtrip(save: &Save) -> bool {
+        let collapsed = collapse_with(save, &DEDUP).unwrap().into_owned();
+        let mut expanded = collapsed.clone();
+        let stubs = expand(&mut expanded);
+        assert_eq!(stubs, super::stubs(&collapsed));
+        assert_eq!(
+            serde_json::to_value(&expanded).unwrap(),
+            serde_json::to_value(save).unwrap()
+        );
+        !stubs.is_empty()
+    }
+
+    #[test]
+    fn long_brackets_survive_a_roundtrip() {
+        for script in ["t[a[1]] = 1", "s = [=[ ]] ]=]", "s = [[ ]=] ]]", "t[a[1]]"] {
+            assert!(roundtrip(&shared("shared.lua", &[script, script])));
+        }
+    }
+
+    #[test]
+    fn keys_with_quotes_are_not_shared() {
+        let script = "print(1)";
+        assert!(!roundtrip(&shared("say \"hi\".lua", &[script, script])));
+        assert!(roundtrip(&shared("say 'hi'.lua", &[script, script])));
+    }
+
+    #[test]
+    fn trailing_whitespace_is_preserved() {
+        assert!(roundtrip(&shared(
+            "shared.lua",
+            &["print(1)\n \t\n", "print(1)\n \t\n"]
+        )));
+        assert!(!roundtrip(&shared(
+            "shared.lua",
+            &["print(1)\n", "print(1)"]
+        )));
+    }
+
+    #[test]
+    fn multiple_modules_are_parsed() {
+        let object = |guid: &str, path: &str, script: &str| {
+            ObjectBuilder::new(guid)
+                .lua_script(script)
+                .tag(testing::tag(path))
+                .build()
+        };
+        let save = SaveBuilder::new("Dedup")
+            .object(object("aaaaaa", "a.lua", "a = [[]]"))
+            .object(object("bbbbbb", "b.lua", "b = ']'"))
+            .object(object("cccccc", "a.lua", "a = [[]]"))
+            .object(object("dddddd", "b.lua", "b = ']'"))
+            .build();
+        let collapsed = collapse_with(&save, &DEDUP).unwrap();
+        let block = collapsed.lua_script.split_once(BEGIN).unwrap().1;
+        let modules = modules(block);
+        assert_eq!(modules["./a.lua"], "a = [[]]");
+        assert_eq!(modules["./b.lua"], "b = ']'");
+        assert!(roundtrip(&save));
+    }
+}
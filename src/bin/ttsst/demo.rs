@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+use serde_json::Value;
+use ttsst::error::Error;
+use ttsst::testing::{fixtures, mock::MockServer};
+
+/// Name of the sample save that is created in the demo directory.
+const SAVE_NAME: &str = "TS_Save_Demo.json";
+
+/// Pretends to be Tabletop Simulator with a sample save loaded, so that ttsst can be tried without the game.
+/// Messages are received on `send_port`, and answers are sent to `reply_port`, which is the port the other ttsst commands listen on.
+pub fn run(dir: &Path, send_port: u16, reply_port: u16) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let save_path = dir.join(SAVE_NAME);
+    if !save_path.exists() {
+        fs::write(&save_path, fixtures::SCRIPTED)?;
+        info!(
+            "created the sample save '{}'",
+            save_path.to_slash_lossy().yellow()
+        );
+    }
+
+    let mock = match MockServer::bind(send_port, &save_path) {
+        Ok(mock) => mock.reply_to(reply_port),
+        #[rustfmt::skip]
+        Err(Error::PortInUse(port)) => return Err(anyhow!("port {} is already in use, close Tabletop Simulator to run the demo", port)),
+        Err(err) => return Err(err.into()),
+    };
+
+    #[rustfmt::skip]
+    info!("serving '{}' like Tabletop Simulator would, press Ctrl-C to stop", save_path.to_slash_lossy().yellow());
+    info!("run commands like `ttsst attach <File> a1b2c3`, `ttsst reload` or `ttsst watch` in another terminal");
+    loop {
+        match mock.handle() {
+            Ok(message) => info!("received {}", describe(&message)),
+            Err(err) => warn!("{}", err),
+        }
+    }
+}
+
+/// Describes a message sent by ttsst.
+fn describe(message: &Value) -> String {
+    match message["messageID"].as_u64() {
+        Some(0) => "a request for the scripts".into(),
+        Some(1) => {
+            let count = message["scriptStates"].as_array().map_or(0, Vec::len);
+            format!("{} script state(s) to reload", count)
+        }
+        Some(2) => format!("the custom message {}", message["customMessage"]),
+        Some(3) => {
+            let script = message["script"].as_str().unwrap_or_default();
+            format!("lua code to execute: {}", script.yellow())
+        }
+        _ => format!("{}", message),
+    }
+}
@@ -0,0 +1,393 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use log::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::api::{Answer, BatchOutcome, ExecuteBatch, MessageExecute, MessageReload, ScriptState};
+use ttsst::error::Error;
+use ttsst::Save;
+
+use crate::app::{Executor, Reloader, LOAD_ERROR_GRACE};
+use crate::config::config;
+use crate::progress::LoadError;
+use crate::session::Session;
+use crate::{interrupt, status};
+
+type Filter = Box<dyn Fn(&Answer) -> bool + Send>;
+
+/// Time after which an unanswered reload is considered lost, so that queued reloads are sent anyway.
+pub const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval in which a reload that updates scripts checks whether the reload in flight has been answered.
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Time after executing a script in which errors in Global are attributed to the script.
+pub const EXECUTE_ERROR_WINDOW: Duration = Duration::from_secs(1);
+
+/// Tracks the reload that has been sent to the game, but hasn't been answered yet.
+#[derive(Default)]
+struct ReloadQueue {
+    /// When the reload in flight has been sent.
+    sent: Option<Instant>,
+    /// Whether another reload has been requested while one was in flight.
+    pending: bool,
+    /// Number of reloads that update scripts and are waiting for the reload in flight to be answered.
+    /// A queued reload is merged into them instead of being sent on its own.
+    waiting: usize,
+}
+
+impl ReloadQueue {
+    fn in_flight(&self) -> bool {
+        self.sent
+            .is_some_and(|sent| sent.elapsed() < RELOAD_TIMEOUT)
+    }
+}
+
+/// Reads incoming messages on a single thread, and fans them out to every subscriber.
+///
+/// Only one thread can listen to the messages sent by the game,
+/// so every other thread that needs to receive messages has to subscribe to the dispatcher.
+///
+/// Reloads are tracked as well, so that the game isn't asked to reload while it is still busy with a previous reload.
+#[derive(Default)]
+pub struct Dispatcher {
+    subscribers: Mutex<Vec<(Filter, Sender<Arc<Answer>>)>>,
+    reloads: Mutex<ReloadQueue>,
+    /// Answers to reloads that have been sent by ttsst, as long as a subscriber holds on to them.
+    answered: Mutex<Vec<Weak<Answer>>>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a receiver for all incoming messages for which `filter` returns `true`.
+    /// Dropping the receiver unsubscribes from the dispatcher.
+    pub fn subscribe<F>(&self, filter: F) -> Receiver<Arc<Answer>>
+    where
+        F: Fn(&Answer) -> bool + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push((Box::new(filter), tx));
+        rx
+    }
+
+    /// Reads messages from `api` and sends them to the subscribers, until the process gets interrupted.
+    /// Subscribers whose receiver has been dropped are removed.
+    ///
+    /// Once the dispatcher has stopped, every subscriber gets disconnected, so that the threads waiting for messages return.
+    pub fn run(&self, api: &Api) -> Result<()> {
+        let result = self.dispatch(api);
+        self.subscribers.lock().unwrap().clear();
+        result
+    }
+
+    fn dispatch(&self, api: &Api) -> Result<()> {
+        loop {
+            let answer = api.read();
+            // The process wakes the dispatcher up by connecting to it when it gets interrupted
+            if interrupt::interrupted() {
+                return Ok(());
+            }
+            let answer = match answer {
+                Ok(answer) => Arc::new(answer),
+                Err(ttsst::error::Error::SerdeError(err)) => {
+                    warn!("ignoring unknown message: {}", err);
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            status::received();
+            if let Answer::AnswerReload(_) = answer.as_ref() {
+                status::reload_answered();
+                if self.reload_answered(api)? {
+                    let mut answered = self.answered.lock().unwrap();
+                    answered.retain(|answer| answer.strong_count() > 0);
+                    answered.push(Arc::downgrade(&answer));
+                }
+            }
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|(filter, tx)| match filter(&answer) {
+                true => tx.send(answer.clone()).is_ok(),
+                false => true,
+            });
+            trace!("dispatched message to {} subscriber(s)", subscribers.len());
+        }
+    }
+}
+
+impl Dispatcher {
+    /// Asks the game to reload the save without updating any scripts.
+    /// If a reload is still in flight, the reload is queued instead, and sent once the game has answered.
+    /// Reloads that are queued in the meantime are coalesced into a single reload.
+    pub fn queue_reload(&self, api: &Api) -> Result<()> {
+        let mut queue = self.reloads.lock().unwrap();
+        if queue.in_flight() {
+            if !queue.pending {
+                debug!("the game is still reloading, queueing the next reload");
+            }
+            queue.pending = true;
+            return Ok(());
+        }
+
+        queue.sent = Some(Instant::now());
+        queue.pending = false;
+        Session::set_pending(true);
+        status::reload_sent();
+        // Send the message using `api.send` instead of `api.reload`,
+        // because waiting for an answer would block the thread since the TCP socket is already in use.
+        api.send(&MessageReload::new(json!([])))?;
+        Ok(())
+    }
+
+    /// Marks the reload in flight as answered, and sends the queued reload if there is one.
+    /// Returns `true` if a reload has been in flight, so that the answer belongs to a reload sent by ttsst.
+    fn reload_answered(&self, api: &Api) -> Result<bool> {
+        let mut queue = self.reloads.lock().unwrap();
+        let sent = queue.sent.take().is_some();
+        if queue.waiting > 0 {
+            // Reloading the save with the updated scripts reloads it as well
+            if std::mem::take(&mut queue.pending) {
+                debug!("merging the queued reload into the next update");
+            }
+        } else if std::mem::take(&mut queue.pending) {
+            debug!("sending the queued reload");
+            queue.sent = Some(Instant::now());
+            status::reload_sent();
+            api.send(&MessageReload::new(json!([])))?;
+        } else {
+            Session::set_pending(false);
+        }
+        Ok(sent)
+    }
+
+    /// Returns `true` if the `answer` belongs to a reload that has been sent by ttsst,
+    /// instead of the user loading the save in-game.
+    pub fn is_own_reload(&self, answer: &Arc<Answer>) -> bool {
+        let answered = self.answered.lock().unwrap();
+        answered
+            .iter()
+            .any(|own| std::ptr::eq(own.as_ptr(), Arc::as_ptr(answer)))
+    }
+
+    /// Waits until the reload in flight has been answered, and marks the reload that updates scripts as in flight.
+    /// A reload that has been queued in the meantime is merged into it.
+    fn claim_reload(&self) -> Result<()> {
+        self.reloads.lock().unwrap().waiting += 1;
+        loop {
+            let mut queue = self.reloads.lock().unwrap();
+            if !queue.in_flight() {
+                queue.waiting -= 1;
+                queue.sent = Some(Instant::now());
+                queue.pending = false;
+                Session::set_pending(true);
+                status::reload_sent();
+                return Ok(());
+            }
+            if interrupt::interrupted() {
+                queue.waiting -= 1;
+                return Err(Error::Cancelled.into());
+            }
+            drop(queue);
+            std::thread::sleep(RELOAD_POLL_INTERVAL);
+        }
+    }
+}
+
+/// An [`Api`] that receives its answers through a [`Dispatcher`],
+/// so that it can be used while another thread is listening to the game.
+pub struct DispatchedApi<'a> {
+    pub api: &'a Api,
+    pub dispatcher: &'a Dispatcher,
+    /// Script states of the most recent reload.
+    pub script_states: Mutex<Vec<ScriptState>>,
+    /// Most recent state of the save, as it has been pushed or read after a reload.
+    pub latest: &'a RwLock<Save>,
+}
+
+impl DispatchedApi<'_> {
+    /// Executes `script` globally and waits for its return value, using the timeout of the config.
+    /// Returns an error if the game reports an error in Global within [`EXECUTE_ERROR_WINDOW`] instead.
+    pub fn execute(&self, script: String) -> Result<Option<Value>> {
+        let message = MessageExecute::new(script);
+        let return_id = message.return_id;
+
+        // Errors don't have an id, so only errors in Global that arrive right after sending the script are attributed to it
+        let sent = Instant::now();
+        let answers = self.dispatcher.subscribe(move |answer| match answer {
+            Answer::AnswerReturn(answer) => answer.return_id == return_id,
+            Answer::AnswerError(answer) => {
+                answer.guid == "-1" && sent.elapsed() < EXECUTE_ERROR_WINDOW
+            }
+            _ => false,
+        });
+        self.request(&answers, &message, |answer| match answer {
+            Answer::AnswerReturn(answer) => Some(Ok(answer.return_value.clone())),
+            Answer::AnswerError(answer) => Some(Err(anyhow!("{}", answer.error))),
+            _ => None,
+        })?
+    }
+
+    /// Sends `message` and waits until `f` returns a value for one of the `answers`,
+    /// the same way as [`Api::request`], using the timeout of the config.
+    /// The message is only sent once, since executed scripts and reloads must not be handled twice by the game.
+    fn request<M, T, F>(&self, answers: &Receiver<Arc<Answer>>, message: &M, mut f: F) -> Result<T>
+    where
+        M: Serialize,
+        F: FnMut(&Answer) -> Option<T>,
+    {
+        self.api.send(message)?;
+        let deadline = config()
+            .timeout()
+            .map(|timeout| (Instant::now() + timeout, timeout));
+        loop {
+            let answer = match deadline {
+                Some((deadline, _)) => {
+                    answers.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => answers.recv().map_err(RecvTimeoutError::from),
+            };
+            match answer {
+                Ok(answer) => match f(&answer) {
+                    Some(value) => return Ok(value),
+                    None => continue,
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Error::Timeout(deadline.unwrap().1).into())
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("the connection to the game has been closed")
+                }
+            }
+        }
+    }
+}
+
+impl Executor for DispatchedApi<'_> {
+    /// Executes the operations of the `batch` with one message per chunk, see [`ExecuteBatch::run`].
+    fn execute_batch(&self, batch: &ExecuteBatch) -> Result<Vec<BatchOutcome>> {
+        batch.run(|chunk| self.execute(chunk.script.clone()), |_, _| Ok(()))
+    }
+}
+
+impl Reloader for DispatchedApi<'_> {
+    /// Sends the updated scripts once the reload in flight has been answered,
+    /// so that the answer to the previous reload isn't mistaken for the answer to this one.
+    fn reload(&self, script_states: Value) -> Result<Vec<LoadError>> {
+        self.dispatcher.claim_reload()?;
+        // Subscribe before sending the message, so that the answer can't be missed
+        let answers = self
+            .dispatcher
+            .subscribe(|answer| matches!(answer, Answer::AnswerReload(_) | Answer::AnswerError(_)));
+
+        let mut errors = Vec::new();
+        let message = MessageReload::new(script_states);
+        self.request(&answers, &message, |answer| match answer {
+            Answer::AnswerReload(_) => Some(()),
+            Answer::AnswerError(answer) => {
+                errors.push(LoadError::from(answer));
+                None
+            }
+            _ => None,
+        })?;
+
+        let deadline = Instant::now() + LOAD_ERROR_GRACE;
+        while let Ok(answer) =
+            answers.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            if let Answer::AnswerError(answer) = answer.as_ref() {
+                errors.push(LoadError::from(answer));
+            }
+        }
+        Ok(errors)
+    }
+
+    /// Keeps the save that is pushed as the most recent state, so that errors are looked up in the scripts the game runs.
+    fn pushing(&self, save: &Save) {
+        *self.latest.write().unwrap() = save.clone();
+    }
+
+    /// Returns the script states of the most recent reload.
+    ///
+    /// Requesting the scripts from the game would cause every thread that listens for reloads to receive the answer,
+    /// as if the save had been reloaded.
+    fn get_scripts(&self) -> Result<Vec<ScriptState>> {
+        Ok(self.script_states.lock().unwrap().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use ttsst::testing::{self, fixtures, mock::MockServer};
+
+    use ttsst::api::MessageGetScripts;
+
+    use super::*;
+    use crate::config::test_project;
+
+    #[test]
+    fn updates_wait_for_the_reload_in_flight() {
+        let path = test_project().root.join("dispatch.json");
+        fs::write(&path, fixtures::SCRIPTED).unwrap();
+
+        let mock = MockServer::bind(0, &path).unwrap();
+        let api = Api::with_ports(mock.port().unwrap(), 0).unwrap();
+        let mock = mock.reply_to(api.port().unwrap());
+        let (tx, received) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            tx.send(mock.handle().unwrap()).unwrap();
+        });
+
+        // The dispatcher runs until the process is interrupted, so it has to outlive the test
+        let api: &'static Api = Box::leak(Box::new(api));
+        let dispatcher: &'static Dispatcher = Box::leak(Box::default());
+        std::thread::spawn(|| dispatcher.run(api));
+        let reloads = dispatcher.subscribe(|answer| matches!(answer, Answer::AnswerReload(_)));
+
+        // A reload is in flight, and another one has been queued
+        *dispatcher.reloads.lock().unwrap() = ReloadQueue {
+            sent: Some(Instant::now()),
+            pending: true,
+            waiting: 0,
+        };
+        let update = std::thread::spawn(|| {
+            let api = DispatchedApi {
+                api,
+                dispatcher,
+                script_states: Mutex::default(),
+                latest: Box::leak(Box::new(RwLock::new(testing::parse_save(
+                    fixtures::SCRIPTED,
+                )))),
+            };
+            api.reload(json!([{ "guid": "a1b2c3", "script": "print('updated')" }]))
+        });
+        while dispatcher.reloads.lock().unwrap().waiting == 0 {
+            std::thread::sleep(RELOAD_POLL_INTERVAL);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(received.try_recv().is_err());
+
+        // Once the game answers, the update is sent instead of the queued reload
+        dispatcher.reload_answered(api).unwrap();
+        assert!(update.join().unwrap().unwrap().is_empty());
+        let message = received.try_recv().unwrap();
+        assert_eq!(message["scriptStates"][0]["script"], "print('updated')");
+        assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(!dispatcher.reloads.lock().unwrap().pending);
+        assert!(dispatcher.is_own_reload(&reloads.recv().unwrap()));
+
+        // Scripts requested by another client are answered the same way as a reload in-game
+        api.send(&MessageGetScripts::new()).unwrap();
+        assert!(!dispatcher.is_own_reload(&reloads.recv().unwrap()));
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use log::*;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde_json::Value;
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::api::{Answer, MessageExecute};
+use ttsst::Object;
+
+use crate::config::config;
+
+/// Path of the file containing the lines entered in the repl.
+const HISTORY_FILE: &str = ".ttsst/repl_history";
+
+/// Executes `input` and prints its return value.
+/// If `input` starts with `@`, the snippet with that name is executed instead.
+/// If an `object` is provided, `self` refers to that object inside of the script.
+pub fn exec(api: &Api, input: &str, object: Option<&Object>) -> Result<()> {
+    let script = resolve(input)?;
+    let script = match object {
+        Some(object) => scoped(&object.guid, &script),
+        None => script,
+    };
+    print_value(execute(api, script)?);
+    Ok(())
+}
+
+/// Reads lua code from stdin line by line, executes it and prints the return value.
+/// Every line gets appended to the history of the project, which can be browsed using the arrow keys.
+pub fn repl(api: &Api) -> Result<()> {
+    info!(
+        "type lua code to execute it, or {} to execute a snippet",
+        "@<name>".yellow()
+    );
+    let mut editor = DefaultEditor::new()?;
+    if Path::new(HISTORY_FILE).exists() {
+        editor.load_history(HISTORY_FILE)?;
+    }
+    let prompt = format!("{} ", ">".bright_white().bold());
+    loop {
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        append_history(line)?;
+
+        match resolve(line).and_then(|script| execute(api, script)) {
+            Ok(value) => print_value(value),
+            Err(err) => error!("{}", err),
+        }
+    }
+}
+
+/// Returns the snippet with the name `input` if it starts with `@`, or `input` itself otherwise.
+fn resolve(input: &str) -> Result<String> {
+    match input.strip_prefix('@') {
+        Some(name) => config()
+            .snippets
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("no snippet with the name '{}' exists", name.yellow())),
+        None => Ok(input.into()),
+    }
+}
+
+/// Wraps `script` in a function, in which `self` refers to the object with the `guid`.
+fn scoped(guid: &str, script: &str) -> String {
+    format!("return (function(self)\n{script}\nend)(getObjectFromGUID(\"{guid}\"))")
+}
+
+/// Executes `script` globally and waits for its return value.
+/// Returns an error if the game reports an error instead.
+pub fn execute(api: &Api, script: String) -> Result<Option<Value>> {
+    let message = MessageExecute::new(script);
+    let return_id = message.return_id;
+    api.request(&message, |answer| match answer {
+        Answer::AnswerReturn(answer) if answer.return_id == return_id => {
+            Some(Ok(answer.return_value))
+        }
+        Answer::AnswerError(answer) => Some(Err(anyhow!("{}", answer.error))),
+        _ => None,
+    })?
+}
+
+/// Prints the return value of a script, if it returned anything.
+fn print_value(value: Option<Value>) {
+    match value {
+        Some(Value::String(string)) => println!("{}", string),
+        Some(Value::Null) | None => (),
+        Some(value) => println!("{}", value),
+    }
+}
+
+/// Appends `line` to the history file.
+fn append_history(line: &str) -> Result<()> {
+    if let Some(dir) = Path::new(HISTORY_FILE).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(HISTORY_FILE)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use path_slash::PathExt;
+use ttsst::tags::{extensions, namespace};
+use ttsst::{Save, Tag};
+
+use crate::config::{normalize, project};
+
+/// Prints whether `tag` is valid, which file it refers to, whether that file exists,
+/// and which objects of the `save` carry it.
+pub fn explain(save: &Save, tag: &str) -> Result<()> {
+    let tag = Tag::from(tag.to_string());
+    println!("{:<8} {}", "tag:".bold(), tag);
+
+    let kind = match () {
+        _ if tag.is_lua() => "lua script",
+        _ if tag.is_xml() => "xml ui",
+        _ => "",
+    };
+    match problem(&tag.clone().into_inner()) {
+        None if !kind.is_empty() => println!("{:<8} {} ({})", "valid:".bold(), "yes".green(), kind),
+        problem => {
+            let problem =
+                problem.unwrap_or_else(|| "it doesn't follow the naming convention".into());
+            println!("{:<8} {}, {}", "valid:".bold(), "no".red(), problem);
+        }
+    }
+
+    if let Ok(path) = tag.path() {
+        let file = project().root.join(normalize(&path));
+        #[rustfmt::skip]
+        println!("{:<8} '{}' ({})", "file:".bold(), path.to_slash_lossy().yellow(), file.to_slash_lossy());
+        let exists = match file.is_file() {
+            true => "yes".green(),
+            false => "no".red(),
+        };
+        println!("{:<8} {}", "exists:".bold(), exists);
+    }
+
+    let objects = save
+        .objects
+        .iter()
+        .filter(|object| object.tags.contains(&tag))
+        .collect_vec();
+    let global = save.global_tags().contains(&tag);
+    match objects.is_empty() && !global {
+        true => println!("{:<8} none", "objects:".bold()),
+        false => {
+            println!("{:<8}", "objects:".bold());
+            if global {
+                println!("  {}", "Global".blue());
+            }
+            for object in objects {
+                match object.valid_lua().and(object.valid_xml()) {
+                    Ok(_) => println!("  {object}"),
+                    Err(err) => println!("  {object} ({})", err.to_string().red()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns why `tag` doesn't follow the `lua/<FilePath>.lua` and `xml/<FilePath>.xml` naming convention,
+/// or [`None`] if no specific problem has been found.
+fn problem(tag: &str) -> Option<String> {
+    if tag.trim() != tag {
+        return Some("it starts or ends with whitespace".into());
+    }
+    if tag.contains('\\') {
+        return Some("it contains `\\`, paths in tags are separated by `/`".into());
+    }
+
+    // Tags are prefixed with the namespace of the project, if it has one
+    let segments = tag.split('/').collect_vec();
+    let tag = match (namespace(), segments.as_slice()) {
+        (Some(namespace), [first, ..]) if *first == namespace => &tag[namespace.len() + 1..],
+        (Some(namespace), _) => {
+            return Some(format!("tags of this project start with `{namespace}/`"));
+        }
+        (None, [first, "lua" | "xml", ..]) => {
+            #[rustfmt::skip]
+            return Some(format!("it belongs to the namespace `{first}`, but this project doesn't set `namespace` in its config"));
+        }
+        (None, _) => tag,
+    };
+
+    let (kind, file_path) = match tag.split_once('/') {
+        Some((kind @ ("lua" | "xml"), file_path)) => (kind, file_path),
+        Some((kind, _)) if ["lua", "xml"].contains(&kind.to_lowercase().as_str()) => {
+            return Some(format!(
+                "tags are case sensitive, use `{}/`",
+                kind.to_lowercase()
+            ));
+        }
+        _ => return Some("it has to start with `lua/` or `xml/`".into()),
+    };
+
+    let extensions = match kind {
+        "lua" => &extensions().lua,
+        _ => &extensions().xml,
+    };
+    let extension = extensions
+        .iter()
+        .find(|ext| file_path.ends_with(&format!(".{ext}")));
+    match extension {
+        Some(ext) if file_path.len() == ext.len() + 1 => Some("the file name is empty".into()),
+        Some(_) => None,
+        None => Some(format!(
+            "`{}/` tags have to end in {}",
+            kind,
+            extensions
+                .iter()
+                .map(|ext| format!("`.{ext}`"))
+                .join(" or ")
+        )),
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use serde::{Deserialize, Serialize};
+use ttsst::{Object, Save};
+
+/// Path of the manifest containing the frozen GUIDs. It is part of the project, so that it can be committed.
+pub const MANIFEST_FILE: &str = "guids.json";
+
+/// The GUID of an object and the tags it carried, when the GUIDs have been frozen.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Frozen {
+    pub guid: String,
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+/// A frozen object that no longer carries its tags, e.g. because it has been copied in-game,
+/// which assigns a new GUID to the copy.
+pub struct Change<'a> {
+    pub frozen: &'a Frozen,
+    /// The object with the frozen GUID, if it still exists.
+    pub object: Option<&'a Object>,
+    /// Objects that carry the frozen tags instead, and aren't frozen themselves.
+    pub candidates: Vec<&'a Object>,
+}
+
+/// Records the GUIDs of the objects in `save` that have a lua script or xml ui attached, together with their tags.
+pub fn freeze(save: &Save) -> Result<()> {
+    let frozen = save
+        .objects
+        .iter()
+        .filter_map(|object| {
+            let lua = object.valid_lua().ok().flatten();
+            let xml = object.valid_xml().ok().flatten();
+            let tags = lua
+                .into_iter()
+                .chain(xml)
+                .map(|tag| tag.into_inner())
+                .collect_vec();
+            (!tags.is_empty()).then(|| Frozen {
+                guid: object.guid.clone(),
+                name: object.display_name().into(),
+                tags,
+            })
+        })
+        .sorted_by(|a, b| a.guid.cmp(&b.guid))
+        .collect_vec();
+
+    write(&frozen)?;
+    info!(
+        "froze the GUIDs of {} object(s) in '{}'",
+        frozen.len(),
+        MANIFEST_FILE.yellow()
+    );
+    Ok(())
+}
+
+pub fn read() -> Result<Vec<Frozen>> {
+    match fs::read_to_string(MANIFEST_FILE) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!("no GUIDs have been frozen, use `ttsst freeze-guids` to freeze them")
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn write(frozen: &[Frozen]) -> Result<()> {
+    fs::write(MANIFEST_FILE, serde_json::to_string_pretty(frozen)? + "\n")?;
+    Ok(())
+}
+
+/// Returns the frozen objects whose GUID no longer carries all of their tags in `save`.
+pub fn changes<'a>(save: &'a Save, frozen: &'a [Frozen]) -> Vec<Change<'a>> {
+    let carries =
+        |object: &Object, tag: &String| object.tags.iter().any(|t| t.clone().into_inner() == *tag);
+
+    frozen
+        .iter()
+        .filter_map(|entry| {
+            let object = save.objects.find_object(&entry.guid).ok();
+            if object.is_some_and(|object| entry.tags.iter().all(|tag| carries(object, tag))) {
+                return None;
+            }
+
+            let candidates = save
+                .objects
+                .iter()
+                .filter(|object| !frozen.iter().any(|other| other.guid == object.guid))
+                .filter(|object| entry.tags.iter().any(|tag| carries(object, tag)))
+                .collect_vec();
+            Some(Change {
+                frozen: entry,
+                object,
+                candidates,
+            })
+        })
+        .collect()
+}
+
+/// Returns the names of the scripts in `save` that contain `guid` as a string literal,
+/// which break if the object with that GUID no longer exists.
+pub fn references(save: &Save, guid: &str) -> Vec<String> {
+    let literals = [format!("\"{guid}\""), format!("'{guid}'")];
+    let contains = |script: &str| literals.iter().any(|literal| script.contains(literal));
+
+    let global = contains(&save.lua_script).then(|| "Global".bright_white().bold().to_string());
+    let objects = save
+        .objects
+        .iter()
+        .filter(|object| contains(&object.lua_script))
+        .map(|object| object.to_string());
+    global.into_iter().chain(objects).collect()
+}
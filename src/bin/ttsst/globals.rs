@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use ttsst::Save;
+
+use crate::lua;
+
+/// Globals of the Tabletop Simulator API and the lua standard library, that scripts shouldn't redefine.
+const API: &[&str] = &[
+    // Tabletop Simulator
+    "self",
+    "Global",
+    "Backgrounds",
+    "Color",
+    "Grid",
+    "Hands",
+    "Info",
+    "JSON",
+    "Lighting",
+    "MusicPlayer",
+    "Notes",
+    "Physics",
+    "Player",
+    "Tables",
+    "Time",
+    "Timer",
+    "Turns",
+    "UI",
+    "Vector",
+    "Wait",
+    "WebRequest",
+    "addContextMenuItem",
+    "addHotkey",
+    "broadcastToAll",
+    "broadcastToColor",
+    "clearContextMenu",
+    "clearPixelPaint",
+    "clearVectorPaint",
+    "copy",
+    "destroyObject",
+    "getAllObjects",
+    "getObjectFromGUID",
+    "getObjects",
+    "getObjectsWithAllTags",
+    "getObjectsWithAnyTags",
+    "getObjectsWithTag",
+    "getSeatedPlayers",
+    "getVectorLines",
+    "group",
+    "log",
+    "logStyle",
+    "paste",
+    "printToAll",
+    "printToColor",
+    "sendExternalMessage",
+    "setLookingForPlayers",
+    "setVectorLines",
+    "showHotkeyConfig",
+    "spawnObject",
+    "spawnObjectData",
+    "spawnObjectJSON",
+    "startLuaCoroutine",
+    "stringColorToRGB",
+    // Lua
+    "_G",
+    "assert",
+    "coroutine",
+    "error",
+    "getmetatable",
+    "ipairs",
+    "math",
+    "next",
+    "os",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawset",
+    "require",
+    "select",
+    "setmetatable",
+    "string",
+    "table",
+    "tonumber",
+    "tostring",
+    "type",
+    "unpack",
+    "xpcall",
+];
+
+/// Reports globals that are defined by the scripts of multiple objects, and globals that
+/// shadow a name of the Tabletop Simulator API.
+///
+/// Event handlers like `onLoad` are expected to be defined by every script, and aren't reported
+/// unless they shadow the API. Objects with identical scripts are treated as a single script.
+pub fn report(save: &Save) -> Result<()> {
+    let mut scripts = BTreeMap::<&str, String>::new();
+    if !save.lua_script.is_empty() {
+        scripts.insert(&save.lua_script, "Global".blue().to_string());
+    }
+    for object in save
+        .objects
+        .iter()
+        .filter(|object| !object.lua_script.is_empty())
+    {
+        let name = match object.valid_lua() {
+            Ok(Some(tag)) => tag.to_string(),
+            _ => object.to_string(),
+        };
+        scripts.entry(&object.lua_script).or_insert(name);
+    }
+
+    let mut definitions = BTreeMap::<String, Vec<&str>>::new();
+    for (script, name) in &scripts {
+        for global in lua::globals(script).into_iter().unique() {
+            definitions.entry(global).or_default().push(name);
+        }
+    }
+
+    let mut collisions = 0;
+    for (global, names) in &definitions {
+        if API.contains(&global.as_str()) {
+            for name in names {
+                warn!(
+                    "{} in {name} shadows the Tabletop Simulator API",
+                    global.bold()
+                );
+                collisions += 1;
+            }
+        } else if names.len() > 1 && !is_event(global) {
+            warn!("{} is defined by {}", global.bold(), names.join(", "));
+            collisions += 1;
+        }
+    }
+
+    match collisions {
+        0 => info!("no collisions found in {} script(s)", scripts.len()),
+        _ => info!(
+            "found {collisions} possible collision(s) in {} script(s)",
+            scripts.len()
+        ),
+    }
+    Ok(())
+}
+
+/// Returns true if `global` is named like an event handler, e.g. `onLoad` or `onObjectDrop`.
+fn is_event(global: &str) -> bool {
+    global
+        .strip_prefix("on")
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(char::is_uppercase)
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use ttsst::tags::extensions;
+use ttsst::Save;
+
+use crate::app::{find_files, read_file};
+use crate::config::normalize;
+use crate::unused::{references, roots};
+
+/// Output format of [`print`].
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum Format {
+    /// Graphviz, e.g. `ttsst graph | dot -Tsvg > graph.svg`
+    #[default]
+    Dot,
+    /// Mermaid flowchart, which can be embedded in Markdown
+    Mermaid,
+}
+
+/// The modules of the project, and the modules they include or require.
+struct Graph {
+    /// Every lua file of the project, and every file that is referenced by one, by their normalized path.
+    nodes: BTreeSet<String>,
+    edges: BTreeSet<(String, String)>,
+    /// Files that are attached to an object, or are a Global file.
+    roots: BTreeSet<String>,
+}
+
+impl Graph {
+    /// Returns the nodes that `from` depends on, directly or indirectly.
+    fn reachable(&self, from: &[&String]) -> BTreeSet<String> {
+        let mut reached = BTreeSet::new();
+        let mut queue = from.to_vec();
+        while let Some(node) = queue.pop() {
+            if !reached.insert(node.clone()) {
+                continue;
+            }
+            queue.extend(self.edges.iter().filter(|(a, _)| a == node).map(|(_, b)| b));
+        }
+        reached
+    }
+
+    /// Returns the nodes that aren't reachable from a root.
+    fn orphans(&self) -> BTreeSet<String> {
+        let reached = self.reachable(&self.roots.iter().collect_vec());
+        self.nodes.difference(&reached).cloned().collect()
+    }
+
+    /// Returns the groups of nodes that depend on each other, so that loading one of them ends up loading itself again.
+    fn cycles(&self) -> Vec<BTreeSet<String>> {
+        let reachable = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let next = self.edges.iter().filter(|(a, _)| a == node).map(|(_, b)| b);
+                (node, self.reachable(&next.collect_vec()))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut cycles: Vec<BTreeSet<String>> = Vec::new();
+        for (node, reached) in &reachable {
+            if !reached.contains(*node) || cycles.iter().any(|cycle| cycle.contains(*node)) {
+                continue;
+            }
+            let cycle = reached
+                .iter()
+                .filter(|other| reachable[other].contains(*node))
+                .cloned()
+                .collect();
+            cycles.push(cycle);
+        }
+        cycles
+    }
+}
+
+/// Prints the dependency graph of the lua modules of the project, which are connected by `#include` and `require`,
+/// or writes it to `output`.
+///
+/// Files that are attached to an object of the `save`, or are a Global file, are the roots of the graph.
+/// Modules that can't be reached from a root, and modules that depend on themselves, are highlighted and reported.
+pub fn print(save: &Save, format: Format, output: Option<PathBuf>) -> Result<()> {
+    let graph = build(save)?;
+    let orphans = graph.orphans();
+    let cycles = graph.cycles();
+
+    let text = match format {
+        Format::Dot => dot(&graph, &orphans, &cycles)?,
+        Format::Mermaid => mermaid(&graph, &orphans, &cycles)?,
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, text)?;
+            info!("wrote the graph to '{}'", path.to_slash_lossy().yellow());
+        }
+        None => print!("{text}"),
+    }
+
+    for cycle in &cycles {
+        warn!(
+            "cycle between {}",
+            cycle
+                .iter()
+                .map(|node| format!("'{}'", node.yellow()))
+                .join(", ")
+        );
+    }
+    for orphan in &orphans {
+        warn!(
+            "'{}' isn't attached, a Global file, or used by one",
+            orphan.yellow()
+        );
+    }
+    #[rustfmt::skip]
+    info!("found {} module(s) with {} dependencies, {} cycle(s) and {} orphan(s)", graph.nodes.len(), graph.edges.len(), cycles.len(), orphans.len());
+    Ok(())
+}
+
+/// Builds the graph of the lua files of the project, see [`print`].
+fn build(save: &Save) -> Result<Graph> {
+    let extensions = extensions();
+    let files = find_files(Path::new("."), &|path| extensions.is_lua(path))?;
+    let key = |path: &Path| normalize(path).to_slash_lossy().to_string();
+
+    let roots = roots(save, &files)?
+        .into_iter()
+        .filter(|path| extensions.is_lua(path))
+        .collect_vec();
+    let mut graph = Graph {
+        nodes: BTreeSet::new(),
+        edges: BTreeSet::new(),
+        roots: roots.iter().map(|path| key(path)).collect(),
+    };
+
+    let mut queue = files.into_iter().chain(roots).collect_vec();
+    while let Some(path) = queue.pop() {
+        if !graph.nodes.insert(key(&path)) {
+            continue;
+        }
+        // Files that don't exist are reported by `reload` instead
+        let Ok(script) = read_file(&path) else {
+            continue;
+        };
+        for reference in references(&script, &path) {
+            graph.edges.insert((key(&path), key(&reference)));
+            queue.push(reference);
+        }
+    }
+    Ok(graph)
+}
+
+/// Returns `true` if the edge from `a` to `b` is part of one of the `cycles`.
+fn in_cycle(cycles: &[BTreeSet<String>], a: &String, b: &String) -> bool {
+    cycles
+        .iter()
+        .any(|cycle| cycle.contains(a) && cycle.contains(b))
+}
+
+/// Formats the graph for Graphviz. Roots are drawn as boxes,
+/// orphans are dashed and gray, and the dependencies of cycles are red.
+fn dot(graph: &Graph, orphans: &BTreeSet<String>, cycles: &[BTreeSet<String>]) -> Result<String> {
+    let mut dot = String::new();
+    writeln!(dot, "digraph modules {{")?;
+    writeln!(dot, "  node [shape=ellipse];")?;
+    for node in &graph.nodes {
+        let mut attributes = Vec::new();
+        if graph.roots.contains(node) {
+            attributes.push("shape=box");
+        }
+        if orphans.contains(node) {
+            attributes.push("style=dashed, color=gray");
+        }
+        match attributes.is_empty() {
+            true => writeln!(dot, "  {node:?};")?,
+            false => writeln!(dot, "  {node:?} [{}];", attributes.join(", "))?,
+        }
+    }
+    for (a, b) in &graph.edges {
+        match in_cycle(cycles, a, b) {
+            true => writeln!(dot, "  {a:?} -> {b:?} [color=red];")?,
+            false => writeln!(dot, "  {a:?} -> {b:?};")?,
+        }
+    }
+    writeln!(dot, "}}")?;
+    Ok(dot)
+}
+
+/// Formats the graph as a Mermaid flowchart. Roots are drawn as boxes with rounded corners,
+/// orphans are dashed, and the dependencies of cycles are thick.
+fn mermaid(
+    graph: &Graph,
+    orphans: &BTreeSet<String>,
+    cycles: &[BTreeSet<String>],
+) -> Result<String> {
+    // Paths aren't valid ids, so the nodes are numbered
+    let ids = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node, format!("n{i}")))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut mermaid = String::new();
+    writeln!(mermaid, "flowchart LR")?;
+    for (node, id) in &ids {
+        let label = node.replace('"', "#quot;");
+        match graph.roots.contains(*node) {
+            true => writeln!(mermaid, "  {id}(\"{label}\")")?,
+            false => writeln!(mermaid, "  {id}[\"{label}\"]")?,
+        }
+    }
+    for (a, b) in &graph.edges {
+        let arrow = if in_cycle(cycles, a, b) { "==>" } else { "-->" };
+        writeln!(mermaid, "  {} {arrow} {}", ids[a], ids[b])?;
+    }
+    if !orphans.is_empty() {
+        writeln!(
+            mermaid,
+            "  classDef orphan stroke-dasharray: 5 5, color: gray;"
+        )?;
+        let orphans = orphans.iter().map(|node| &ids[node]).join(",");
+        writeln!(mermaid, "  class {orphans} orphan;")?;
+    }
+    Ok(mermaid)
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use regex::Regex;
+use ttsst::Save;
+
+use crate::app::SaveFile;
+
+/// Searches the lua scripts and xml ui of the save at `path` for `exprs`, and prints every matching line.
+/// Returns a summary with the number of matches.
+pub fn grep(path: &Path, exprs: &Regex) -> Result<String> {
+    let save_file = SaveFile::read_from_path(path)?;
+    let save = &save_file.save;
+
+    let mut count = 0;
+    for (name, script) in scripts(save) {
+        for (index, line) in script.lines().enumerate() {
+            if exprs.is_match(line) {
+                println!(
+                    "{}:{}: {}",
+                    name,
+                    (index + 1).to_string().green(),
+                    line.trim()
+                );
+                count += 1;
+            }
+        }
+    }
+
+    Ok(format!("{} match(es)", count))
+}
+
+/// Returns the global lua script and xml ui, and the scripts of every object,
+/// together with the name of their owner.
+fn scripts(save: &Save) -> Vec<(String, &str)> {
+    let global = "Global".bright_white().bold().to_string();
+    let mut scripts = vec![
+        (global.clone(), save.lua_script.as_str()),
+        (global, save.xml_ui.as_str()),
+    ];
+    for object in save.objects.iter() {
+        let name = format!("{}", object.guid.yellow());
+        scripts.push((name.clone(), &object.lua_script));
+        scripts.push((name, &object.xml_ui));
+    }
+    scripts
+}
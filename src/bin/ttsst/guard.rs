@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use serde::{Deserialize, Serialize};
+use ttsst::api::ScriptState;
+use ttsst::{Prompter, Save};
+
+use crate::app::read_file;
+use crate::config::config;
+use crate::{include, transform, utils};
+
+/// Path of the file containing the scripts that have been pushed to the game most recently.
+const PUSHED_FILE: &str = ".ttsst/pushed.json";
+
+/// The lua script and xml ui of an object, as it has been pushed to the game.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Pushed {
+    pub script: String,
+    pub ui: String,
+}
+
+/// Options offered for each object that has been edited in-game.
+const KEEP_LOCAL: &str = "Keep local: overwrite the in-game changes";
+const KEEP_IN_GAME: &str = "Keep in-game: pull the in-game changes into the files";
+const MERGE: &str = "Merge: edit both versions in the editor";
+
+/// An object whose scripts have been edited in-game since they have been pushed.
+struct Conflict<'a> {
+    guid: &'a str,
+    name: String,
+    state: &'a ScriptState,
+}
+
+/// Compares the in-game `states` of the objects in `guids` with the scripts that have been pushed most recently.
+/// If any of them have been edited in-game, the user is asked for each of them whether to overwrite the in-game changes,
+/// to pull them into their files, or to merge both versions in the editor.
+///
+/// Objects whose in-game changes are kept or merged are updated in the `save` as well, so that the resolved version gets pushed.
+pub fn check(
+    save: &mut Save,
+    guids: &[String],
+    states: &[ScriptState],
+    prompter: &dyn Prompter,
+) -> Result<()> {
+    let pushed = read()?;
+    let conflicts = states
+        .iter()
+        .filter(|state| guids.contains(&state.guid))
+        .filter_map(|state| {
+            let pushed = pushed.get(&state.guid)?;
+            let ui = state.ui.as_deref().unwrap_or_default();
+            (pushed.script != state.script || pushed.ui != ui).then(|| Conflict {
+                guid: &state.guid,
+                name: match save.objects.find_object(&state.guid) {
+                    Ok(object) => object.to_string(),
+                    Err(_) => "Global".bright_white().bold().to_string(),
+                },
+                state,
+            })
+        })
+        .collect_vec();
+
+    if conflicts.is_empty() || config().force {
+        return Ok(());
+    }
+
+    for conflict in &conflicts {
+        warn!(
+            "{} has been edited in-game since it was last pushed",
+            conflict.name
+        );
+        // Global has no files that could be merged
+        let options = match save.objects.find_object(conflict.guid) {
+            Ok(_) => vec![KEEP_LOCAL, KEEP_IN_GAME, MERGE],
+            Err(_) => vec![KEEP_LOCAL, KEEP_IN_GAME],
+        };
+        let message = format!("How should the changes of {} be resolved?", conflict.name);
+        let options = options.into_iter().map(String::from).collect();
+        let choice = prompter.select(&message, options).map_err(|_| {
+            anyhow!("the in-game changes would be overwritten, use --force to overwrite them")
+        })?;
+        match choice.as_str() {
+            KEEP_IN_GAME => pull(save, conflict)?,
+            MERGE => merge(save, conflict)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files of the object in `save` that has the guid of the `conflict`,
+/// together with their in-game version.
+fn files<'a>(save: &Save, conflict: &Conflict<'a>) -> Result<Vec<(PathBuf, &'a str)>> {
+    let Ok(object) = save.objects.find_object(conflict.guid) else {
+        return Ok(Vec::new());
+    };
+    let files = [
+        (object.valid_lua()?, conflict.state.script.as_str()),
+        (
+            object.valid_xml()?,
+            conflict.state.ui.as_deref().unwrap_or_default(),
+        ),
+    ];
+    let mut paths = Vec::new();
+    for (tag, script) in files {
+        if let Some(tag) = tag {
+            paths.push((tag.path()?, script));
+        }
+    }
+    Ok(paths)
+}
+
+/// Writes the in-game scripts of a conflicting object into the files of its tags,
+/// and updates the object in the `save`, so that pushing it keeps the in-game version.
+///
+/// Included files are replaced with their `#include` directive or `<Include>` tag again, see [`include::unbundle_file`].
+/// Files that are transformed in a way that can't be reverted are skipped, see [`transform::irreversible`].
+fn pull(save: &mut Save, conflict: &Conflict) -> Result<()> {
+    let ui = conflict.state.ui.as_deref().unwrap_or_default();
+    if conflict.guid == "-1" {
+        save.lua_script = conflict.state.script.clone();
+        save.xml_ui = ui.into();
+        info!("kept the in-game version of {}", conflict.name);
+        return Ok(());
+    }
+
+    for (path, script) in files(save, conflict)? {
+        if let Some(name) = transform::irreversible(&path)? {
+            #[rustfmt::skip]
+            warn!("skipped '{}', since the '{}' transformer can't be reverted", path.to_slash_lossy().yellow(), name.yellow());
+            continue;
+        }
+        fs::write(&path, include::unbundle_file(&path, script))?;
+        #[rustfmt::skip]
+        info!("pulled {} into '{}'", conflict.name, path.to_slash_lossy().yellow());
+    }
+    let object = save.objects.find_object_mut(conflict.guid)?;
+    object.set_lua_script(conflict.state.script.as_str());
+    object.set_xml_ui(ui);
+    Ok(())
+}
+
+/// Writes the local and the in-game version of each file of a conflicting object into a temporary file,
+/// and opens a file containing both versions separated by conflict markers in the editor.
+/// Once the editor is closed, the result is written into the file of the object, and the object is updated in the `save`.
+///
+/// The in-game version is unbundled like by [`pull`], and files whose transformation can't be reverted keep the local version.
+fn merge(save: &mut Save, conflict: &Conflict) -> Result<()> {
+    // The directory is removed once the merge has ended
+    let dir = tempfile::Builder::new()
+        .prefix(&format!("ttsst-{}-", conflict.guid))
+        .tempdir()?;
+
+    for (path, in_game) in files(save, conflict)? {
+        if let Some(name) = transform::irreversible(&path)? {
+            #[rustfmt::skip]
+            warn!("kept the local version of '{}', since the '{}' transformer can't be reverted", path.to_slash_lossy().yellow(), name.yellow());
+            continue;
+        }
+        let in_game = include::unbundle_file(&path, in_game);
+        let local = read_file(&path)?;
+        if local == in_game {
+            continue;
+        }
+
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        let file = |name: &str| dir.path().join(format!("{name}.{ext}"));
+        fs::write(file("local"), &local)?;
+        fs::write(file("in-game"), &in_game)?;
+        #[rustfmt::skip]
+        let merged = format!("<<<<<<< local\n{}\n=======\n{}\n>>>>>>> in-game\n", local.trim_end(), in_game.trim_end());
+        fs::write(file("merged"), merged)?;
+
+        #[rustfmt::skip]
+        info!("merging '{}', both versions are in '{}'", path.to_slash_lossy().yellow(), dir.path().to_slash_lossy().yellow());
+        utils::edit(&file("merged"))?;
+        let merged = fs::read_to_string(file("merged"))?;
+        if merged
+            .lines()
+            .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"))
+        {
+            let merged = file("merged");
+            // Keep the merged file, so that the merge doesn't have to be started over
+            let _ = dir.into_path();
+            #[rustfmt::skip]
+            bail!("'{}' still contains conflict markers", merged.to_slash_lossy().yellow());
+        }
+        fs::write(&path, merged)?;
+        #[rustfmt::skip]
+        info!("merged {} into '{}'", conflict.name, path.to_slash_lossy().yellow());
+    }
+
+    let object = save.objects.find_object_mut(conflict.guid)?;
+    if let Some(tag) = object.valid_lua()? {
+        object.set_lua_script(transform::read_lua(tag.path()?)?.script);
+    }
+    if let Some(tag) = object.valid_xml()? {
+        object.set_xml_ui(include::read_xml(tag.path()?)?.script);
+    }
+    Ok(())
+}
+
+/// Records the scripts of the objects in `guids` as pushed.
+/// The global lua script and xml ui use the guid `-1`.
+pub fn record(save: &Save, guids: &[String]) -> Result<()> {
+    let mut pushed = read()?;
+    for guid in guids {
+        let (script, ui) = match guid.as_str() {
+            "-1" => (save.lua_script.clone(), save.xml_ui.clone()),
+            guid => match save.objects.find_object(guid) {
+                Ok(object) => (object.lua_script.to_string(), object.xml_ui.to_string()),
+                Err(_) => continue,
+            },
+        };
+        pushed.insert(guid.clone(), Pushed { script, ui });
+    }
+
+    if let Some(dir) = Path::new(PUSHED_FILE).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(PUSHED_FILE, serde_json::to_string(&pushed)?)?;
+    Ok(())
+}
+
+/// Reads the scripts that have been pushed most recently.
+pub fn read() -> Result<HashMap<String, Pushed>> {
+    match Path::new(PUSHED_FILE).exists() {
+        true => Ok(serde_json::from_str(&fs::read_to_string(PUSHED_FILE)?)?),
+        false => Ok(HashMap::new()),
+    }
+}
@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+use itertools::Itertools;
+use ttsst::Save;
+
+use crate::config::config;
+use crate::lua;
+
+/// Marks the start of the helper module in the Global script.
+const BEGIN: &str = "--[[ttsst:helper]]";
+/// Marks the end of the helper module in the Global script.
+const END: &str = "--[[/ttsst:helper]]";
+
+/// The helper module. It is only defined if the scripts haven't defined `ttsst` themselves.
+///
+/// Messages sent to ttsst are tables with a `ttsst` field, containing the kind of the message.
+const HELPER: &str = r##"if ttsst == nil then
+    ttsst = { version = "{version}", errorHooks = {} }
+
+    -- Prints the values separated by spaces, with tables encoded as JSON
+    function ttsst.log(...)
+        local values = {}
+        for i = 1, select("#", ...) do
+            local value = select(i, ...)
+            if type(value) == "table" then
+                value = JSON.encode(value)
+            end
+            values[i] = tostring(value)
+        end
+        print(table.concat(values, " "))
+    end
+
+    -- Sends a call of the procedure `name` with the table `args` to ttsst
+    function ttsst.rpc(name, args)
+        sendExternalMessage({ ttsst = "rpc", name = name, args = args or {} })
+    end
+
+    -- Registers a function that is called with the message of every reported error
+    function ttsst.onError(hook)
+        table.insert(ttsst.errorHooks, hook)
+    end
+
+    -- Calls the error hooks and sends the error to ttsst
+    function ttsst.reportError(message)
+        message = tostring(message)
+        for _, hook in ipairs(ttsst.errorHooks) do
+            pcall(hook, message)
+        end
+        sendExternalMessage({ ttsst = "error", message = message })
+    end
+
+    -- Returns a function that calls `fn`, and reports its errors instead of raising them
+    function ttsst.protect(fn)
+        return function(...)
+            local results = table.pack(pcall(fn, ...))
+            if not results[1] then
+                ttsst.reportError(results[2])
+                return nil
+            end
+            return table.unpack(results, 2, results.n)
+        end
+    end
+end"##;
+
+/// Returns the `save` as it gets written and pushed, if `helper` is enabled in the config:
+/// the helper module is prepended to the Global script, which provides `ttsst.log()`, `ttsst.rpc()` and error reporting.
+///
+/// The module is injected into the first line, so that the line numbers of errors still match the file.
+/// A module that has been injected before is replaced.
+pub fn inject(save: Cow<'_, Save>) -> Cow<'_, Save> {
+    if !config().helper {
+        return save;
+    }
+    let mut save = save;
+    let script = strip(&save.lua_script).to_string();
+    save.to_mut().lua_script = format!("{BEGIN} {} {END} {script}", module());
+    save
+}
+
+/// Removes the helper module from the Global script of the `save`, which reverses [`inject`].
+/// Saves that contain it are cleaned up as well if `helper` has been disabled since.
+pub fn remove(save: &mut Save) {
+    let script = strip(&save.lua_script);
+    if script.len() != save.lua_script.len() {
+        save.lua_script = script.to_string();
+    }
+}
+
+/// Returns the helper module on a single line.
+fn module() -> String {
+    let helper = HELPER.replace("{version}", env!("CARGO_PKG_VERSION"));
+    lua::minify(&helper)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .join(" ")
+}
+
+/// Returns the Global `script` without the helper module.
+fn strip(script: &str) -> &str {
+    script
+        .strip_prefix(BEGIN)
+        .and_then(|rest| rest.split_once(END))
+        .map_or(script, |(_, rest)| rest.strip_prefix(' ').unwrap_or(rest))
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use ttsst::{Object, Save};
+
+use crate::utils::line_count;
+
+/// Path of the journal containing the most recent reload operations.
+const HISTORY_FILE: &str = ".ttsst/history.json";
+
+/// Maximum number of reload operations that are kept in the journal.
+const MAX_ENTRIES: usize = 50;
+
+/// A single reload operation.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Entry {
+    pub timestamp: String,
+    pub changes: Vec<Change>,
+}
+
+/// A lua script or xml ui that has been changed by a reload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Change {
+    pub guid: String,
+    pub name: String,
+    pub file: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl Entry {
+    /// Compares the scripts of `save` with the scripts of `before`,
+    /// and creates a new entry containing every script that has changed.
+    pub fn new(before: &Save, save: &Save) -> Self {
+        let mut changes = Vec::new();
+
+        // Global lua script and xml ui
+        #[rustfmt::skip]
+        changes.extend(Change::new("-1", "Global", "Global Lua", &before.lua_script, &save.lua_script));
+        #[rustfmt::skip]
+        changes.extend(Change::new("-1", "Global", "Global UI", &before.xml_ui, &save.xml_ui));
+
+        for object in save.objects.iter() {
+            let Ok(old) = before.objects.find_object(&object.guid) else {
+                continue;
+            };
+            let (lua, xml) = files(object);
+            #[rustfmt::skip]
+            changes.extend(Change::new(&object.guid, object.display_name(), &lua, &old.lua_script, &object.lua_script));
+            #[rustfmt::skip]
+            changes.extend(Change::new(&object.guid, object.display_name(), &xml, &old.xml_ui, &object.xml_ui));
+        }
+
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            changes,
+        }
+    }
+
+    /// Returns a list of unique files that have been pushed.
+    pub fn files(&self) -> Vec<&str> {
+        self.changes
+            .iter()
+            .map(|c| c.file.as_str())
+            .unique()
+            .collect()
+    }
+
+    /// Returns a short summary of added and removed lines.
+    pub fn summary(&self) -> String {
+        let (insertions, deletions) = self.changes.iter().fold((0, 0), |acc, change| {
+            let (ins, del) = change.line_count();
+            (acc.0 + ins, acc.1 + del)
+        });
+        format!(
+            "{} object(s), {}, {}",
+            self.changes.iter().unique_by(|c| &c.guid).count(),
+            format!("+{insertions}").green(),
+            format!("-{deletions}").red(),
+        )
+    }
+}
+
+impl Change {
+    fn new(guid: &str, name: &str, file: &str, before: &str, after: &str) -> Option<Self> {
+        (before != after).then(|| Self {
+            guid: guid.into(),
+            name: name.into(),
+            file: file.into(),
+            before: before.into(),
+            after: after.into(),
+        })
+    }
+
+    /// Returns the number of inserted and deleted lines.
+    fn line_count(&self) -> (usize, usize) {
+        line_count(&self.before, &self.after)
+    }
+}
+
+/// Appends `entry` to the journal, if it contains any changes.
+/// Only the last [`MAX_ENTRIES`] entries are kept.
+pub fn record(entry: Entry) -> Result<()> {
+    if entry.changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut entries = read()?;
+    entries.push(entry);
+    let skip = entries.len().saturating_sub(MAX_ENTRIES);
+    let entries = entries.into_iter().skip(skip).collect_vec();
+
+    if let Some(dir) = Path::new(HISTORY_FILE).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(HISTORY_FILE, serde_json::to_string_pretty(&entries)?)?;
+    debug!("recorded reload in {}", HISTORY_FILE);
+    Ok(())
+}
+
+/// Reads all entries from the journal.
+pub fn read() -> Result<Vec<Entry>> {
+    match Path::new(HISTORY_FILE).exists() {
+        true => Ok(serde_json::from_str(&fs::read_to_string(HISTORY_FILE)?)?),
+        false => Ok(Vec::new()),
+    }
+}
+
+/// Prints a list of all recorded reload operations.
+pub fn list() -> Result<()> {
+    let entries = read()?;
+    if entries.is_empty() {
+        info!("no reloads have been recorded yet");
+    }
+
+    for (index, entry) in entries.iter().enumerate() {
+        println!(
+            "{} {} {} ({})",
+            format!("{index:>3}").bright_white().bold(),
+            timestamp(entry).bright_white(),
+            entry.files().iter().map(|file| file.yellow()).join(", "),
+            entry.summary(),
+        );
+    }
+    Ok(())
+}
+
+/// Prints the diff of the reload operation at `index`.
+pub fn show(index: usize) -> Result<()> {
+    let entries = read()?;
+    let entry = entries
+        .get(index)
+        .ok_or_else(|| anyhow!("no reload with the index {} exists", index))?;
+
+    println!("{} {}", timestamp(entry).bright_white(), entry.summary());
+    for change in &entry.changes {
+        println!();
+        #[rustfmt::skip]
+        println!("{} ({}) {}", change.guid.yellow(), change.name.bright_white().bold(), change.file.yellow());
+
+        let diff = TextDiff::from_lines(&change.before, &change.after);
+        for hunk in diff.unified_diff().context_radius(2).iter_hunks() {
+            println!("{}", hunk.header().to_string().cyan());
+            for change in hunk.iter_changes() {
+                let line = change.to_string_lossy();
+                let line = line.trim_end_matches('\n');
+                match change.tag() {
+                    ChangeTag::Insert => println!("{}", format!("+{line}").green()),
+                    ChangeTag::Delete => println!("{}", format!("-{line}").red()),
+                    ChangeTag::Equal => println!(" {line}"),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the file names of the lua and xml tags of `object`.
+fn files(object: &Object) -> (String, String) {
+    let tag = |tag: Option<ttsst::Tag>, default: &str| match tag {
+        Some(tag) => tag.into_inner(),
+        None => default.into(),
+    };
+    (
+        tag(object.valid_lua().ok().flatten(), "Lua Script"),
+        tag(object.valid_xml().ok().flatten(), "XML UI"),
+    )
+}
+
+/// Formats the timestamp of `entry` using the local time.
+fn timestamp(entry: &Entry) -> String {
+    match chrono::DateTime::parse_from_rfc3339(&entry.timestamp) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        Err(_) => entry.timestamp.clone(),
+    }
+}
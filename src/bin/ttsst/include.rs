@@ -0,0 +1,377 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use colored::*;
+use path_slash::PathExt;
+use regex::Regex;
+
+use crate::app::read_file;
+use crate::config::{config, normalize};
+use crate::utils::long_path;
+
+/// Matches a line with an `#include <path>` or `#include path` directive.
+pub const INCLUDE: &str = r"^\s*#include\s+(?:<(?<wrapped>[^>]+)>|(?<plain>\S+))\s*$";
+
+/// Matches an `<Include src="path"/>` tag in a xml ui.
+pub const XML_INCLUDE: &str = r#"<Include\s+src\s*=\s*"(?<src>[^"]+)"\s*/>"#;
+
+/// Returns the [`INCLUDE`] regex, which is only compiled once.
+fn include_exprs() -> &'static Regex {
+    static EXPRS: OnceLock<Regex> = OnceLock::new();
+    EXPRS.get_or_init(|| Regex::new(INCLUDE).unwrap())
+}
+
+/// Returns the [`XML_INCLUDE`] regex, which is only compiled once.
+fn xml_include_exprs() -> &'static Regex {
+    static EXPRS: OnceLock<Regex> = OnceLock::new();
+    EXPRS.get_or_init(|| Regex::new(XML_INCLUDE).unwrap())
+}
+
+/// A lua script whose `#include` directives have been expanded.
+#[derive(Debug)]
+pub struct Expanded {
+    pub script: String,
+    /// Paths of the files that have been included, directly or by another included file.
+    pub includes: Vec<PathBuf>,
+}
+
+/// Replaces the `#include <path>` and `#include path` directives of the lua `script`, which has been read from `path`,
+/// with the content of the included files, using the convention of the official editor plugins.
+///
+/// Included files are enclosed in `----#include` comments, so that they can be told apart in the game.
+/// `#include <path>` additionally wraps the file in a `do ... end` block, so that its locals don't leak into the script.
+pub fn bundle(script: &str, path: &Path) -> Result<Expanded> {
+    let mut includes = Vec::new();
+    let script = expand_script(script, path, &mut vec![normalize(path)], &mut includes)?;
+    Ok(Expanded { script, includes })
+}
+
+/// Expands the directives of the file at `path`. `stack` contains the files that are currently being expanded,
+/// to detect files that include themselves.
+fn expand(path: &Path, stack: &mut Vec<PathBuf>, includes: &mut Vec<PathBuf>) -> Result<String> {
+    expand_script(&read_file(path)?, path, stack, includes)
+}
+
+/// Expands the directives of the `script` read from `path`, see [`expand`].
+fn expand_script(
+    script: &str,
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let exprs = include_exprs();
+    if !script.contains("#include") {
+        return Ok(script.into());
+    }
+
+    let mut expanded = Vec::new();
+    for line in script.lines() {
+        let Some(captures) = exprs.captures(line) else {
+            expanded.push(line.to_string());
+            continue;
+        };
+        let (name, wrapped) = match captures.name("wrapped") {
+            Some(name) => (name.as_str(), true),
+            None => (&captures["plain"], false),
+        };
+
+        let file = resolve(name, path)?;
+        if stack.contains(&normalize(&file)) {
+            #[rustfmt::skip]
+            bail!("'{}' includes itself through '{}'", file.to_slash_lossy().yellow(), path.to_slash_lossy().yellow());
+        }
+        if !includes.contains(&file) {
+            includes.push(file.clone());
+        }
+
+        stack.push(normalize(&file));
+        let content = expand(&file, stack, includes)?;
+        let content = content.strip_suffix('\n').unwrap_or(&content);
+        stack.pop();
+
+        let marker = format!("----{}", line.trim());
+        expanded.push(marker.clone());
+        match wrapped {
+            true => expanded.push(format!("do\n{content}\nend")),
+            false => expanded.push(content.into()),
+        }
+        expanded.push(marker);
+    }
+
+    let mut expanded = expanded.join("\n");
+    if script.ends_with('\n') {
+        expanded.push('\n');
+    }
+    Ok(expanded)
+}
+
+/// Reverts [`bundle`], replacing the included files that are enclosed in `----#include` comments
+/// with the `#include` directive again.
+pub fn unbundle(script: &str) -> String {
+    if !script.contains("----#include") {
+        return script.into();
+    }
+
+    let mut lines = Vec::new();
+    let mut open = None;
+    for line in script.lines() {
+        match open {
+            Some(marker) if line == marker => open = None,
+            Some(_) => (),
+            None if line.starts_with("----#include") => {
+                lines.push(&line[4..]);
+                open = Some(line);
+            }
+            None => lines.push(line),
+        }
+    }
+
+    let mut unbundled = lines.join("\n");
+    if script.ends_with('\n') {
+        unbundled.push('\n');
+    }
+    unbundled
+}
+
+/// Returns the file and line of that file that `line` of the bundled lua `script`, which has been read from `path`, comes from,
+/// by following the `----#include` comments [`bundle`] encloses the included files in.
+/// Lines that have been added by `bundle`, like the comments themselves, belong to the line of the `#include` directive.
+///
+/// Files that can't be resolved anymore, e.g. because they have been moved since the script has been pushed,
+/// are treated as part of the including file.
+pub fn locate(script: &str, path: &Path, line: usize) -> (PathBuf, usize) {
+    /// An included file, whose lines are counted while walking through the script.
+    struct Frame<'a> {
+        path: PathBuf,
+        marker: Option<&'a str>,
+        line: usize,
+        /// Whether the file has been wrapped in a `do ... end` block.
+        wrapped: bool,
+        /// Whether the `do` of the block hasn't been passed yet.
+        opening: bool,
+    }
+
+    let exprs = include_exprs();
+    let mut stack = vec![Frame {
+        path: path.into(),
+        marker: None,
+        line: 0,
+        wrapped: false,
+        opening: false,
+    }];
+    let mut location = (path.to_path_buf(), line);
+    let lines = script.lines().collect::<Vec<_>>();
+    for (index, current) in lines.iter().enumerate().take(line) {
+        let depth = stack.len() - 1;
+        let top = &mut stack[depth];
+        let directive = current
+            .strip_prefix("----")
+            .and_then(|directive| exprs.captures(directive));
+        let added = top.marker == Some(*current)
+            || std::mem::take(&mut top.opening)
+            || (top.wrapped && *current == "end" && lines.get(index + 1) == top.marker.as_ref());
+
+        if top.marker == Some(*current) {
+            stack.pop();
+        } else if let Some(captures) = directive {
+            top.line += 1;
+            let (name, wrapped) = match captures.name("wrapped") {
+                Some(name) => (name.as_str(), true),
+                None => (&captures["plain"], false),
+            };
+            let including = (top.path.clone(), top.line);
+            if let Ok(file) = resolve(name, &including.0) {
+                stack.push(Frame {
+                    path: file,
+                    marker: Some(current),
+                    line: 0,
+                    wrapped,
+                    opening: wrapped,
+                });
+            }
+            location = including;
+            continue;
+        } else if !added {
+            top.line += 1;
+        }
+
+        // Lines added by `bundle` belong to the `#include` directive in the including file
+        let frame = match added {
+            true => &stack[depth - 1],
+            false => &stack[depth],
+        };
+        location = (frame.path.clone(), frame.line);
+    }
+    location
+}
+
+/// Reads the xml ui at `path` and replaces its `<Include src="path"/>` tags with the content of the included files,
+/// using the convention of the official editor plugins.
+///
+/// Included files are enclosed in `<!-- include path -->` comments, so that they can be told apart in the game.
+pub fn read_xml<P: AsRef<Path>>(path: P) -> Result<Expanded> {
+    let mut includes = Vec::new();
+    let script = expand_xml(
+        path.as_ref(),
+        &mut vec![normalize(path.as_ref())],
+        &mut includes,
+    )?;
+    Ok(Expanded { script, includes })
+}
+
+/// Expands the `<Include>` tags of the file at `path`, see [`expand`].
+fn expand_xml(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let exprs = xml_include_exprs();
+    let xml = read_file(path)?;
+    if !xml.contains("<Include") {
+        return Ok(xml);
+    }
+
+    let mut expanded = String::new();
+    let mut last = 0;
+    for captures in exprs.captures_iter(&xml) {
+        let name = &captures["src"];
+        let file = resolve_xml(name, path)?;
+        if stack.contains(&normalize(&file)) {
+            #[rustfmt::skip]
+            bail!("'{}' includes itself through '{}'", file.to_slash_lossy().yellow(), path.to_slash_lossy().yellow());
+        }
+        if !includes.contains(&file) {
+            includes.push(file.clone());
+        }
+
+        stack.push(normalize(&file));
+        let content = expand_xml(&file, stack, includes)?;
+        let content = content.strip_suffix('\n').unwrap_or(&content);
+        stack.pop();
+
+        let tag = captures.get(0).unwrap();
+        let marker = format!("<!-- include {name} -->");
+        expanded += &xml[last..tag.start()];
+        expanded += &format!("{marker}\n{content}\n{marker}");
+        last = tag.end();
+    }
+    expanded += &xml[last..];
+    Ok(expanded)
+}
+
+/// Reverts [`read_xml`], replacing the included files that are enclosed in `<!-- include path -->` comments
+/// with the `<Include src="path"/>` tag again.
+pub fn unbundle_xml(xml: &str) -> String {
+    static EXPRS: OnceLock<Regex> = OnceLock::new();
+    let exprs = EXPRS.get_or_init(|| Regex::new(r"<!-- include (?<src>.+?) -->").unwrap());
+    let mut unbundled = String::new();
+    let mut last = 0;
+    while let Some(captures) = exprs.captures(&xml[last..]) {
+        let marker = captures.get(0).unwrap();
+        let (start, end) = (last + marker.start(), last + marker.end());
+        let Some(closing) = xml[end..].find(marker.as_str()) else {
+            break;
+        };
+        unbundled += &xml[last..start];
+        unbundled += &format!("<Include src=\"{}\"/>", &captures["src"]);
+        last = end + closing + marker.len();
+    }
+    unbundled += &xml[last..];
+    unbundled
+}
+
+/// Reverts the expansion of the `content` that has been read from the lua or xml file at `path`,
+/// see [`unbundle`] and [`unbundle_xml`].
+pub fn unbundle_file(path: &Path, content: &str) -> String {
+    match ttsst::tags::extensions().is_lua(path) {
+        true => unbundle(content),
+        false => unbundle_xml(content),
+    }
+}
+
+/// Returns the file `name` refers to. Names starting with `./` or `../` are relative to the including file at `from`,
+/// other names are searched for in the include directories. Names without a lua extension, e.g. `util/math`,
+/// are completed with one of the lua [`extensions`](ttsst::tags::extensions).
+pub fn resolve(name: &str, from: &Path) -> Result<PathBuf> {
+    find(name, from, &ttsst::tags::extensions().lua)
+}
+
+/// Returns the file `name` in an `<Include>` tag refers to, like [`resolve`] does for lua files.
+/// Names without a xml extension are completed with one of the xml [`extensions`](ttsst::tags::extensions).
+pub fn resolve_xml(name: &str, from: &Path) -> Result<PathBuf> {
+    find(name, from, &ttsst::tags::extensions().xml)
+}
+
+/// Returns the file `name` refers to, completing it with one of the `extensions`, see [`resolve`].
+fn find(name: &str, from: &Path, extensions: &[String]) -> Result<PathBuf> {
+    let name = name.replace('\\', "/");
+    let dirs = match name.starts_with("./") || name.starts_with("../") {
+        true => vec![from.parent().unwrap_or(Path::new(".")).to_path_buf()],
+        false => config().include_dirs.clone(),
+    };
+
+    let candidates = match extensions
+        .iter()
+        .any(|ext| name.ends_with(&format!(".{ext}")))
+    {
+        true => vec![name.clone()],
+        false => extensions
+            .iter()
+            .map(|ext| format!("{name}.{ext}"))
+            .collect(),
+    };
+
+    let found = dirs.iter().find_map(|dir| {
+        candidates
+            .iter()
+            .map(|candidate| dir.join(candidate.strip_prefix("./").unwrap_or(candidate)))
+            .find(|path| long_path(path).is_ok_and(|path| path.is_file()))
+    });
+    match found {
+        Some(path) => Ok(path),
+        #[rustfmt::skip]
+        None => bail!("'{}' included by '{}' does not exist", name.yellow(), from.to_slash_lossy().yellow()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::config::test_project;
+
+    #[test]
+    fn lines_are_located_in_included_files() {
+        let root = &test_project().root;
+        let path = root.join("locate_main.lua");
+        fs::write(root.join("locate_util.lua"), "local u = 1\nlocal v = 2\n").unwrap();
+        fs::write(root.join("locate_wrapped.lua"), "local w = 3\n").unwrap();
+        let script = "local a = 1\n#include ./locate_util\n#include <./locate_wrapped>\nprint(a)\n";
+        let bundled = bundle(script, &path).unwrap().script;
+
+        let located = (1..=bundled.lines().count())
+            .map(|line| {
+                let (file, line) = locate(&bundled, &path, line);
+                let name = file.file_stem().unwrap().to_string_lossy().into_owned();
+                (name.trim_start_matches("locate_").to_string(), line)
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("main", 1),
+            ("main", 2),
+            ("util", 1),
+            ("util", 2),
+            ("main", 2),
+            ("main", 3),
+            ("main", 3),
+            ("wrapped", 1),
+            ("main", 3),
+            ("main", 3),
+            ("main", 4),
+        ]
+        .map(|(name, line)| (name.to_string(), line));
+        assert_eq!(located, expected);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Result};
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+
+use crate::config::CONFIG_FILE;
+
+/// Placeholder that gets replaced with the name of the project in every file of a template.
+const NAME_PLACEHOLDER: &str = "{{project-name}}";
+
+/// Creates a new project in `dir`, optionally from a `template` git repository.
+/// If `save` is provided, the project gets bound to that save file.
+pub fn init(
+    dir: &Path,
+    template: Option<&str>,
+    name: Option<&str>,
+    save: Option<&Path>,
+) -> Result<()> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow!("cannot determine the project name, use --name"))?,
+    };
+
+    if let Some(template) = template {
+        clone_template(template, dir)?;
+        let count = substitute(dir, &name)?;
+        #[rustfmt::skip]
+        info!("created '{}' from '{}', replaced {} placeholder(s)", dir.to_slash_lossy().yellow(), template.yellow(), count);
+    }
+
+    fs::create_dir_all(dir)?;
+    let config_path = dir.join(CONFIG_FILE);
+    let mut config = match config_path.exists() {
+        true => fs::read_to_string(&config_path)?.parse::<toml_edit::DocumentMut>()?,
+        false => toml_edit::DocumentMut::new(),
+    };
+    if let Some(save) = save {
+        if config.contains_key("save") {
+            bail!(
+                "{} already binds the project to a save",
+                CONFIG_FILE.yellow()
+            );
+        }
+        config.insert("save", toml_edit::value(save.to_slash_lossy().as_ref()));
+        #[rustfmt::skip]
+        info!("bound the project to '{}'", save.to_slash_lossy().yellow());
+    }
+    fs::write(&config_path, config.to_string())?;
+
+    info!(
+        "initialized project {} in '{}'",
+        name.bright_white().bold(),
+        dir.to_slash_lossy().yellow()
+    );
+    Ok(())
+}
+
+/// Clones the `url` into `dir` and removes the history of the template.
+fn clone_template(url: &str, dir: &Path) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        bail!("'{}' is not empty", dir.to_slash_lossy().yellow());
+    }
+
+    debug!("cloning {} into {}", url, dir.display());
+    let status = Command::new("git")
+        .args(["clone", "--depth", "1", "--quiet", url])
+        .arg(dir)
+        .status()
+        .map_err(|err| anyhow!("could not run git: {}", err))?;
+    if !status.success() {
+        bail!("could not clone '{}'", url.yellow());
+    }
+
+    fs::remove_dir_all(dir.join(".git"))?;
+    Ok(())
+}
+
+/// Replaces the name placeholder with `name` in every text file inside of `dir`.
+/// Returns the number of replaced placeholders.
+fn substitute(dir: &Path, name: &str) -> Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            count += substitute(&path, name)?;
+            continue;
+        }
+
+        // Binary files can't be read as a string and are skipped
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let matches = content.matches(NAME_PLACEHOLDER).count();
+        if matches > 0 {
+            fs::write(&path, content.replace(NAME_PLACEHOLDER, name))?;
+            count += matches;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_are_bound_using_toml() {
+        let dir = std::env::temp_dir().join(format!("ttsst-init-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CONFIG_FILE), "saves-dir = \"Saves\"\n\n[write]\n").unwrap();
+
+        let save = Path::new("Saves/\"Quoted\" Save.json");
+        init(&dir, None, Some("test"), Some(save)).unwrap();
+        let config: toml::Table =
+            toml::from_str(&fs::read_to_string(dir.join(CONFIG_FILE)).unwrap()).unwrap();
+        assert_eq!(config["save"].as_str(), Some("Saves/\"Quoted\" Save.json"));
+        assert_eq!(config["saves-dir"].as_str(), Some("Saves"));
+        assert!(config["write"].is_table());
+
+        assert!(init(&dir, None, Some("test"), Some(save)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
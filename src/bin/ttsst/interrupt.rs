@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use log::*;
+
+/// Exit code of a process that has been interrupted using Ctrl-C, following the convention of shells.
+pub const EXIT_CODE: i32 = 130;
+
+type Hook = Arc<dyn Fn() + Send + Sync>;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static STATE: Mutex<State> = Mutex::new(State {
+    deferred: 0,
+    hooks: Vec::new(),
+});
+
+struct State {
+    /// Number of [`Deferred`] guards that are alive.
+    deferred: usize,
+    /// Functions that stop the threads of a long running command, see [`on_interrupt`].
+    hooks: Vec<Hook>,
+}
+
+/// Handles Ctrl-C, so that an interruption can't leave a half-written save or file tree behind.
+///
+/// If no operation is [`defer`]ring the interruption, the process exits right away,
+/// unless a long running command has registered a hook to shut down cleanly instead, see [`on_interrupt`].
+/// Pressing Ctrl-C a second time exits right away in any case.
+pub fn init() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE);
+        }
+        if STATE.lock().unwrap().deferred > 0 {
+            info!("finishing the current operation, press Ctrl-C again to stop right away");
+            return;
+        }
+        shutdown();
+    })?;
+    Ok(())
+}
+
+/// Returns whether Ctrl-C has been pressed, or a shutdown has been [`request`]ed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Registers `hook` to be called instead of exiting when the process gets interrupted.
+/// The hook has to make the threads of the command return, e.g. by waking them up.
+pub fn on_interrupt<F: Fn() + Send + Sync + 'static>(hook: F) {
+    STATE.lock().unwrap().hooks.push(Arc::new(hook));
+}
+
+/// Shuts down the same way as if Ctrl-C had been pressed, e.g. because one of the threads has failed.
+pub fn request() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    if STATE.lock().unwrap().deferred == 0 {
+        shutdown();
+    }
+}
+
+/// Runs the registered hooks, or exits if there are none.
+fn shutdown() {
+    let hooks = STATE.lock().unwrap().hooks.clone();
+    if hooks.is_empty() {
+        std::process::exit(EXIT_CODE);
+    }
+    hooks.iter().for_each(|hook| hook());
+}
+
+/// Defers an interruption until the returned guard is dropped,
+/// so that an operation which writes multiple files, or writes the save and pushes it, is completed as a whole.
+#[must_use]
+pub fn defer() -> Deferred {
+    STATE.lock().unwrap().deferred += 1;
+    Deferred
+}
+
+/// Guard returned by [`defer`].
+pub struct Deferred;
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        let mut state = STATE.lock().unwrap();
+        state.deferred -= 1;
+        let shutdown_now = state.deferred == 0 && interrupted();
+        drop(state);
+        if shutdown_now {
+            shutdown();
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpStream};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::*;
+use log::*;
+use path_slash::PathExt;
+use serde_json::{json, Value};
+use ttsst::api::Answer;
+use ttsst::api::ExternalEditorApi as Api;
+
+use crate::dispatch::Dispatcher;
+use crate::interrupt;
+
+/// Prints the messages that scripts send to ttsst, one per line and without timestamps or colors,
+/// so that the output can be piped into other programs.
+///
+/// By default, the messages printed using `print()` and the payloads sent using `sendExternalMessage()` are printed.
+/// If `custom` is set, only the payloads are printed, as JSON. If a `key` is provided,
+/// only payloads that are tables containing the key are printed, and only the value of that key.
+///
+/// If a `record` file is provided, the printed payloads are appended to it as well, see [`Recorder`].
+pub fn listen(api: &Api, custom: bool, key: Option<&str>, record: Option<PathBuf>) -> Result<()> {
+    let mut recorder = record.map(Recorder::new).transpose()?;
+    let dispatcher = Dispatcher::new();
+    let messages = dispatcher.subscribe(move |answer| match answer {
+        Answer::AnswerCustomMessage(_) => true,
+        Answer::AnswerPrint(_) => !custom,
+        _ => false,
+    });
+
+    // Wake the dispatcher up when the process gets interrupted, so that it stops
+    let mut addr = api.local_addr()?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+    interrupt::on_interrupt(move || {
+        let _ = TcpStream::connect(addr);
+    });
+
+    std::thread::scope(|scope| {
+        let dispatched = scope.spawn(|| dispatcher.run(api));
+        // The dispatcher disconnects when it stops
+        while let Ok(message) = messages.recv() {
+            match message.as_ref() {
+                Answer::AnswerPrint(answer) => println!("{}", answer.message),
+                Answer::AnswerCustomMessage(answer) => {
+                    let payload = match key {
+                        Some(key) => answer.custom_message.get(key),
+                        None => Some(&answer.custom_message),
+                    };
+                    match payload {
+                        Some(Value::String(string)) if key.is_some() => println!("{string}"),
+                        Some(payload) => println!("{payload}"),
+                        None => continue,
+                    }
+                    if let (Some(recorder), Some(payload)) = (&mut recorder, payload) {
+                        if let Err(err) = recorder.record(payload) {
+                            interrupt::request();
+                            return Err(err);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        dispatched.join().unwrap()
+    })
+}
+
+/// Appends payloads to a file together with the time they have been received, e.g. to analyze dice results after a playtest.
+///
+/// Files with a `.csv` extension get a column for each key of the payloads, which are taken from the header of the file,
+/// or from the first payload if the file doesn't exist yet. Other files get a line of JSON for each payload.
+struct Recorder {
+    path: PathBuf,
+    /// Keys of the payloads that have a column in the csv file.
+    columns: Option<Vec<String>>,
+}
+
+impl Recorder {
+    fn new(path: PathBuf) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let columns = match is_csv(&path) && path.exists() {
+            true => {
+                let mut reader = csv::Reader::from_path(&path)?;
+                Some(reader.headers()?.iter().skip(1).map(String::from).collect())
+            }
+            false => None,
+        };
+        // Info messages would be mixed into the output that is piped into other programs
+        debug!("recording payloads to '{}'", path.to_slash_lossy().yellow());
+        Ok(Self { path, columns })
+    }
+
+    /// Appends the `payload`. Payloads that aren't tables are skipped by csv files.
+    fn record(&mut self, payload: &Value) -> Result<()> {
+        let timestamp = chrono::Local::now().to_rfc3339();
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if !is_csv(&self.path) {
+            let line = json!({ "timestamp": timestamp, "payload": payload });
+            writeln!(file, "{line}")?;
+            return Ok(());
+        }
+
+        let Value::Object(fields) = payload else {
+            warn!("skipped a payload that isn't a table: {}", payload);
+            return Ok(());
+        };
+        let mut writer = csv::Writer::from_writer(file);
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => {
+                let columns = self.columns.insert(fields.keys().cloned().collect());
+                writer.write_record(
+                    std::iter::once("timestamp").chain(columns.iter().map(String::as_str)),
+                )?;
+                columns
+            }
+        };
+        if let Some(key) = fields.keys().find(|key| !columns.contains(key)) {
+            debug!(
+                "'{}' has no column in '{}'",
+                key,
+                self.path.to_slash_lossy()
+            );
+        }
+        let values = columns.iter().map(|column| match fields.get(column) {
+            Some(Value::String(string)) => string.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        });
+        writer.write_record(std::iter::once(timestamp).chain(values))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
@@ -1,8 +1,24 @@
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
 use anyhow::Result;
+use chrono::NaiveDate;
+use clap::ValueEnum;
 use colored::*;
 use log::*;
 
-pub struct ConsoleLogger;
+use crate::config::LogConfig;
+use crate::utils;
+
+/// The file that log messages get written to, if file logging is enabled.
+static LOG_FILE: OnceLock<Mutex<RotatingFile>> = OnceLock::new();
+
+pub struct ConsoleLogger {
+    /// Whether every message is printed to stderr, instead of only errors.
+    stderr: bool,
+}
 
 impl log::Log for ConsoleLogger {
     fn enabled(&self, _: &Metadata) -> bool {
@@ -26,9 +42,25 @@ impl log::Log for ConsoleLogger {
 
             #[rustfmt::skip]
             match record.level() {
-                Level::Error => eprintln!("{} {}", level_string, record.args()),
-                _            =>  println!("{} {}", level_string, record.args()),
+                Level::Error     => eprintln!("{} {}", level_string, record.args()),
+                _ if self.stderr => eprintln!("{} {}", level_string, record.args()),
+                _                =>  println!("{} {}", level_string, record.args()),
             };
+
+            if let Some(file) = LOG_FILE.get() {
+                let level = record.level().to_string().to_lowercase();
+                if let Err(err) = file
+                    .lock()
+                    .unwrap()
+                    .write(&level, &record.args().to_string())
+                {
+                    eprintln!(
+                        "{} failed to write log file: {}",
+                        "error:".red().bold(),
+                        err
+                    );
+                }
+            }
         }
     }
 
@@ -38,7 +70,15 @@ impl log::Log for ConsoleLogger {
 impl ConsoleLogger {
     #[must_use = "You must call init() to begin logging"]
     pub fn new() -> Self {
-        ConsoleLogger
+        ConsoleLogger { stderr: false }
+    }
+
+    /// Prints every message to stderr if `stderr` is set, so that stdout only contains the output of the command,
+    /// e.g. JSON that is piped into other programs.
+    #[must_use = "You must call init() to begin logging"]
+    pub fn stderr(mut self, stderr: bool) -> Self {
+        self.stderr = stderr;
+        self
     }
 
     #[must_use = "You must call init() to begin logging"]
@@ -48,3 +88,120 @@ impl ConsoleLogger {
         Ok(())
     }
 }
+
+/// When to use colors in the output.
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum ColorChoice {
+    /// Use colors if the output is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    /// Always use colors
+    Always,
+    /// Never use colors
+    Never,
+}
+
+/// Enables or disables colors for every message printed using [`colored`],
+/// including log messages and the messages of the console.
+/// See <https://no-color.org/>.
+pub fn init_color(choice: ColorChoice) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    colored::control::set_override(match choice {
+        ColorChoice::Auto => !no_color && io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    });
+}
+
+/// Enables writing log messages to the file configured in `config`.
+pub fn init_file(config: &LogConfig) -> Result<()> {
+    if let Some(path) = &config.file {
+        let file = RotatingFile::open(path, config)?;
+        let _ = LOG_FILE.set(Mutex::new(file));
+    }
+    Ok(())
+}
+
+/// A log file that gets rotated once it exceeds its maximum size, or once a day.
+/// Rotated files are renamed to `<File>.1`, `<File>.2`, ... and only `retention` files are kept.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    date: NaiveDate,
+    max_size: Option<u64>,
+    daily: bool,
+    retention: usize,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, config: &LogConfig) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            path: path.into(),
+            size: file.metadata()?.len(),
+            date: chrono::Local::now().date_naive(),
+            file,
+            max_size: config.max_size,
+            daily: config.daily,
+            retention: config.retention,
+        })
+    }
+
+    /// Writes a log message without color codes to the file, rotating it beforehand if necessary.
+    fn write(&mut self, level: &str, message: &str) -> Result<()> {
+        let now = chrono::Local::now();
+        let line = format!(
+            "{} {}: {}\n",
+            now.format("%Y-%m-%d %H:%M:%S"),
+            level,
+            utils::strip_colors(message)
+        );
+
+        let exceeds_size = self
+            .max_size
+            .is_some_and(|max_size| self.size + line.len() as u64 > max_size);
+        let new_day = self.daily && self.date != now.date_naive();
+        if (exceeds_size && self.size > 0) || new_day {
+            self.rotate()?;
+            self.date = now.date_naive();
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts every rotated file by one, removing files that exceed the retention count.
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |index: usize| PathBuf::from(format!("{}.{}", self.path.display(), index));
+
+        for index in (1..=self.retention).rev() {
+            let path = rotated(index);
+            if !path.exists() {
+                continue;
+            }
+            match index < self.retention {
+                true => fs::rename(&path, rotated(index + 1))?,
+                false => fs::remove_file(&path)?,
+            }
+        }
+        match self.retention {
+            0 => fs::remove_file(&self.path)?,
+            _ => fs::rename(&self.path, rotated(1))?,
+        }
+
+        self.file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
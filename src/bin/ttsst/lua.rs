@@ -0,0 +1,323 @@
+//! A minimal lua lexer, that is good enough to find the globals a script defines.
+
+/// Keywords of lua, which can't be used as names.
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Token<'a> {
+    Name(&'a str),
+    Symbol(&'a str),
+    /// A string or number.
+    Literal,
+}
+
+/// Returns the names of the globals that are defined at the top level of `script`,
+/// either by an assignment like `name = value`, or by a function definition like `function name()`.
+/// Locals, fields and globals that are defined inside of functions or blocks are ignored.
+pub fn globals(script: &str) -> Vec<String> {
+    let tokens = tokenize(script);
+    let is_name = |index: usize| matches!(tokens.get(index), Some(Token::Name(name)) if !KEYWORDS.contains(name));
+
+    let mut globals = Vec::new();
+    // Number of open blocks, like functions or `if ... end`
+    let mut depth = 0usize;
+    // Number of open parentheses, brackets and braces
+    let mut nesting = 0usize;
+    // Number of `while` and `for` loops whose `do` hasn't been reached yet
+    let mut loops = 0usize;
+
+    let mut index = 0;
+    while index < tokens.len() {
+        let top = depth == 0 && nesting == 0;
+        match tokens[index] {
+            Token::Name("local") if top => {
+                // Skip the names of `local a, b` and `local function name`
+                index += 1;
+                if tokens.get(index) == Some(&Token::Name("function")) {
+                    depth += 1;
+                    index += 2;
+                }
+                while is_name(index) || tokens.get(index) == Some(&Token::Symbol(",")) {
+                    index += 1;
+                }
+                continue;
+            }
+            Token::Name("function") => {
+                if let (true, Some(Token::Name(name)), Some(Token::Symbol("("))) =
+                    (top, tokens.get(index + 1), tokens.get(index + 2))
+                {
+                    globals.push(name.to_string());
+                }
+                depth += 1;
+            }
+            Token::Name("if" | "repeat") => depth += 1,
+            Token::Name("while" | "for") => {
+                depth += 1;
+                loops += 1;
+            }
+            Token::Name("do") if loops > 0 => loops -= 1,
+            Token::Name("do") => depth += 1,
+            Token::Name("end" | "until") => depth = depth.saturating_sub(1),
+            Token::Symbol("(" | "[" | "{") => nesting += 1,
+            Token::Symbol(")" | "]" | "}") => nesting = nesting.saturating_sub(1),
+            Token::Name(_) if top && is_name(index) => {
+                // Fields like `a.b = value` or `a:b()` are not globals
+                if index > 0 && matches!(tokens[index - 1], Token::Symbol("." | ":")) {
+                    index += 1;
+                    continue;
+                }
+                // Collect the names of `a, b = value`
+                let mut names = vec![index];
+                while tokens.get(names[names.len() - 1] + 1) == Some(&Token::Symbol(","))
+                    && is_name(names[names.len() - 1] + 2)
+                {
+                    names.push(names[names.len() - 1] + 2);
+                }
+                let last = names[names.len() - 1];
+                if tokens.get(last + 1) == Some(&Token::Symbol("=")) {
+                    for name in names {
+                        if let Token::Name(name) = tokens[name] {
+                            globals.push(name.to_string());
+                        }
+                    }
+                }
+                index = last;
+            }
+            _ => (),
+        }
+        index += 1;
+    }
+    globals
+}
+
+/// Returns `true` if `script` only consists of whitespace and comments.
+pub fn is_blank(script: &str) -> bool {
+    tokenize(script).is_empty()
+}
+
+/// Removes comments, indentation and trailing whitespace from `script`, and collapses other whitespace into a single space.
+/// Line breaks are kept, so that the line numbers of errors still match the file. Strings are kept as they are.
+///
+/// The `----#include` markers added by [`include::bundle`](crate::include::bundle) are comments as well,
+/// so a minified script can't be unbundled anymore.
+pub fn minify(script: &str) -> String {
+    let bytes = script.as_bytes();
+    let mut minified = String::with_capacity(script.len());
+    // Whether whitespace has been skipped since the last token
+    let mut space = false;
+    let mut index = 0;
+    while index < bytes.len() {
+        let start = index;
+        match bytes[index] {
+            b'-' if bytes.get(index + 1) == Some(&b'-') => {
+                index = match long_bracket(bytes, index + 2) {
+                    Some(end) => end,
+                    None => script[index..]
+                        .find('\n')
+                        .map_or(bytes.len(), |end| index + end),
+                };
+                let lines = script[start..index].matches('\n').count();
+                if lines > 0 {
+                    trim_line(&mut minified);
+                    minified += &"\n".repeat(lines);
+                }
+                space = lines == 0;
+                continue;
+            }
+            b'\n' => {
+                trim_line(&mut minified);
+                minified.push('\n');
+                space = false;
+                index += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                space = true;
+                index += 1;
+                continue;
+            }
+            b'[' if long_bracket(bytes, index).is_some() => {
+                index = long_bracket(bytes, index).unwrap_or(bytes.len());
+            }
+            quote @ (b'"' | b'\'') => {
+                index += 1;
+                while index < bytes.len() && bytes[index] != quote && bytes[index] != b'\n' {
+                    index += if bytes[index] == b'\\' { 2 } else { 1 };
+                }
+                index = (index + 1).min(bytes.len());
+            }
+            _ => index += script[index..].chars().next().map_or(1, char::len_utf8),
+        }
+        if space && !minified.is_empty() && !minified.ends_with('\n') {
+            minified.push(' ');
+        }
+        space = false;
+        minified += &script[start..index.min(bytes.len())];
+    }
+    trim_line(&mut minified);
+    minified
+}
+
+/// Removes the trailing spaces of the last line of `minified`.
+fn trim_line(minified: &mut String) {
+    minified.truncate(minified.trim_end_matches(' ').len());
+}
+
+/// Splits `script` into tokens, skipping whitespace and comments.
+fn tokenize(script: &str) -> Vec<Token<'_>> {
+    let bytes = script.as_bytes();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let start = index;
+        match bytes[index] {
+            b'-' if bytes.get(index + 1) == Some(&b'-') => {
+                index = match long_bracket(bytes, index + 2) {
+                    Some(end) => end,
+                    None => script[index..]
+                        .find('\n')
+                        .map_or(bytes.len(), |end| index + end),
+                };
+            }
+            b'[' if long_bracket(bytes, index).is_some() => {
+                index = long_bracket(bytes, index).unwrap_or(bytes.len());
+                tokens.push(Token::Literal);
+            }
+            quote @ (b'"' | b'\'') => {
+                index += 1;
+                while index < bytes.len() && bytes[index] != quote && bytes[index] != b'\n' {
+                    // Skip escaped characters, like `\"`
+                    index += if bytes[index] == b'\\' { 2 } else { 1 };
+                }
+                index = (index + 1).min(bytes.len());
+                tokens.push(Token::Literal);
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while index < bytes.len()
+                    && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_')
+                {
+                    index += 1;
+                }
+                tokens.push(Token::Name(&script[start..index]));
+            }
+            c if c.is_ascii_digit() => {
+                while index < bytes.len()
+                    && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'.')
+                {
+                    index += 1;
+                }
+                tokens.push(Token::Literal);
+            }
+            c if c.is_ascii_whitespace() => index += 1,
+            _ => {
+                let symbols = ["...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>"];
+                let len = match symbols
+                    .iter()
+                    .find(|symbol| script[index..].starts_with(*symbol))
+                {
+                    Some(symbol) => symbol.len(),
+                    None => script[index..].chars().next().map_or(1, char::len_utf8),
+                };
+                index += len;
+                tokens.push(Token::Symbol(&script[start..index]));
+            }
+        }
+    }
+    tokens
+}
+
+/// Returns the index after the long bracket that starts at `index`, like `[[ ... ]]` or `[==[ ... ]==]`,
+/// or [`None`] if there is no long bracket at `index`.
+///
+/// Brackets that aren't closed are not treated as long brackets, so that a `--[[` without `]]`
+/// only comments out its line, instead of hiding the rest of the script, e.g. from [`is_blank`].
+fn long_bracket(bytes: &[u8], index: usize) -> Option<usize> {
+    if bytes.get(index) != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[index + 1..]
+        .iter()
+        .take_while(|&&c| c == b'=')
+        .count();
+    if bytes.get(index + 1 + level) != Some(&b'[') {
+        return None;
+    }
+
+    let close = [b"]".as_slice(), &b"=".repeat(level), b"]"].concat();
+    let content = index + 2 + level;
+    bytes[content..]
+        .windows(close.len())
+        .position(|window| window == close)
+        .map(|position| content + position + close.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_blank() {
+        for script in [
+            "",
+            " \n\t\n",
+            "-- comment\n",
+            "--[[ long\ncomment ]]",
+            "--[==[ closed by ]] only at the end ]==]",
+            "--[[ a ]] --[=[ b ]=]\n-- c",
+        ] {
+            assert!(is_blank(script), "{script:?} is blank");
+        }
+    }
+
+    #[test]
+    fn code_next_to_comments_is_not_blank() {
+        for script in [
+            // Comments don't nest, so the first `]]` closes the comment
+            "--[[ a --[[ b ]] print(1) ]]",
+            "--[==[ a ]==] print(1)",
+            "x = [[ -- not a comment ]]",
+            "s = '--[[' -- string",
+            // Unterminated comments don't hide the rest of the script
+            "--[[\nprint(1)",
+            "--[==[ a ]]\nprint(1)",
+        ] {
+            assert!(!is_blank(script), "{script:?} is not blank");
+        }
+    }
+
+    #[test]
+    fn long_brackets_have_levels() {
+        let bytes = b"[==[ ]] ]=] ]==] rest";
+        assert_eq!(long_bracket(bytes, 0), Some(16));
+        assert_eq!(long_bracket(b"[[ ]]", 0), Some(5));
+        assert_eq!(long_bracket(b"[=[ ]]", 0), None);
+        assert_eq!(long_bracket(b"[ = [", 0), None);
+    }
+
+    #[test]
+    fn minify_keeps_lines_and_strings() {
+        let script =
+            "  local a =   1 -- one\n\n--[[ two\nlines ]] b = 'x  -- y'\nc = [[  z  ]]  \n";
+        assert_eq!(
+            minify(script),
+            "local a = 1\n\n\nb = 'x  -- y'\nc = [[  z  ]]\n"
+        );
+        assert_eq!(minify(script).lines().count(), script.lines().count());
+    }
+
+    #[test]
+    fn minify_removes_include_markers() {
+        let script = "----#include lib\nx = 1\n----#include lib\nprint(x)";
+        assert_eq!(minify(script), "\nx = 1\n\nprint(x)");
+        assert!(is_blank(&minify("--[==[ ]] ]==]")));
+    }
+
+    #[test]
+    fn globals_skip_locals_and_blocks() {
+        let script = "local a = 1\nb, c = 2, 3\nfunction d() e = 4 end\nf.g = 5\n--[[ h = 6 ]]";
+        assert_eq!(globals(script), ["b", "c", "d"]);
+    }
+}
@@ -1,16 +1,58 @@
-#![feature(never_type)]
-
+mod agent;
+mod alias;
 mod app;
+mod autosave;
+mod batch;
+mod cheatsheet;
+mod config;
 mod console;
+mod dedup;
+mod demo;
+mod dispatch;
+mod exec;
+mod explain;
+mod freeze;
+mod globals;
+mod graph;
+mod grep;
+mod guard;
+mod helper;
+mod history;
+mod include;
+mod init;
+mod interrupt;
+mod listen;
 mod logger;
+mod lua;
+mod metadata;
+mod official;
 mod parser;
+mod players;
+mod port;
+mod probe;
+mod progress;
+mod roundtrip;
+mod session;
+mod slim;
+mod status;
+mod strict;
+mod stubs;
+mod transform;
+mod unused;
 mod utils;
 
-use anyhow::Result;
-use clap::{Args, Parser, Subcommand};
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
 use std::path::PathBuf;
 
-use crate::{app::SaveFile, logger::ConsoleLogger};
+use ttsst::prompt::{InquirePrompter, NonInteractive, Prompter};
+
+use crate::{
+    app::SaveFile,
+    config::{Project, WatchRoot},
+    logger::ConsoleLogger,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -22,6 +64,113 @@ struct Cli {
     #[arg(short = 'v', long = "verbose", global = true)]
     #[arg(action = clap::ArgAction::Count)]
     pub verbosity: u8,
+
+    /// Run as if ttsst was started in <DIR> instead of the current directory
+    #[arg(short = 'C', long = "cwd", value_name = "DIR", global = true)]
+    pub cwd: Option<PathBuf>,
+
+    /// Write log messages to a file in addition to the console
+    #[arg(long, value_name = "FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// When to use colors in the output
+    #[arg(long, value_name = "WHEN", global = true)]
+    #[arg(value_enum, default_value_t)]
+    pub color: logger::ColorChoice,
+
+    /// Fail instead of prompting for input, e.g. when no GUIDs are provided
+    #[arg(long, global = true)]
+    pub no_input: bool,
+
+    /// Overwrite scripts that have been edited in-game without asking
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Fail instead of warning about problems that would leave the save inconsistent, e.g. a Lua script without a tag
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Write the progress of reloads as lines of JSON to a file or named pipe, e.g. for editor extensions
+    #[arg(long, value_name = "PATH", global = true)]
+    pub events: Option<PathBuf>,
+
+    /// Listen for answers of the game on a different port, if they are forwarded to it
+    #[arg(long, value_name = "PORT", global = true)]
+    #[arg(default_value_t = ttsst::api::LISTEN_PORT)]
+    pub port: u16,
+
+    /// Send messages to the game on a different port, e.g. to a mock server or a forwarded port
+    #[arg(long, value_name = "PORT", global = true)]
+    #[arg(default_value_t = ttsst::api::SEND_PORT)]
+    pub send_port: u16,
+
+    /// Send messages to the game running on a different machine, e.g. `192.168.0.10`
+    #[arg(long, value_name = "ADDR", global = true)]
+    #[arg(default_value = ttsst::api::LOCALHOST)]
+    pub host: String,
+
+    /// Listen for answers of the game on a different interface, e.g. `0.0.0.0` to accept them from other machines
+    #[arg(long, value_name = "ADDR", global = true)]
+    #[arg(default_value = ttsst::api::LOCALHOST)]
+    pub bind: String,
+
+    /// Wait for the game to start instead of failing, e.g. if ttsst is started together with the game
+    #[arg(long, global = true)]
+    pub wait: bool,
+
+    /// Give up waiting for an answer of the game after this many seconds, instead of waiting forever
+    #[arg(long, value_name = "SECONDS", global = true)]
+    pub timeout: Option<u64>,
+
+    /// Ask the game for its scripts again this many times, if the answer times out
+    #[arg(long, value_name = "N", global = true)]
+    pub retries: Option<u32>,
+}
+
+impl Cli {
+    /// Returns the global options that have been passed, so that `watch` can be started with the same options by the agent.
+    fn global_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        let mut option = |name: &str, value: Option<OsString>| {
+            if let Some(value) = value {
+                args.extend([name.into(), value]);
+            }
+        };
+        option("--cwd", self.cwd.clone().map(Into::into));
+        option("--log-file", self.log_file.clone().map(Into::into));
+        option("--events", self.events.clone().map(Into::into));
+        option(
+            "--timeout",
+            self.timeout.map(|timeout| timeout.to_string().into()),
+        );
+        option(
+            "--retries",
+            self.retries.map(|retries| retries.to_string().into()),
+        );
+        option("--port", Some(self.port.to_string().into()));
+        option("--send-port", Some(self.send_port.to_string().into()));
+        option("--host", Some(self.host.clone().into()));
+        option("--bind", Some(self.bind.clone().into()));
+        let color = self.color.to_possible_value().unwrap();
+        option("--color", Some(color.get_name().into()));
+
+        let flags = [
+            ("--no-input", self.no_input),
+            ("--force", self.force),
+            ("--strict", self.strict),
+            ("--wait", self.wait),
+        ];
+        args.extend(
+            flags
+                .into_iter()
+                .filter(|(_, set)| *set)
+                .map(|(flag, _)| flag.into()),
+        );
+        if self.verbosity > 0 {
+            args.push(format!("-{}", "v".repeat(self.verbosity.into())).into());
+        }
+        args
+    }
 }
 
 #[derive(Args, Debug)]
@@ -34,31 +183,223 @@ pub struct Guids {
     /// Show hidden objects like Zones in the selection prompt, if no GUIDs are provided
     #[arg(short, long)]
     all: bool,
+
+    /// Only show scripting zones in the selection prompt, if no GUIDs are provided
+    #[arg(short, long, conflicts_with = "all")]
+    zones: bool,
 }
 
 #[derive(Args, Debug)]
+pub struct CloneArgs {
+    /// Number of copies
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 1)]
+    count: usize,
+
+    /// Distance of every copy from the previous one
+    #[arg(long, value_name = "X,Y,Z", default_value = "2,0,0")]
+    #[arg(value_parser = parser::vector)]
+    offset: [f64; 3],
+
+    /// Move every copy onto the nearest point of a grid with cells of this size
+    #[arg(long, value_name = "SIZE")]
+    snap: Option<f64>,
+
+    /// Attach a file to the copies instead of the files of the object, e.g. `card_{n}.lua`,
+    /// where `{n}` is replaced with the number of the copy
+    #[arg(long = "attach", value_name = "FILE")]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args, Default, Debug)]
 pub struct ReloadArgs {
     /// Reload a single object
     #[arg(short, long, value_name = "GUID")]
     #[arg(value_parser = parser::guid)]
     guid: Option<String>,
+
+    /// Substitute `%key%` placeholders in XML UI using the translations of a language
+    #[arg(short, long, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Objects whose files are read even if they haven't changed, see `reload-targets` in the config
+    #[arg(skip)]
+    targets: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ConsoleArgs {
+    /// Format used to print error messages
+    #[arg(long = "errorformat", value_name = "FORMAT")]
+    #[arg(value_enum, default_value_t)]
+    error_format: console::ErrorFormat,
+
+    /// Format of the timestamp printed in front of every message, e.g. "%H:%M:%S"
+    #[arg(long, value_name = "FORMAT")]
+    timestamp_format: Option<String>,
+
+    /// Print timestamps in UTC instead of the local time
+    #[arg(long)]
+    utc: bool,
+
+    /// Link errors to the file and line they occurred at, e.g. "vscode://file/{path}:{line}:{col}"
+    #[arg(long, value_name = "TEMPLATE")]
+    link: Option<String>,
+
+    /// Only show messages printed by scripts at this level or above, using prefixes like "[debug] ..." [default: trace]
+    #[arg(long, value_name = "LEVEL")]
+    #[arg(value_enum)]
+    level: Option<console::PrintLevel>,
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// The path(s) that will be watched for changes [default: the project root]
+    #[arg(value_name = "PATH(S)")]
+    #[arg(value_parser = parser::path_exists)]
+    paths: Vec<PathBuf>,
+
+    /// Watch a path with its own options, e.g. `--path src:flat,ignore=*.bak,exec=make`
+    #[arg(long = "path", value_name = "PATH[:OPTIONS]")]
+    #[arg(value_parser = parser::watch_root)]
+    roots: Vec<WatchRoot>,
+
+    /// What to do with objects whose file gets deleted or renamed [default: prompt]
+    #[arg(long, value_name = "POLICY")]
+    #[arg(value_enum)]
+    on_remove: Option<console::RemovePolicy>,
+
+    /// Run a shell command after every reload that updated the save
+    #[arg(long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// Poll for changes every <MS> milliseconds, if file system events don't arrive, e.g. on network shares
+    #[arg(long, value_name = "MS")]
+    #[arg(num_args = 0..=1, default_missing_value = "1000")]
+    poll: Option<u64>,
+
+    /// Wait <MS> milliseconds after a path caused a reload, before it causes another one [default: 1000]
+    #[arg(long, value_name = "MS")]
+    cooldown: Option<u64>,
+
+    /// Push the watched paths and run the commands once, as a change would, and then exit
+    #[arg(long, conflicts_with_all = ["poll", "on_remove"])]
+    run_once: bool,
+
+    /// How the result of the reload is printed when using `--run-once`
+    #[arg(long, value_enum, default_value_t, requires = "run_once")]
+    output: progress::Output,
+
+    /// Don't show the status line at the bottom of the terminal
+    #[arg(long)]
+    no_status: bool,
+}
+
+impl WatchArgs {
+    /// Returns the paths passed to `watch`, followed by the `[[watch]]` tables of the config.
+    /// If there are none, the project root is watched.
+    fn roots(&self) -> Vec<WatchRoot> {
+        let config_roots = config::config().watch.iter().map(|root| WatchRoot {
+            path: PathBuf::from(".\\").join(&root.path),
+            ..root.clone()
+        });
+        let roots = (self.paths.iter().cloned().map(WatchRoot::new))
+            .chain(self.roots.iter().cloned())
+            .chain(config_roots)
+            .collect::<Vec<_>>();
+
+        match roots.is_empty() {
+            true => vec![WatchRoot::new(PathBuf::from(".\\"))],
+            false => roots,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MetaArgs {
+    /// Set the name of the save
+    #[arg(long, value_name = "NAME")]
+    name: Option<String>,
+
+    /// Set the game mode of the save
+    #[arg(long, value_name = "GAME_MODE")]
+    game_mode: Option<String>,
+
+    /// Set the tags of the save, separated by commas
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    tags: Option<Vec<String>>,
+
+    /// Set the description of the save
+    #[arg(long, value_name = "TEXT")]
+    description: Option<String>,
+
+    /// Use an image as the thumbnail of the save
+    #[arg(long, value_name = "FILE")]
+    #[arg(value_parser = parser::path_is_file)]
+    thumbnail: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Create a new project, optionally from a template
+    Init {
+        /// Directory of the new project
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+
+        /// Git repository that is cloned as a template
+        #[arg(long, value_name = "GIT_URL")]
+        template: Option<String>,
+
+        /// Name that replaces the `{{project-name}}` placeholders of the template
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+
+        /// Save file the project belongs to
+        #[arg(long, value_name = "FILE")]
+        #[arg(value_parser = parser::path_is_json)]
+        save: Option<PathBuf>,
+    },
+
     /// Attach Lua scripts or XML UI to object(s)
     Attach {
-        /// Path to the Lua script or XML UI that should be attached
-        #[arg(value_name = "FILE")]
-        #[arg(value_parser = parser::path_is_file)]
+        /// Path to the Lua script or XML UI that should be attached, it can be created if it doesn't exist.
+        /// If it is a directory, the files inside of it are attached to the objects they are named after, or the selected ones
+        #[arg(value_name = "FILE|DIR")]
         path: PathBuf,
 
+        /// Attach a XML UI or Lua script together with the file, e.g. `widget.xml` for `widget.lua`
+        #[arg(long, value_name = "FILE")]
+        #[arg(value_parser = parser::path_is_not_dir)]
+        with: Option<PathBuf>,
+
+        /// Attach the file with the same name and the other kind together with the file, e.g. `widget.xml` for `widget.lua`
+        #[arg(short, long, conflicts_with = "with")]
+        pair: bool,
+
+        /// Attach the file(s) to Global instead of an object, so that they are used instead of the `Global.<Ext>` files
+        #[arg(short, long, conflicts_with = "guids")]
+        global: bool,
+
+        /// Pick the objects in-game using "Scripting Editor" in their context menu, instead of providing GUIDs
+        #[arg(long, conflicts_with_all = ["guids", "global"])]
+        interactive_pick: bool,
+
         #[command(flatten)]
         guids: Guids,
     },
 
     /// Detach Lua scripts and XML UI from object(s)
     Detach {
+        /// Detach the files attached to Global using `attach --global`
+        #[arg(short, long, conflicts_with = "guids")]
+        global: bool,
+
+        #[command(flatten)]
+        guids: Guids,
+    },
+
+    /// Write the in-game Lua scripts and XML UI of object(s) into their attached files
+    Pull {
         #[command(flatten)]
         guids: Guids,
     },
@@ -67,22 +408,179 @@ enum Commands {
     Reload {
         /// The script path(s) to reload
         #[arg(value_name = "PATH(S)")]
-        #[arg(value_parser = parser::path_exists, default_value = ".\\")]
+        #[arg(value_parser = parser::path_exists, default_value = ".")]
         paths: Vec<PathBuf>,
 
         #[command(flatten)]
         args: ReloadArgs,
+
+        /// How the result of the reload is printed
+        #[arg(long, value_enum, default_value_t)]
+        output: progress::Output,
     },
 
     /// Mirror Tabletop Simulator messages to the console
-    Console,
+    Console {
+        #[command(flatten)]
+        args: ConsoleArgs,
+    },
 
     /// Watch script path(s) and reload on change
     Watch {
-        /// The path(s) that will be watched for changes
-        #[arg(value_name = "PATH(S)")]
-        #[arg(value_parser = parser::path_exists, default_value = ".\\")]
-        paths: Vec<PathBuf>,
+        #[command(flatten)]
+        watch: WatchArgs,
+
+        #[command(flatten)]
+        args: ConsoleArgs,
+    },
+
+    /// Run in the background, and watch while Tabletop Simulator has the tracked save loaded
+    Agent {
+        /// Arguments passed to `watch`, e.g. `ttsst agent -- src --exec make`
+        #[arg(last = true, value_name = "WATCH ARGS")]
+        watch: Vec<String>,
+    },
+
+    /// Print the messages scripts send using print() and sendExternalMessage(), e.g. to pipe them into other programs
+    Listen {
+        /// Only print the payloads sent using sendExternalMessage(), as JSON
+        #[arg(long)]
+        custom: bool,
+
+        /// Only print the value of this key of the payloads, skipping payloads without it
+        #[arg(long, value_name = "KEY", requires = "custom")]
+        key: Option<String>,
+
+        /// Append the printed payloads with a timestamp to a JSON Lines file, or a CSV file if it ends with `.csv`
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+    },
+
+    /// Duplicate an object, e.g. to stamp out a family of scripted components
+    Clone {
+        /// The GUID of the object that is duplicated
+        #[arg(value_name = "GUID")]
+        #[arg(value_parser = parser::guid)]
+        guid: String,
+
+        #[command(flatten)]
+        args: CloneArgs,
+    },
+
+    /// Open the Lua script or XML UI attached to an object in $VISUAL or $EDITOR, and reload it once the editor has been closed
+    Edit {
+        /// The GUID of the object whose file is opened
+        #[arg(value_name = "GUID")]
+        #[arg(value_parser = parser::guid)]
+        guid: String,
+
+        /// Open the XML UI instead of the Lua script
+        #[arg(short, long)]
+        xml: bool,
+
+        /// File that is attached if the object has none, named after the object by default, e.g. `Board.a1b2c3.lua`
+        #[arg(long, value_name = "FILE")]
+        #[arg(value_parser = parser::path_is_not_dir)]
+        file: Option<PathBuf>,
+    },
+
+    /// Move a Lua script or XML UI and update the tags of all objects that use it
+    Mv {
+        /// Current path of the Lua script or XML UI
+        #[arg(value_name = "OLD_PATH")]
+        #[arg(value_parser = parser::path_is_file)]
+        old: PathBuf,
+
+        /// New path of the Lua script or XML UI
+        #[arg(value_name = "NEW_PATH")]
+        new: PathBuf,
+    },
+
+    /// Execute Lua code in the current save and print its return value
+    Exec {
+        /// The Lua code to execute, or `@<NAME>` to execute a snippet from the config
+        #[arg(value_name = "LUA")]
+        script: String,
+
+        /// Select an object that `self` refers to inside of the Lua code
+        #[arg(short, long)]
+        pick: bool,
+
+        /// Include hidden objects in the selection
+        #[arg(short, long, requires = "pick")]
+        all: bool,
+    },
+
+    /// Execute Lua code line by line in an interactive prompt
+    Repl,
+
+    /// Generate EmmyLua annotations of the attached objects for editor completion
+    Stubs {
+        /// Path of the generated file
+        #[arg(short, long, value_name = "FILE", default_value = ".ttsst/stubs.lua")]
+        output: PathBuf,
+    },
+
+    /// Generate a cheatsheet of the documented functions of the Global and object scripts
+    Cheatsheet {
+        /// Path of the generated file
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            default_value = ".ttsst/cheatsheet.md"
+        )]
+        output: PathBuf,
+
+        /// Write the cheatsheet into the Notebook tab with the <TITLE> in-game as well
+        #[arg(long, value_name = "TITLE")]
+        #[arg(num_args = 0..=1, default_missing_value = "Cheatsheet")]
+        notebook: Option<String>,
+    },
+
+    /// Record the GUIDs of the objects that have a Lua script or XML UI attached
+    FreezeGuids,
+
+    /// Check that tags, scripts and component tags are in sync, and that the objects frozen using freeze-guids still have the same GUIDs
+    Check {
+        /// Check these saves instead of the current save, without the frozen GUIDs, the file names can contain `*` and `?` wildcards
+        #[arg(long = "save", value_name = "SAVE")]
+        saves: Vec<PathBuf>,
+    },
+
+    /// Report globals that are defined by multiple object scripts, or shadow the Tabletop Simulator API
+    Globals,
+
+    /// List the objects that have files attached, with their aliases and metadata
+    List,
+
+    /// List the Lua and XML files of the project that aren't referenced by a tag, the Global script, or another file
+    Unused,
+
+    /// Print the dependency graph of the Lua modules, which are connected by `#include` and `require`
+    Graph {
+        /// Output format of the graph
+        #[arg(long, value_enum, default_value_t)]
+        format: graph::Format,
+
+        /// Write the graph to a file instead
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Explain which file a tag refers to, and which objects of the current save carry it
+    ExplainTag {
+        /// The tag to explain, e.g. `lua/foo/bar.ttslua`
+        #[arg(value_name = "TAG")]
+        tag: String,
+    },
+
+    /// List the zones of the current save, or the objects inside of a zone
+    Zones {
+        /// The GUID of the zone whose objects should be listed
+        #[arg(value_name = "GUID")]
+        #[arg(value_parser = parser::guid)]
+        guid: Option<String>,
     },
 
     /// Create a backup of the current save as a JSON file
@@ -91,34 +589,545 @@ enum Commands {
         #[arg(value_parser = parser::path_is_json)]
         path: PathBuf,
     },
+
+    /// View or set the name, game mode, tags, description and thumbnail of the current save
+    Meta {
+        #[command(flatten)]
+        args: MetaArgs,
+    },
+
+    /// Report what the size of a save is made up of, and remove blank scripts
+    Slim {
+        /// Path to the save file [default: the tracked save]
+        #[arg(value_name = "SAVE")]
+        save: Option<PathBuf>,
+    },
+
+    /// Check that saves survive being parsed and serialized again without losing data
+    VerifyRoundtrip {
+        /// Path(s) to the save files, the file names can contain `*` and `?` wildcards
+        #[arg(value_name = "SAVE(S)", required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Print the JSON Schema of the save file format
+    Schema {
+        /// Write the schema to a file instead
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Search the Lua scripts and XML UI of saves for a regular expression
+    Grep {
+        /// The regular expression to search for
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Search these saves instead of the current save, the file names can contain `*` and `?` wildcards
+        #[arg(long = "save", value_name = "SAVE")]
+        saves: Vec<PathBuf>,
+    },
+
+    /// Import the scripts of the official VSCode or Atom plugin into the current project
+    ImportOfficial {
+        /// Directory containing the `<Name>.<GUID>.lua` and `<Name>.<GUID>.xml` files
+        #[arg(value_name = "DIR")]
+        #[arg(value_parser = parser::path_exists)]
+        dir: PathBuf,
+
+        /// Directory the files are copied into
+        #[arg(long, value_name = "DIR", default_value = ".\\")]
+        into: PathBuf,
+    },
+
+    /// Export the scripts of the current save in the layout of the official VSCode or Atom plugin
+    ExportOfficial {
+        /// Directory the `<Name>.<GUID>.lua` and `<Name>.<GUID>.xml` files are written to
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// How the scripts of objects inside of containers, like the cards of a deck, are laid out
+        #[arg(long, value_enum, default_value_t)]
+        layout: official::Layout,
+
+        /// Write the top-level elements of every XML UI, e.g. panels, into separate files that are included using `<Include>`
+        #[arg(long)]
+        split_xml: bool,
+    },
+
+    /// Pretend to be Tabletop Simulator with a sample save loaded, to try ttsst without the game
+    Demo {
+        /// Directory the sample save is created in
+        #[arg(value_name = "DIR", default_value = ".ttsst/demo")]
+        dir: PathBuf,
+    },
+
+    /// List, add or remove aliases that can be used instead of GUIDs
+    Alias {
+        #[command(subcommand)]
+        command: Option<AliasCommands>,
+    },
+
+    /// Find autosaves that are newer than the tracked save, and offer to use them instead
+    Autosave,
+
+    /// Show the history of reloads
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommands>,
+    },
+
+    /// Manage translations for XML UI
+    L10n {
+        #[command(subcommand)]
+        command: L10nCommands,
+    },
+}
+
+impl Commands {
+    /// Returns `true` if the command prints JSON to stdout, which must not contain log messages.
+    fn prints_json(&self) -> bool {
+        use progress::Output::Json;
+        matches!(
+            self,
+            Commands::Reload { output: Json, .. }
+                | Commands::Watch {
+                    watch: WatchArgs { output: Json, .. },
+                    ..
+                }
+        )
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum AliasCommands {
+    /// Define an alias for the GUID of an object
+    Add {
+        /// Name of the alias, e.g. `score_board`
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// The GUID the alias refers to
+        #[arg(value_name = "GUID")]
+        guid: String,
+    },
+    /// Remove an alias
+    Remove {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommands {
+    /// Show the changes of a reload
+    Show {
+        /// Index of the reload as listed by the history command
+        #[arg(value_name = "N")]
+        index: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum L10nCommands {
+    /// Harvest `%key%` placeholders from XML UI files
+    Extract {
+        /// The path(s) to search for XML UI files
+        #[arg(value_name = "PATH(S)")]
+        #[arg(value_parser = parser::path_exists, default_value = ".\\")]
+        paths: Vec<PathBuf>,
+
+        /// Add missing keys to the translation file of a language
+        #[arg(short, long, value_name = "LANG")]
+        lang: Option<String>,
+    },
 }
 
 fn main() {
+    agent::remember_start_dir();
+    // Paths get validated while parsing the arguments,
+    // so the directory has to be changed before the arguments are parsed.
+    if let Err(err) = change_dir(std::env::args_os().skip(1)) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
     let cli = Cli::parse();
 
     if let Err(err) = run(cli) {
         log::error!("{}", err);
         std::process::exit(1);
     }
+    if interrupt::interrupted() {
+        std::process::exit(interrupt::EXIT_CODE);
+    }
 }
 
 fn run(args: Cli) -> Result<()> {
     use log::LevelFilter;
-    ConsoleLogger::new().init(match args.verbosity {
-        0 => LevelFilter::Info,
-        1 => LevelFilter::Debug,
-        _ => LevelFilter::Trace,
+    logger::init_color(args.color);
+    // Log messages would be mixed into JSON printed to stdout
+    ConsoleLogger::new()
+        .stderr(args.command.prints_json())
+        .init(match args.verbosity {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        })?;
+    interrupt::init()?;
+
+    // Resolve paths relative to the project root, and then enter the project root,
+    // so that tags are the same regardless of the directory ttsst is run from.
+    let mut project = Project::discover()?;
+    let mut args = args;
+    resolve_paths(&mut args.command, &project)?;
+    if let Some(log_file) = &args.log_file {
+        project.config.log.file = Some(project.absolute(log_file)?);
+    }
+    // Named pipes like `\\.\pipe\ttsst` are absolute, and must not be normalized
+    let events = match &args.events {
+        Some(events) if events.is_absolute() => Some(events.clone()),
+        Some(events) => Some(project.absolute(events)?),
+        None => None,
+    };
+    project.config.force |= args.force;
+    project.config.strict |= args.strict;
+    project.config.timeout = args.timeout.or(project.config.timeout);
+    project.config.retries = args.retries.unwrap_or(project.config.retries);
+    let project = project.enter()?;
+    logger::init_file(&project.config.log)?;
+    if let Some(events) = &events {
+        progress::init(events)?;
+    }
+    if let Some(namespace) = &project.config.namespace {
+        ttsst::tags::set_namespace(namespace)?;
+    }
+    ttsst::tags::set_extensions(ttsst::tags::Extensions {
+        lua: project.config.extensions.lua.clone(),
+        xml: project.config.extensions.xml.clone(),
     })?;
 
-    let api = tts_external_api::ExternalEditorApi::new();
+    let aliases = resolve_aliases(&mut args.command)?;
+
+    // Commands that don't require a connection to the game
+    match args.command {
+        Commands::Alias { command } => {
+            return match command {
+                Some(AliasCommands::Add { name, guid }) => alias::add(&name, &guid),
+                Some(AliasCommands::Remove { name }) => alias::remove(&name),
+                None => alias::list(),
+            }
+        }
+        Commands::History { command } => {
+            return match command {
+                Some(HistoryCommands::Show { index }) => history::show(index),
+                None => history::list(),
+            }
+        }
+        Commands::L10n { command } => {
+            return match command {
+                L10nCommands::Extract { paths, lang } => app::extract_l10n(&paths, lang),
+            }
+        }
+        Commands::Init {
+            dir,
+            template,
+            name,
+            save,
+        } => return init::init(&dir, template.as_deref(), name.as_deref(), save.as_deref()),
+        Commands::VerifyRoundtrip { paths } => {
+            return batch::run(&batch::saves(&paths)?, roundtrip::verify)
+        }
+        Commands::Schema { output } => return schema(output),
+        Commands::Demo { dir } => return demo::run(&dir, args.send_port, args.port),
+        Commands::Agent { ref watch } => {
+            let watch = (args.global_args().into_iter())
+                .chain(["watch".into()])
+                .chain(watch.iter().map(OsString::from))
+                .collect();
+            return agent::run(&args.host, args.send_port, &args.bind, args.port, watch);
+        }
+        Commands::Check { saves } if !saves.is_empty() => {
+            return batch::run(&batch::saves(&saves)?, strict::check_file);
+        }
+        Commands::Grep { pattern, saves } if !saves.is_empty() => {
+            let exprs = regex::Regex::new(&pattern)?;
+            return batch::run(&batch::saves(&saves)?, |save| grep::grep(save, &exprs));
+        }
+        _ => (),
+    }
+
+    let settings = &project.config.prompt;
+    let inquire = match settings.width {
+        Some(width) => InquirePrompter::new().with_width(width),
+        None => InquirePrompter::new(),
+    }
+    .with_layout(settings.layout);
+    let prompter: &dyn Prompter = match args.no_input {
+        true => &NonInteractive,
+        false => &inquire,
+    };
+    // Autosaves are compared with the tracked save, and saves are slimmed down on disk,
+    // so neither has to be loaded in-game
+    if let Commands::Autosave = args.command {
+        return autosave::check(prompter);
+    }
+    if let Commands::Slim { save } = args.command {
+        return slim::run(save, prompter);
+    }
+
+    let api = port::bind(&args.host, args.send_port, &args.bind, args.port)?;
+    if args.wait {
+        port::wait_for_game(&args.host, args.send_port);
+    }
     let mut save_file = SaveFile::read(&api)?;
+    alias::check(&save_file.save, &aliases);
 
     match args.command {
-        Commands::Attach { path, guids } => save_file.attach(&api, path, guids),
-        Commands::Detach { guids } => save_file.detach(&api, guids),
-        Commands::Reload { paths, args } => save_file.reload(&api, &paths, args),
-        Commands::Console => console::start(&save_file, &api, None::<&[PathBuf]>)?,
-        Commands::Watch { paths } => console::start(&save_file, &api, Some(&paths))?,
+        Commands::Attach {
+            path,
+            with,
+            pair,
+            global,
+            interactive_pick,
+            guids,
+        } => {
+            if interactive_pick && args.no_input {
+                bail!("--interactive-pick waits for Enter to be pressed, and can't be used with --no-input");
+            }
+            if path.is_dir() {
+                if with.is_some() || pair || global || interactive_pick || guids.guids.is_some() {
+                    #[rustfmt::skip]
+                    bail!("'{}' is a directory, whose files are attached one by one, so no GUIDs, --with, --pair, --global or --interactive-pick can be used", path.display());
+                }
+                return save_file.attach_dir(&api, prompter, &path, guids.all);
+            }
+            let other = match pair {
+                true => ttsst::tags::extensions().counterpart(&path),
+                false => with,
+            };
+            let paths = std::iter::once(path).chain(other).collect::<Vec<_>>();
+            match global {
+                true => save_file.attach_global(&api, prompter, &paths),
+                false => save_file.attach(&api, prompter, &paths, guids, interactive_pick),
+            }
+        }
+        Commands::Detach { global: true, .. } => save_file.detach_global(&api, prompter),
+        Commands::Detach { guids, .. } => save_file.detach(&api, prompter, guids),
+        Commands::Pull { guids } => save_file.pull(&api, prompter, guids),
+        #[rustfmt::skip]
+        Commands::Reload { paths, args, output } => save_file.reload(&api, prompter, &paths, args)?.print(output),
+        Commands::Console { args } => console::start(&save_file, &api, prompter, None, args),
+        #[rustfmt::skip]
+        Commands::Listen { custom, key, record } => listen::listen(&api, custom, key.as_deref(), record),
+        Commands::Watch { watch, .. } if watch.run_once => {
+            console::run_once(&mut save_file, &api, prompter, &watch)
+        }
+        #[rustfmt::skip]
+        Commands::Watch { watch, args } => console::start(&save_file, &api, prompter, Some(watch), args),
+        #[rustfmt::skip]
+        Commands::Clone { guid, args } => save_file.clone_object(&api, prompter, &guid, args),
+        Commands::Edit { guid, xml, file } => save_file.edit(&api, prompter, &guid, xml, file),
+        Commands::Mv { old, new } => save_file.rename(&api, prompter, old, new),
         Commands::Backup { path } => save_file.backup(path),
+        Commands::Meta { args } => save_file.meta(&api, prompter, args),
+        Commands::Exec { script, pick, all } => {
+            let object = match pick {
+                #[rustfmt::skip]
+                true => Some(app::select_object(prompter, &save_file.save.objects, "Select the object to execute the code on:", all)?),
+                false => None,
+            };
+            exec::exec(&api, &script, object.as_ref())
+        }
+        Commands::Repl => exec::repl(&api),
+        Commands::Grep { pattern, .. } => {
+            let exprs = regex::Regex::new(&pattern)?;
+            batch::run(&[save_file.path], |save| grep::grep(save, &exprs))
+        }
+        Commands::Zones { guid } => save_file.zones(&api, guid),
+        Commands::ExplainTag { tag } => explain::explain(&save_file.save, &tag),
+        Commands::FreezeGuids => freeze::freeze(&save_file.save),
+        Commands::Check { .. } => {
+            strict::check(&save_file.save)?;
+            save_file.check_guids(&api, prompter)
+        }
+        Commands::Globals => globals::report(&save_file.save),
+        Commands::List => metadata::list(&save_file.save),
+        Commands::Unused => unused::report(&save_file.save),
+        Commands::Graph { format, output } => graph::print(&save_file.save, format, output),
+        Commands::Stubs { output } => stubs::generate(&save_file.save, &output),
+        #[rustfmt::skip]
+        Commands::Cheatsheet { output, notebook } => cheatsheet::generate(&save_file.save, &api, &output, notebook.as_deref()),
+        #[rustfmt::skip]
+        Commands::ImportOfficial { dir, into } => save_file.import_official(&api, prompter, dir, into),
+        #[rustfmt::skip]
+        Commands::ExportOfficial { dir, layout, split_xml } => official::export(&save_file.save, &dir, layout, split_xml),
+        Commands::Init { .. }
+        | Commands::VerifyRoundtrip { .. }
+        | Commands::Schema { .. }
+        | Commands::Alias { .. }
+        | Commands::Demo { .. }
+        | Commands::Agent { .. }
+        | Commands::History { .. }
+        | Commands::Autosave
+        | Commands::Slim { .. }
+        | Commands::L10n { .. } => unreachable!(),
+    }
+}
+
+/// Prints the JSON Schema of the save file format, or writes it to `output`.
+fn schema(output: Option<PathBuf>) -> Result<()> {
+    let schema = serde_json::to_string_pretty(&ttsst::Save::json_schema())?;
+    match output {
+        Some(path) => std::fs::write(path, schema)?,
+        None => println!("{schema}"),
+    }
+    Ok(())
+}
+
+/// Changes the current directory to the directory of every `-C <DIR>` or `--cwd <DIR>` in `args`.
+/// Like `git -C`, multiple directories are resolved relative to the previous one.
+fn change_dir<I: Iterator<Item = OsString>>(mut args: I) -> Result<()> {
+    while let Some(arg) = args.next() {
+        let dir = match arg.to_str() {
+            // Arguments after `--` are never options
+            Some("--") => break,
+            Some("-C" | "--cwd") => args.next().map(PathBuf::from),
+            Some(arg) => arg
+                .strip_prefix("--cwd=")
+                .or_else(|| arg.strip_prefix("-C"))
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            None => None,
+        };
+        if let Some(dir) = dir {
+            std::env::set_current_dir(&dir)
+                .map_err(|err| anyhow!("cannot change to '{}': {}", dir.display(), err))?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts the paths of `command` into paths relative to the project root.
+fn resolve_paths(command: &mut Commands, project: &Project) -> Result<()> {
+    let relative = |paths: &mut Vec<PathBuf>| -> Result<()> {
+        for path in paths.iter_mut() {
+            *path = project.relative(&path)?;
+        }
+        Ok(())
+    };
+
+    match command {
+        Commands::Attach { path, with, .. } => {
+            *path = project.relative(&path)?;
+            if let Some(with) = with {
+                *with = project.relative(&with)?;
+            }
+        }
+        Commands::Reload { paths, .. } => relative(paths)?,
+        Commands::Clone { args, .. } => relative(&mut args.files)?,
+        Commands::Watch { watch, .. } => {
+            relative(&mut watch.paths)?;
+            for root in &mut watch.roots {
+                root.path = project.relative(&root.path)?;
+            }
+        }
+        Commands::ImportOfficial { dir, into } => {
+            *dir = project.absolute(&dir)?;
+            *into = project.relative(&into)?;
+        }
+        Commands::Edit {
+            file: Some(file), ..
+        } => *file = project.relative(&file)?,
+        Commands::Mv { old, new } => {
+            *old = project.relative(&old)?;
+            *new = project.relative(&new)?;
+        }
+        Commands::Init { dir, save, .. } => {
+            *dir = project.absolute(&dir)?;
+            if let Some(save) = save {
+                *save = project.absolute(&save)?;
+            }
+        }
+        Commands::Backup { path }
+        | Commands::Stubs { output: path }
+        | Commands::Cheatsheet { output: path, .. }
+        | Commands::ExportOfficial { dir: path, .. }
+        | Commands::Demo { dir: path } => *path = project.absolute(&path)?,
+        Commands::VerifyRoundtrip { paths }
+        | Commands::Check { saves: paths }
+        | Commands::Grep { saves: paths, .. } => {
+            for path in paths.iter_mut() {
+                *path = project.absolute(&path)?;
+            }
+        }
+        Commands::Schema {
+            output: Some(output),
+        }
+        | Commands::Graph {
+            output: Some(output),
+            ..
+        }
+        | Commands::Listen {
+            record: Some(output),
+            ..
+        }
+        | Commands::Slim { save: Some(output) } => *output = project.absolute(&output)?,
+        Commands::Meta { args } => {
+            if let Some(thumbnail) = &mut args.thumbnail {
+                *thumbnail = project.absolute(&thumbnail)?;
+            }
+        }
+        Commands::L10n {
+            command: L10nCommands::Extract { paths, .. },
+        } => relative(paths)?,
+        Commands::Detach { .. }
+        | Commands::Pull { .. }
+        | Commands::Edit { file: None, .. }
+        | Commands::Console { .. }
+        | Commands::Listen { record: None, .. }
+        | Commands::Exec { .. }
+        | Commands::Repl
+        | Commands::Zones { .. }
+        | Commands::ExplainTag { .. }
+        | Commands::FreezeGuids
+        | Commands::Globals
+        | Commands::List
+        | Commands::Unused
+        | Commands::Graph { output: None, .. }
+        | Commands::Schema { output: None }
+        | Commands::Alias { .. }
+        | Commands::Autosave
+        | Commands::Slim { save: None }
+        | Commands::Agent { .. }
+        | Commands::History { .. } => (),
+    }
+    Ok(())
+}
+
+/// Replaces the aliases in the GUIDs of `command` with the GUIDs they refer to.
+/// Returns the names of the aliases that have been used.
+fn resolve_aliases(command: &mut Commands) -> Result<Vec<String>> {
+    let mut aliases = Vec::new();
+    let mut resolve = |guid: &mut String| -> Result<()> {
+        let resolved = alias::resolve(guid)?;
+        if resolved != *guid {
+            aliases.push(std::mem::replace(guid, resolved));
+        }
+        Ok(())
+    };
+
+    match command {
+        Commands::Attach { guids, .. }
+        | Commands::Detach { guids, .. }
+        | Commands::Pull { guids } => {
+            for guid in guids.guids.iter_mut().flatten() {
+                resolve(guid)?;
+            }
+        }
+        Commands::Reload { args, .. } => args.guid.iter_mut().try_for_each(resolve)?,
+        Commands::Zones { guid } => guid.iter_mut().try_for_each(resolve)?,
+        Commands::Clone { guid, .. } | Commands::Edit { guid, .. } => resolve(guid)?,
+        _ => (),
     }
+    Ok(aliases)
 }
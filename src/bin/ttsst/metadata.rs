@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use serde::Deserialize;
+use ttsst::{Object, Save};
+
+use crate::config::config;
+use crate::utils::hash;
+
+/// Path of the sidecar file containing the metadata of the objects, which is kept out of the save.
+const METADATA_FILE: &str = ".ttsst/objects.toml";
+
+/// Metadata of an object, stored in a table named after its GUID.
+/// `owner` and `notes` are edited by hand, the other fields are maintained by ttsst.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ObjectMetadata {
+    /// Who is responsible for the object.
+    pub owner: Option<String>,
+    pub notes: Option<String>,
+    /// Paths of the files attached to the object, as of the most recent push.
+    pub files: Vec<String>,
+    /// Hash of the lua script and xml ui of the object, as of the most recent push.
+    pub pushed: Option<String>,
+}
+
+/// Reads the metadata of the objects by their GUID. A missing or corrupt file results in no metadata.
+pub fn load() -> BTreeMap<String, ObjectMetadata> {
+    let Ok(toml) = fs::read_to_string(METADATA_FILE) else {
+        return BTreeMap::new();
+    };
+    toml::from_str(&toml).unwrap_or_else(|err| {
+        warn!("ignoring the corrupt metadata file: {}", err);
+        BTreeMap::new()
+    })
+}
+
+/// Returns the number of objects in the `save` whose scripts differ from the ones that have been pushed,
+/// e.g. because they have been edited in-game. Always zero, unless `metadata` is enabled in the config.
+pub fn changed(save: &Save) -> usize {
+    if !config().metadata {
+        return 0;
+    }
+    let metadata = load();
+    let changed = save.objects.iter().filter(|object| {
+        metadata
+            .get(&object.guid)
+            .is_some_and(|metadata| is_changed(metadata, object))
+    });
+    changed.count()
+}
+
+/// Whether the scripts of `object` differ from the ones that have been pushed according to its `metadata`.
+fn is_changed(metadata: &ObjectMetadata, object: &Object) -> bool {
+    metadata
+        .pushed
+        .as_ref()
+        .is_some_and(|pushed| *pushed != scripts_hash(object))
+}
+
+/// Returns the hash of the lua script and xml ui of an `object`.
+fn scripts_hash(object: &Object) -> String {
+    hash(&format!("{}\0{}", object.lua_script, object.xml_ui))
+}
+
+/// Records the attached files and the hash of the scripts of the objects in the `save` that has been pushed,
+/// if `metadata` is enabled in the config. Objects without attached files lose these fields.
+pub fn pushed(save: &Save) -> Result<()> {
+    record(save.objects.iter(), true)
+}
+
+/// Records the attached files of `objects` after files have been attached to or detached from them,
+/// if `metadata` is enabled in the config. Their hash is only updated once they have been pushed.
+pub fn attached<'a, I: IntoIterator<Item = &'a Object>>(objects: I) -> Result<()> {
+    record(objects, false)
+}
+
+/// Records the attached files of `objects`, and the hash of their scripts if they have been `pushed`.
+///
+/// The file is edited in place, so that the formatting and comments of the hand-written fields are kept.
+fn record<'a, I: IntoIterator<Item = &'a Object>>(objects: I, pushed: bool) -> Result<()> {
+    if !config().metadata {
+        return Ok(());
+    }
+    let path = Path::new(METADATA_FILE);
+    let mut document = match path.exists() {
+        true => fs::read_to_string(path)?.parse::<toml_edit::DocumentMut>()?,
+        false => toml_edit::DocumentMut::new(),
+    };
+
+    for object in objects {
+        let files = [object.valid_lua(), object.valid_xml()]
+            .into_iter()
+            .filter_map(|tag| tag.ok().flatten()?.path().ok())
+            .map(|path| {
+                path.strip_prefix(".")
+                    .unwrap_or(&path)
+                    .to_slash_lossy()
+                    .to_string()
+            })
+            .collect_vec();
+
+        if files.is_empty() {
+            if let Some(table) = document
+                .get_mut(&object.guid)
+                .and_then(|item| item.as_table_mut())
+            {
+                table.remove("files");
+                table.remove("pushed");
+                if table.is_empty() {
+                    document.remove(&object.guid);
+                }
+            }
+            continue;
+        }
+
+        let Some(table) = document
+            .entry(&object.guid)
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+        else {
+            bail!(
+                "'{}' in {} is not a table",
+                object.guid,
+                METADATA_FILE.yellow()
+            );
+        };
+        table["files"] = toml_edit::value(files.into_iter().collect::<toml_edit::Array>());
+        if pushed {
+            table["pushed"] = toml_edit::value(scripts_hash(object));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, document.to_string())?;
+    Ok(())
+}
+
+/// Prints the objects that have files attached or metadata, together with their aliases and metadata.
+/// Objects whose scripts differ from the ones that have been pushed, e.g. because they have been edited in-game, are marked.
+pub fn list(save: &Save) -> Result<()> {
+    let metadata = load();
+    let objects = save
+        .objects
+        .iter()
+        .filter(|object| {
+            object.tags.iter().any(|tag| tag.is_valid()) || metadata.contains_key(&object.guid)
+        })
+        .collect_vec();
+    if objects.is_empty() {
+        info!("no objects have files attached");
+    }
+
+    for object in objects {
+        let aliases = config()
+            .aliases
+            .iter()
+            .filter(|(_, guid)| **guid == object.guid)
+            .map(|(name, _)| name.bright_white().bold())
+            .join(", ");
+        match aliases.is_empty() {
+            true => println!("{object}"),
+            false => println!("{object} as {aliases}"),
+        }
+
+        let Some(metadata) = metadata.get(&object.guid) else {
+            continue;
+        };
+        if let Some(owner) = &metadata.owner {
+            println!("    owner: {}", owner);
+        }
+        if let Some(notes) = &metadata.notes {
+            println!("    notes: {}", notes);
+        }
+        if is_changed(metadata, object) {
+            println!("    {}", "changed since the most recent push".yellow());
+        }
+    }
+    Ok(())
+}
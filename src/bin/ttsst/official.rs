@@ -0,0 +1,475 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use ttsst::{Object, Save, Tag};
+
+use crate::app::read_file;
+use crate::config::normalize;
+use crate::utils::write_atomic;
+use crate::{include, interrupt};
+
+/// GUID the official plugins use for the Global script and ui.
+pub const GLOBAL_GUID: &str = "-1";
+
+/// A lua script or xml ui in the layout of the official editor plugins for VSCode and Atom,
+/// which name their files `<Name>.<GUID>.lua` and `<Name>.<GUID>.xml`.
+#[derive(Debug)]
+pub struct OfficialFile {
+    pub name: String,
+    pub guid: String,
+    /// Either `lua` or `xml`.
+    pub ext: &'static str,
+    /// Directory of the file, relative to the directory that has been read.
+    pub dir: PathBuf,
+    pub path: PathBuf,
+}
+
+/// Reads the files in `dir` and its subdirectories that follow the naming convention of the official plugins,
+/// e.g. the files written by [`export`] for objects inside of containers. Other files are ignored.
+pub fn read_dir(dir: &Path) -> Result<Vec<OfficialFile>> {
+    let exprs = Regex::new(r"^(?<name>.*)\.(?<guid>[0-9a-zA-Z]{6}|-1)\.(?<ext>lua|ttslua|xml)$")?;
+    let mut files = Vec::new();
+    read_files(dir, Path::new(""), &exprs, &mut files)?;
+
+    match files.is_empty() {
+        #[rustfmt::skip]
+        true => bail!("'{}' contains no files named `<Name>.<GUID>.lua` or `<Name>.<GUID>.xml`", dir.to_slash_lossy().yellow()),
+        false => Ok(files),
+    }
+}
+
+/// Adds the files in the `relative` directory of `root` that match `exprs` to `files`, and then the ones in its subdirectories.
+fn read_files(
+    root: &Path,
+    relative: &Path,
+    exprs: &Regex,
+    files: &mut Vec<OfficialFile>,
+) -> Result<()> {
+    let paths = fs::read_dir(root.join(relative))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .sorted()
+        .collect_vec();
+
+    files.extend(
+        paths
+            .iter()
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                let file_name = path.file_name()?.to_str()?.to_owned();
+                let captures = exprs.captures(&file_name)?;
+                Some(OfficialFile {
+                    name: captures["name"].into(),
+                    guid: captures["guid"].into(),
+                    ext: match &captures["ext"] {
+                        "xml" => "xml",
+                        _ => "lua",
+                    },
+                    dir: relative.to_path_buf(),
+                    path: path.clone(),
+                })
+            }),
+    );
+    for path in paths.iter().filter(|path| path.is_dir()) {
+        if let Some(name) = path.file_name() {
+            read_files(root, &relative.join(name), exprs, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the files of the official plugins in `dir` into the `into` directory.
+/// Objects are stored as `<Name>.lua` and `<Name>.xml`, and Global as `Global.lua` and `Global.xml`.
+/// Files in subdirectories of `dir` keep their directory, e.g. the ones [`export`] writes for objects inside of containers.
+/// Objects with the same name and script share a file, otherwise the GUID is appended to the name.
+///
+/// Files inside of `dir` that are included by a xml ui using `<Include src="path"/>` are copied as well,
+/// keeping their path relative to `dir`, e.g. the files written by [`export`] with `--split-xml`.
+///
+/// Returns the GUID of the object and the path of the file it uses, in the `.\<FilePath>` form of `into`.
+/// Fails without copying anything, if a file with a different content already exists.
+pub fn import(dir: &Path, into: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut targets: BTreeMap<PathBuf, String> = BTreeMap::new();
+    let mut imported = Vec::new();
+    for file in read_dir(dir)? {
+        if file.ext == "xml" {
+            for include in include::read_xml(&file.path)?.includes {
+                let Ok(relative) = include.strip_prefix(dir) else {
+                    continue;
+                };
+                if relative
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_)))
+                {
+                    continue;
+                }
+                let path = into.join(relative);
+                let content = read_file(&include)?;
+                if normalize(&path).exists() && read_file(normalize(&path))? != content {
+                    #[rustfmt::skip]
+                    bail!("'{}' already exists, use `--into <DIR>` to import into a different directory", path.to_slash_lossy().yellow());
+                }
+                targets.insert(path, content);
+            }
+        }
+
+        let content = read_file(&file.path)?;
+        let name = match file.guid == GLOBAL_GUID {
+            true => "Global".into(),
+            false => sanitize(&file.name),
+        };
+
+        let dir = into.join(&file.dir);
+        let mut path = dir.join(format!("{}.{}", name, file.ext));
+        if targets.get(&path).is_some_and(|other| *other != content) {
+            path = dir.join(format!("{}_{}.{}", name, file.guid, file.ext));
+        }
+        let file_path = normalize(&path);
+        if file_path.exists() && read_file(&file_path)? != content && !targets.contains_key(&path) {
+            #[rustfmt::skip]
+            bail!("'{}' already exists, use `--into <DIR>` to import into a different directory", path.to_slash_lossy().yellow());
+        }
+
+        targets.insert(path.clone(), content);
+        imported.push((file.guid, path));
+    }
+
+    let _deferred = interrupt::defer();
+    for (path, content) in &targets {
+        if let Some(dir) = normalize(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(normalize(path), content)?;
+        debug!("wrote '{}'", path.to_slash_lossy());
+    }
+    info!(
+        "imported {} file(s) into '{}'",
+        targets.len(),
+        into.to_slash_lossy().yellow()
+    );
+    Ok(imported)
+}
+
+/// How [`export`] lays out the scripts of objects inside of containers, like the cards of a deck.
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum Layout {
+    /// Write them into a directory named after the container, e.g. `Bag/Card.a1b2c3.lua`
+    #[default]
+    Nested,
+    /// Write them into the same directory as every other file, like the official plugins do
+    Flat,
+}
+
+/// Writes the Global script and ui, and the files attached to the objects of `save`,
+/// into `dir` using the naming convention of the official plugins, so that they can be edited with them.
+/// Attached files are read from the project, so that changes which haven't been reloaded yet are exported as well.
+///
+/// The scripts of objects inside of containers are exported as well, according to the `layout`.
+/// Since they can't have files attached, the scripts stored in the save are used.
+///
+/// If `split_xml` is set, the top-level elements of every xml ui are written into separate files, see [`split`].
+pub fn export(save: &Save, dir: &Path, layout: Layout, split_xml: bool) -> Result<()> {
+    let mut files = vec![(
+        dir.join(file_name("Global", GLOBAL_GUID, "lua")),
+        save.lua_script.to_string(),
+    )];
+    if !save.xml_ui.is_empty() {
+        files.push((
+            dir.join(file_name("Global", GLOBAL_GUID, "xml")),
+            save.xml_ui.to_string(),
+        ));
+    }
+
+    for object in save.objects.iter() {
+        // Fall back to the script in the save, if the attached file doesn't exist
+        let read = |tag: Tag, script: &str| match read_file(tag.path()?) {
+            Ok(file) => Ok::<_, anyhow::Error>(file),
+            Err(_) => {
+                warn!(
+                    "{} does not exist, exporting the version in the save instead",
+                    tag
+                );
+                Ok(script.into())
+            }
+        };
+
+        let name = object.display_name();
+        if let Some(tag) = object.valid_lua()? {
+            let path = dir.join(file_name(name, &object.guid, "lua"));
+            files.push((path, read(tag, &object.lua_script)?));
+        }
+        if let Some(tag) = object.valid_xml()? {
+            let path = dir.join(file_name(name, &object.guid, "xml"));
+            files.push((path, read(tag, &object.xml_ui)?));
+        }
+        contained_files(object, dir, layout, &mut files)?;
+    }
+    if split_xml {
+        files = files
+            .into_iter()
+            .flat_map(|(path, content)| match path.extension() {
+                Some(ext) if ext == "xml" => split(&path, &content),
+                _ => vec![(path, content)],
+            })
+            .collect();
+    }
+
+    let _deferred = interrupt::defer();
+    for (path, content) in &files {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, content)?;
+        debug!("wrote '{}'", path.to_slash_lossy());
+    }
+    info!(
+        "exported {} file(s) into '{}'",
+        files.len(),
+        dir.to_slash_lossy().yellow()
+    );
+    Ok(())
+}
+
+/// Adds the scripts of the objects inside of `container`, and of the containers inside of them, to `files`.
+fn contained_files(
+    container: &Object,
+    dir: &Path,
+    layout: Layout,
+    files: &mut Vec<(PathBuf, String)>,
+) -> Result<()> {
+    let objects = container.contained_objects()?;
+    if objects.is_empty() {
+        return Ok(());
+    }
+    let dir = match layout {
+        Layout::Nested => dir.join(sanitize(container.display_name())),
+        Layout::Flat => dir.to_path_buf(),
+    };
+
+    for object in &objects {
+        let name = object.display_name();
+        for (ext, script) in [("lua", &object.lua_script), ("xml", &object.xml_ui)] {
+            if !script.is_empty() {
+                files.push((
+                    dir.join(file_name(name, &object.guid, ext)),
+                    script.to_string(),
+                ));
+            }
+        }
+        contained_files(object, &dir, layout, files)?;
+    }
+    Ok(())
+}
+
+/// Splits the xml ui that is written to `path` into a file for each of its top-level elements, e.g. panels,
+/// and replaces them with `<Include src="path"/>` tags. The files are named after the `id` of their element, or its tag,
+/// and are written into a directory named after the file, e.g. `Board.a1b2c3/menu.xml` for `Board.a1b2c3.xml`.
+///
+/// Returns the file at `path` and the included files. The ui is kept as it is,
+/// if it contains less than two elements that could be split, or if it can't be parsed.
+fn split(path: &Path, xml: &str) -> Vec<(PathBuf, String)> {
+    let elements = match top_level_elements(xml) {
+        Some(elements) => elements
+            .into_iter()
+            .filter(|element| element.name != "Include")
+            .collect_vec(),
+        None => {
+            warn!(
+                "'{}' could not be split, since it is not valid xml",
+                path.to_slash_lossy().yellow()
+            );
+            vec![]
+        }
+    };
+    if elements.len() < 2 {
+        return vec![(path.to_path_buf(), xml.into())];
+    }
+
+    let dir = path.with_extension("");
+    let dir_name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let mut files = Vec::new();
+    let mut names: BTreeMap<String, usize> = BTreeMap::new();
+    let mut main = String::new();
+    let mut last = 0;
+    for Element { range, name, id } in elements {
+        let name = sanitize(&id.unwrap_or(name));
+        let count = names.entry(name.clone()).or_default();
+        *count += 1;
+        let name = match count {
+            1 => format!("{name}.xml"),
+            n => format!("{name}_{n}.xml"),
+        };
+
+        main += &xml[last..range.start];
+        main += &format!("<Include src=\"./{dir_name}/{name}\"/>");
+        last = range.end;
+        files.push((dir.join(name), format!("{}\n", &xml[range])));
+    }
+    main += &xml[last..];
+    files.insert(0, (path.to_path_buf(), main));
+    files
+}
+
+/// A top-level element of a xml ui.
+struct Element {
+    /// Byte range of the element, from its opening to its closing tag.
+    range: Range<usize>,
+    /// Name of its tag, e.g. `Panel`.
+    name: String,
+    id: Option<String>,
+}
+
+/// Returns the top-level elements of `xml`. Comments, processing instructions and text between the elements are skipped.
+/// Returns `None` if the tags aren't balanced or aren't closed.
+fn top_level_elements(xml: &str) -> Option<Vec<Element>> {
+    let ids = Regex::new(r#"\sid\s*=\s*"([^"]*)""#).unwrap();
+    let mut elements: Vec<Element> = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut i = 0;
+    while let Some(offset) = xml[i..].find('<') {
+        let open = i + offset;
+        let rest = &xml[open..];
+        let skip = [
+            ("<!--", "-->"),
+            ("<![CDATA[", "]]>"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .into_iter()
+        .find(|(prefix, _)| rest.starts_with(prefix));
+        if let Some((_, suffix)) = skip {
+            i = open + rest.find(suffix)? + suffix.len();
+            continue;
+        }
+
+        // Find the end of the tag, ignoring `>` inside of quoted attribute values
+        let mut quote = None;
+        let close = rest.char_indices().find_map(|(j, c)| match (quote, c) {
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                None
+            }
+            (Some(q), c) if q == c => {
+                quote = None;
+                None
+            }
+            (None, '>') => Some(j),
+            _ => None,
+        })?;
+        let tag = &rest[..=close];
+        i = open + close + 1;
+
+        if tag.starts_with("</") {
+            depth = depth.checked_sub(1)?;
+            if depth == 0 {
+                elements.last_mut()?.range = start..i;
+            }
+            continue;
+        }
+        if depth == 0 {
+            start = open;
+            let name = tag[1..]
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or_default();
+            let id = ids.captures(tag).map(|captures| captures[1].to_string());
+            elements.push(Element {
+                range: start..i,
+                name: name.to_string(),
+                id,
+            });
+        }
+        if !tag.ends_with("/>") {
+            depth += 1;
+        }
+    }
+    match depth {
+        0 => Some(elements),
+        _ => None,
+    }
+}
+
+/// Returns the name of a file in the layout of the official plugins, e.g. `Card.a1b2c3.lua`.
+pub fn file_name(name: &str, guid: &str, ext: &str) -> String {
+    format!("{}.{}.{}", sanitize(name), guid, ext)
+}
+
+/// Replaces characters that can't be used in file names, and removes trailing dots and spaces,
+/// which are not supported on Windows.
+fn sanitize(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    match name.trim_end_matches(['.', ' ']) {
+        "" => "Object".into(),
+        name => name.into(),
+    }
+}
+
+/// Returns the GUIDs of the objects inside of the containers of `save`, and of the containers inside of them.
+pub fn contained_guids(save: &Save) -> Result<HashSet<String>> {
+    let mut guids = HashSet::new();
+    let mut queue = save.objects.iter().cloned().collect_vec();
+    while let Some(object) = queue.pop() {
+        for contained in object.contained_objects()? {
+            guids.insert(contained.guid.clone());
+            queue.push(contained);
+        }
+    }
+    Ok(guids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdirectories_are_read() {
+        let dir = std::env::temp_dir().join("ttsst-official-read-dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Bag/Deck")).unwrap();
+        for file in [
+            "Global.-1.lua",
+            "Bag.a1b2c3.lua",
+            "Bag/notes.txt",
+            "Bag/Deck/Card.d4e5f6.xml",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let files = read_dir(&dir).unwrap();
+        let found = files
+            .iter()
+            .map(|file| {
+                (
+                    file.dir.to_slash_lossy().to_string(),
+                    file.guid.as_str(),
+                    file.ext,
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            found,
+            [
+                ("".into(), "a1b2c3", "lua"),
+                ("".into(), "-1", "lua"),
+                ("Bag/Deck".into(), "d4e5f6", "xml"),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
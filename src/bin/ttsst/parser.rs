@@ -1,27 +1,47 @@
 use std::{ffi::OsStr, path::PathBuf};
 use thiserror::Error;
 
+use crate::config::{Glob, WatchRoot};
+
 #[derive(Error, Debug)]
 pub enum ParseError {
-    #[error("not a valid GUID")]
+    #[error("not a valid GUID or alias")]
     InvalidGUID,
     #[error("not a file")]
     NotAFile,
     #[error("does not exist")]
     DoesNotExist,
+    #[error("is a directory")]
+    IsDirectory,
     #[error("not a json file")]
     NotJsonFile,
+    #[error("expected three numbers separated by commas, e.g. '2,0,0'")]
+    InvalidVector,
+    #[error("unknown option '{0}', expected 'flat', 'ignore=<PATTERN>' or 'exec=<COMMAND>'")]
+    UnknownOption(String),
+    #[error("'{0}' is not a valid pattern")]
+    InvalidPattern(String),
 }
 
+/// Accepts a GUID or an alias, which gets resolved once the config has been read.
 pub fn guid(s: &str) -> Result<String, ParseError> {
-    let len = s.len();
-    let is_numerical = s.chars().all(|c| c.is_ascii_alphanumeric());
-    match (len, is_numerical) {
-        (6, true) => Ok(s.into()),
-        _ => Err(ParseError::InvalidGUID),
+    match is_guid(s) || is_alias(s) {
+        true => Ok(s.into()),
+        false => Err(ParseError::InvalidGUID),
     }
 }
 
+pub fn is_guid(s: &str) -> bool {
+    s.len() == 6 && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Aliases start with a letter and only contain letters, digits, `_` and `-`.
+pub fn is_alias(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 pub fn path_is_file(s: &str) -> Result<PathBuf, ParseError> {
     let path = PathBuf::from(s);
     match path.is_file() {
@@ -30,6 +50,14 @@ pub fn path_is_file(s: &str) -> Result<PathBuf, ParseError> {
     }
 }
 
+pub fn path_is_not_dir(s: &str) -> Result<PathBuf, ParseError> {
+    let path = PathBuf::from(s);
+    match path.is_dir() {
+        true => Err(ParseError::IsDirectory),
+        false => Ok(path),
+    }
+}
+
 pub fn path_exists(s: &str) -> Result<PathBuf, ParseError> {
     let path = PathBuf::from(s);
     match path.exists() {
@@ -45,3 +73,48 @@ pub fn path_is_json(s: &str) -> Result<PathBuf, ParseError> {
         false => Err(ParseError::NotJsonFile),
     }
 }
+
+/// Parses three numbers separated by commas, e.g. `2,0,-1.5`.
+pub fn vector(s: &str) -> Result<[f64; 3], ParseError> {
+    let numbers = s
+        .split(',')
+        .map(|n| n.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ParseError::InvalidVector)?;
+    numbers.try_into().map_err(|_| ParseError::InvalidVector)
+}
+
+/// Options of a watched path, see [`watch_root`].
+const WATCH_OPTIONS: [&str; 3] = ["flat", "ignore=", "exec="];
+
+/// Parses `<PATH>[:<OPTIONS>]`, where the options are separated by commas:
+/// `flat` doesn't watch subdirectories, `ignore=<PATTERN>` ignores matching files,
+/// and `exec=<COMMAND>` runs a command. Since commands can contain commas, `exec` has to come last.
+pub fn watch_root(s: &str) -> Result<WatchRoot, ParseError> {
+    // Windows paths contain a `:` after the drive letter, so only a `:` followed by an option starts the options
+    let (path, mut options) = s
+        .match_indices(':')
+        .map(|(i, _)| (&s[..i], &s[i + 1..]))
+        .find(|(_, options)| WATCH_OPTIONS.iter().any(|o| options.starts_with(o)))
+        .unwrap_or((s, ""));
+
+    let mut root = WatchRoot::new(path_exists(path)?);
+    while !options.is_empty() {
+        if let Some(command) = options.strip_prefix("exec=") {
+            root.exec = Some(command.into());
+            break;
+        }
+
+        let (option, rest) = options.split_once(',').unwrap_or((options, ""));
+        match option.split_once('=') {
+            None if option == "flat" => root.recursive = false,
+            Some(("ignore", pattern)) => root.ignore.push(
+                Glob::try_from(pattern.to_string())
+                    .map_err(|_| ParseError::InvalidPattern(pattern.into()))?,
+            ),
+            _ => return Err(ParseError::UnknownOption(option.into())),
+        }
+        options = rest;
+    }
+    Ok(root)
+}
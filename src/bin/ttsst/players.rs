@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use serde::Deserialize;
+use serde_json::Value;
+use ttsst::api::ExecuteBatch;
+use ttsst::Prompter;
+
+use crate::app::Executor;
+use crate::config::config;
+
+/// Returns the players that are connected to the game, except for the host, who is running ttsst.
+const PLAYERS: &str = r#"local players = {}
+for _, player in ipairs(Player.getPlayers()) do
+    if not player.host then
+        table.insert(players, { name = player.steam_name, color = player.color, seated = player.seated })
+    end
+end
+return players"#;
+
+/// The players that have been confirmed to be connected during a reload, so that watching doesn't ask again on every change.
+static CONFIRMED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A player connected to the game, other than the host.
+#[derive(Deserialize, Debug)]
+struct Player {
+    name: String,
+    color: String,
+    #[serde(default)]
+    seated: bool,
+}
+
+/// Asks whether to reload if other players are seated, since a reload resets the scripting state of the game in the middle of it.
+/// Reloading is only allowed without asking if `--force` is passed, or if the same players have been confirmed before.
+///
+/// If the players can't be determined, e.g. because the game doesn't answer the probe, the reload is allowed.
+pub fn check(api: &impl Executor, prompter: &dyn Prompter) -> Result<()> {
+    if config().force {
+        return Ok(());
+    }
+    let players = match connected(api) {
+        Ok(players) => players,
+        Err(err) => {
+            debug!("could not check for other players: {}", err);
+            return Ok(());
+        }
+    };
+    if !players.iter().any(|player| player.seated) {
+        return Ok(());
+    }
+
+    let names = players
+        .iter()
+        .map(|player| match player.seated {
+            true => format!("{} ({})", player.name, player.color),
+            false => format!("{} (spectating)", player.name),
+        })
+        .collect_vec();
+    if *CONFIRMED.lock().unwrap() == names {
+        return Ok(());
+    }
+
+    warn!(
+        "other players are connected: {}",
+        names.iter().map(|name| name.yellow()).join(", ")
+    );
+    let message = format!(
+        "Reloading resets the scripting state for {}. Do you want to reload anyway?",
+        names.join(", ")
+    );
+    if !prompter.confirm(&message, false)? {
+        bail!("other players are seated, use --force to reload anyway");
+    }
+    *CONFIRMED.lock().unwrap() = names;
+    Ok(())
+}
+
+/// Returns the players connected to the game, except for the host.
+fn connected(api: &impl Executor) -> Result<Vec<Player>> {
+    let mut batch = ExecuteBatch::new();
+    batch.push("players", PLAYERS);
+    let Some(outcome) = api.execute_batch(&batch)?.into_iter().next() else {
+        bail!("the game didn't answer");
+    };
+    match outcome.result {
+        // Empty tables are encoded as objects
+        Ok(Some(Value::Object(object))) if object.is_empty() => Ok(Vec::new()),
+        Ok(Some(value)) => Ok(serde_json::from_value(value)?),
+        Ok(None) => Ok(Vec::new()),
+        Err(err) => bail!("{}", err),
+    }
+}
@@ -0,0 +1,127 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use ttsst::api::ExternalEditorApi as Api;
+use ttsst::error::Error;
+
+use crate::config::config;
+
+/// Starts listening on `port` of the `bind` interface, and sends messages to `send_port` of the game running on `host`.
+/// If the port is already in use, e.g. by the official VSCode or Atom plugin,
+/// the returned error contains the process that owns the port, where the platform allows to find it.
+///
+/// The timeout and retries of the config are applied to the returned api.
+pub fn bind(host: &str, send_port: u16, bind: &str, port: u16) -> Result<Api> {
+    match Api::with_hosts(host, send_port, bind, port) {
+        Ok(api) => match config().timeout() {
+            Some(timeout) => Ok(api.timeout(timeout).retries(config().retries)),
+            None => Ok(api),
+        },
+        Err(Error::PortInUse(port)) => {
+            let owner = match owner(port) {
+                Some((name, pid)) => format!(" by '{}' (pid {})", name.yellow(), pid),
+                None => String::new(),
+            };
+            Err(anyhow!(
+                "port {} is already in use{}\n\
+                 Close other editor plugins for Tabletop Simulator, like the official VSCode or Atom plugin,\n\
+                 or use `--port <PORT>` to listen on a different port, if the answers of the game are forwarded to it",
+                port,
+                owner
+            ))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// How long to wait for the game to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often [`wait_for_game`] checks whether the game is running.
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns `true` if the game running on `host` accepts connections on `send_port`.
+pub fn game_running(host: &str, send_port: u16) -> bool {
+    let Ok(mut addrs) = (host, send_port).to_socket_addrs() else {
+        return false;
+    };
+    addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+}
+
+/// Waits until the game running on `host` accepts connections, e.g. if ttsst has been started together with the game.
+pub fn wait_for_game(host: &str, send_port: u16) {
+    if game_running(host, send_port) {
+        return;
+    }
+    info!("waiting for Tabletop Simulator to start");
+    while !game_running(host, send_port) {
+        std::thread::sleep(WAIT_INTERVAL);
+    }
+    info!("Tabletop Simulator has started");
+}
+
+/// Returns the name and the id of the process listening on `port`.
+fn owner(port: u16) -> Option<(String, u32)> {
+    match cfg!(windows) {
+        true => owner_netstat(port),
+        false => owner_lsof(port).or_else(|| owner_ss(port)),
+    }
+}
+
+/// Runs `program` and returns its output, if it succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        false => None,
+    }
+}
+
+/// Uses `netstat` to find the process id, and `tasklist` to find its name.
+fn owner_netstat(port: u16) -> Option<(String, u32)> {
+    // e.g. `  TCP    127.0.0.1:39998    0.0.0.0:0    LISTENING    1234`
+    let netstat = output("netstat", &["-ano", "-p", "TCP"])?;
+    let pid = netstat.lines().find_map(|line| {
+        let columns = line.split_whitespace().collect_vec();
+        match columns.as_slice() {
+            [_, local, _, "LISTENING", pid] if local.ends_with(&format!(":{port}")) => {
+                pid.parse().ok()
+            }
+            _ => None,
+        }
+    })?;
+
+    // e.g. `"Code.exe","1234","Console","1","105,344 K"`
+    let filter = format!("PID eq {pid}");
+    let tasklist = output("tasklist", &["/FI", &filter, "/FO", "CSV", "/NH"])?;
+    let name = tasklist.split(',').next()?.trim().trim_matches('"');
+    Some((name.into(), pid))
+}
+
+/// Uses `lsof`, which prints the process id and name on lines starting with `p` and `c`.
+fn owner_lsof(port: u16) -> Option<(String, u32)> {
+    let filter = format!("-iTCP:{port}");
+    let lsof = output("lsof", &["-nP", &filter, "-sTCP:LISTEN", "-Fpc"])?;
+    let pid = lsof.lines().find_map(|line| line.strip_prefix('p'))?;
+    let name = lsof.lines().find_map(|line| line.strip_prefix('c'))?;
+    Some((name.into(), pid.parse().ok()?))
+}
+
+/// Uses `ss`, which prints the process as `users:(("node",pid=1234,fd=20))`.
+fn owner_ss(port: u16) -> Option<(String, u32)> {
+    let filter = format!("sport = :{port}");
+    let ss = output("ss", &["-ltnpH", &filter])?;
+    let users = ss.split_once("users:((\"")?.1;
+    let (name, rest) = users.split_once('"')?;
+    let pid = rest
+        .split_once("pid=")?
+        .1
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+    Some((name.into(), pid.parse().ok()?))
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use serde_json::Value;
+use ttsst::api::ExecuteBatch;
+
+use crate::app::Executor;
+use crate::config::config;
+
+/// Evaluates the probes of the config, prints their values,
+/// and appends them to the probe log if one has been configured.
+/// A probe that fails is logged, and its value is left empty.
+pub fn run(api: &impl Executor) -> Result<()> {
+    let probes = &config().probes;
+    if probes.is_empty() {
+        return Ok(());
+    }
+
+    // All probes are evaluated at once, instead of waiting for the game to answer each of them
+    let mut batch = ExecuteBatch::new();
+    for (name, expression) in probes {
+        batch.push(name, script(expression));
+    }
+    let outcomes = api.execute_batch(&batch)?;
+
+    let mut values = Vec::new();
+    for outcome in outcomes {
+        let value = match outcome.result {
+            Ok(value) => format(value),
+            Err(err) => {
+                warn!("probe '{}' failed: {}", outcome.label.yellow(), err);
+                String::new()
+            }
+        };
+        info!(
+            "{} = {}",
+            outcome.label.yellow(),
+            value.bright_white().bold()
+        );
+        values.push(value);
+    }
+
+    if let Some(path) = &config().probe_log {
+        append(path, probes.keys().collect_vec(), values)?;
+    }
+    Ok(())
+}
+
+/// Turns an `expression` into a script that returns its value, unless it is a script that returns a value itself.
+fn script(expression: &str) -> String {
+    match expression.trim_start().starts_with("return") {
+        true => expression.into(),
+        false => format!("return {expression}"),
+    }
+}
+
+/// Formats the return value of a probe. Strings are not quoted.
+fn format(value: Option<Value>) -> String {
+    match value {
+        Some(Value::String(string)) => string,
+        Some(Value::Null) | None => "nil".into(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Appends a row with the current time and `values` to the csv file at `path`.
+/// The header containing the `names` of the probes is written when the file is created.
+fn append(path: &Path, names: Vec<&String>, values: Vec<String>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let exists = path.exists();
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    let mut writer = csv::Writer::from_writer(file);
+    if !exists {
+        writer.write_record(std::iter::once(&"timestamp".to_string()).chain(names))?;
+    }
+    let timestamp = chrono::Local::now().to_rfc3339();
+    writer.write_record(std::iter::once(timestamp).chain(values))?;
+    writer.flush()?;
+    Ok(())
+}
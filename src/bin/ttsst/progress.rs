@@ -0,0 +1,126 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::Colorize;
+use log::*;
+use serde::Serialize;
+use ttsst::api::AnswerError;
+
+static EVENTS: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// The progress of a reload, so that other programs, like editor extensions, can display it.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A reload of `paths` has started.
+    ReloadStarted { paths: Vec<String> },
+    /// `count` attached files have been read, the other files haven't changed.
+    FilesRead { count: usize },
+    /// The scripts have been sent, and the game is reloading the save.
+    WaitingForGame,
+    /// The reload has completed, see [`ReloadReport`].
+    Completed(ReloadReport),
+    /// The reload has failed with `error`.
+    Failed { error: String },
+}
+
+/// The result of a reload, which is emitted when it has completed, passed to the commands run after it,
+/// and printed by `reload --output json`.
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct ReloadReport {
+    /// False if no file has changed, so the save hasn't been reloaded.
+    pub changed: bool,
+    /// GUIDs of the objects whose scripts have been sent to the game, `-1` being Global.
+    pub objects: Vec<String>,
+    /// Size of the scripts that have been sent to the game.
+    pub bytes: usize,
+    /// Time the reload took in milliseconds, from reading the files until the game has loaded the save.
+    pub duration: u64,
+    /// Errors the game reported while loading the save.
+    pub errors: Vec<LoadError>,
+}
+
+/// An error message the game sent while loading the save, e.g. an error in `onLoad`.
+#[derive(Serialize, Clone, Debug)]
+pub struct LoadError {
+    pub guid: String,
+    pub error: String,
+}
+
+impl From<&AnswerError> for LoadError {
+    fn from(answer: &AnswerError) -> Self {
+        Self {
+            guid: answer.guid.clone(),
+            error: answer.error.clone(),
+        }
+    }
+}
+
+/// How the result of a reload is printed.
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum Output {
+    /// Print the errors the game reported while loading the save as warnings
+    #[default]
+    Text,
+    /// Print the [`ReloadReport`] as JSON to stdout
+    Json,
+}
+
+impl ReloadReport {
+    pub fn print(&self, output: Output) -> Result<()> {
+        match output {
+            Output::Text => {
+                for error in &self.errors {
+                    warn!(
+                        "{} reported an error while loading: {}",
+                        error.guid.yellow(),
+                        error.error
+                    );
+                }
+            }
+            Output::Json => println!("{}", serde_json::to_string(self)?),
+        }
+        Ok(())
+    }
+}
+
+/// An [`Event`] and the time it occurred at.
+#[derive(Serialize)]
+struct Record<'a> {
+    time: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Writes events to `path` as lines of json. `path` can also be a named pipe,
+/// e.g. `\\.\pipe\ttsst` on Windows, in which case the reading end has to be opened first.
+pub fn init(path: &Path) -> Result<()> {
+    let file = match path.exists() {
+        true => fs::OpenOptions::new().append(true).open(path)?,
+        false => File::create(path)?,
+    };
+    let _ = EVENTS.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Writes `event`, if events are enabled. Events that can't be written are dropped,
+/// so that a client that has disconnected doesn't interrupt the reload.
+pub fn emit(event: Event) {
+    let Some(file) = EVENTS.get() else {
+        return;
+    };
+    let record = Record {
+        time: chrono::Local::now().to_rfc3339(),
+        event: &event,
+    };
+    let result = serde_json::to_string(&record)
+        .map_err(std::io::Error::from)
+        .and_then(|json| writeln!(file.lock().unwrap(), "{json}"));
+    if let Err(err) = result {
+        debug!("could not write {:?}: {}", event, err);
+    }
+}
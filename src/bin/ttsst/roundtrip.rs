@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use colored::*;
+use serde_json::{Map, Value};
+use ttsst::Save;
+
+/// A difference between a save file and the save after it has been parsed and serialized again.
+#[derive(Debug)]
+enum Difference {
+    /// A field or array element that is missing after the round-trip.
+    Lost(String),
+    /// A field or array element that didn't exist in the original save.
+    Added(String),
+    /// An object whose fields are serialized in a different order.
+    Reordered(String),
+    /// A value that has been changed, e.g. a number that has lost precision.
+    Altered(String, Value, Value),
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Difference::Lost(path) => write!(f, "{} {}", "lost".red(), path.yellow()),
+            Difference::Added(path) => write!(f, "{} {}", "added".green(), path.yellow()),
+            Difference::Reordered(path) => write!(f, "{} {}", "reordered".cyan(), path.yellow()),
+            #[rustfmt::skip]
+            Difference::Altered(path, before, after) => write!(f, "{} {}: {} -> {}", "altered".red(), path.yellow(), before, after),
+        }
+    }
+}
+
+/// Parses the save at `path`, serializes it again and prints every difference to the original.
+/// Returns a summary of the result, or an error if any field was lost, added or altered.
+pub fn verify(path: &Path) -> Result<String> {
+    let json = fs::read_to_string(path)?;
+    let original: Value = serde_json::from_str(&json)?;
+    let save: Save = serde_json::from_str(&json)?;
+    let roundtrip = serde_json::to_value(&save)?;
+
+    let mut differences = Vec::new();
+    compare("$", &original, &roundtrip, &mut differences);
+    for difference in &differences {
+        println!("{}", difference);
+    }
+
+    let (reordered, changed): (Vec<_>, Vec<_>) = differences
+        .iter()
+        .partition(|difference| matches!(difference, Difference::Reordered(_)));
+    match (changed.len(), reordered.len()) {
+        (0, 0) => Ok("survives a round-trip unchanged".into()),
+        #[rustfmt::skip]
+        (0, n) => Ok(format!("survives a round-trip, but {} object(s) are reordered", n)),
+        #[rustfmt::skip]
+        (n, _) => bail!("{} field(s) did not survive a round-trip: {}", n, summary(&changed)),
+    }
+}
+
+/// Recursively compares `before` and `after`, and appends every difference to `differences`.
+fn compare(path: &str, before: &Value, after: &Value, differences: &mut Vec<Difference>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                differences.push(Difference::Lost(format!("{path}.{key}")));
+            }
+            for key in after.keys().filter(|key| !before.contains_key(*key)) {
+                differences.push(Difference::Added(format!("{path}.{key}")));
+            }
+
+            if common_keys(before, after) != common_keys(after, before) {
+                differences.push(Difference::Reordered(path.into()));
+            }
+
+            for (key, value) in before {
+                if let Some(other) = after.get(key) {
+                    compare(&format!("{path}.{key}"), value, other, differences);
+                }
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            for (index, (value, other)) in before.iter().zip(after).enumerate() {
+                compare(&format!("{path}[{index}]"), value, other, differences);
+            }
+            for index in after.len()..before.len() {
+                differences.push(Difference::Lost(format!("{path}[{index}]")));
+            }
+            for index in before.len()..after.len() {
+                differences.push(Difference::Added(format!("{path}[{index}]")));
+            }
+        }
+        (before, after) if before != after => {
+            #[rustfmt::skip]
+            differences.push(Difference::Altered(path.into(), before.clone(), after.clone()));
+        }
+        _ => (),
+    }
+}
+
+/// Returns the keys of `map` that also exist in `other`, in the order of `map`.
+fn common_keys<'a>(map: &'a Map<String, Value>, other: &Map<String, Value>) -> Vec<&'a String> {
+    map.keys().filter(|key| other.contains_key(*key)).collect()
+}
+
+/// Returns the number of lost, added and altered fields.
+fn summary(differences: &[&Difference]) -> String {
+    let count = |f: fn(&&&Difference) -> bool| differences.iter().filter(f).count();
+    format!(
+        "{} lost, {} added, {} altered",
+        count(|d| matches!(d, Difference::Lost(_))),
+        count(|d| matches!(d, Difference::Added(_))),
+        count(|d| matches!(d, Difference::Altered(..))),
+    )
+}
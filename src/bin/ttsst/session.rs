@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use log::*;
+use serde::{Deserialize, Serialize};
+use ttsst::{Save, Tag};
+
+use crate::config::{config, normalize};
+use crate::utils::{hash, long_path, write_atomic};
+use crate::{include, transform};
+
+/// Path of the file containing the state of the most recent session.
+const SESSION_FILE: &str = ".ttsst/session.json";
+
+/// Serializes updates of the session file, which are made from multiple threads while watching.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// State that is persisted across restarts, so that ttsst can resume after it has crashed.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct Session {
+    /// Save the scripts have been pushed to most recently.
+    pub save: Option<PathBuf>,
+    /// Hashes of the attached files by their tag, as they have been pushed to `save`.
+    pub pushed: BTreeMap<String, String>,
+    /// Whether a reload has been sent or queued, which hasn't been answered by the game.
+    pub pending: bool,
+    /// Stamps of the attached files by their tag, as they have been read most recently.
+    #[serde(default)]
+    pub stamps: BTreeMap<String, Stamp>,
+    /// Number of files that have been read and stamped since the session has been loaded.
+    #[serde(skip)]
+    pub reads: usize,
+}
+
+/// Identifies the content of an attached file without reading it.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Stamp {
+    /// Modification time of the file in nanoseconds since the unix epoch.
+    modified: u128,
+    /// Size of the file in bytes.
+    size: u64,
+    /// Language the placeholders of a xml file have been substituted with.
+    lang: Option<String>,
+    /// Hash of the content that has been read, after placeholders have been substituted.
+    hash: String,
+    /// Modification time and size of the files that have been included using `#include`.
+    #[serde(default)]
+    includes: BTreeMap<PathBuf, (u128, u64)>,
+    /// Hash of the `[transform]` config a lua script has been read with, see [`transform::fingerprint`].
+    #[serde(default)]
+    transform: Option<String>,
+}
+
+impl Stamp {
+    /// Returns the modification time and size of the file at `path`.
+    fn metadata<P: AsRef<Path>>(path: P) -> Option<(u128, u64)> {
+        let metadata = fs::metadata(long_path(path).ok()?).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some((modified.as_nanos(), metadata.len()))
+    }
+
+    /// Returns `true` if neither the file of `tag` nor the files it includes have changed since the stamp has been made,
+    /// and lua scripts would still be transformed the same way.
+    fn is_current(&self, tag: &Tag) -> bool {
+        self.transform.as_deref() == tag.is_lua().then(transform::fingerprint)
+            && tag.path().ok().and_then(Stamp::metadata) == Some((self.modified, self.size))
+            && self
+                .includes
+                .iter()
+                .all(|(path, metadata)| Stamp::metadata(path).as_ref() == Some(metadata))
+    }
+}
+
+impl Session {
+    /// Reads the session file. A missing or corrupt file results in an empty session.
+    pub fn load() -> Self {
+        let Ok(json) = fs::read_to_string(SESSION_FILE) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|err| {
+            warn!("ignoring the corrupt session file: {}", err);
+            Self::default()
+        })
+    }
+
+    /// Writes the session atomically, see [`write_atomic`].
+    fn store(&self) -> Result<()> {
+        let path = Path::new(SESSION_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the save the project is bound to in the config, or the save that has been pushed to most recently.
+    pub fn tracked_save() -> Result<PathBuf> {
+        match config().save.clone().or(Session::load().save) {
+            Some(save) => Ok(save),
+            None => bail!(
+                "no save is tracked, bind the project to a save using `ttsst init --save <SAVE>`"
+            ),
+        }
+    }
+
+    /// Reads the session file, applies `f` to the session and writes it again.
+    pub fn update<F: FnOnce(&mut Session)>(f: F) -> Result<()> {
+        let _lock = LOCK.lock().unwrap();
+        let mut session = Session::load();
+        f(&mut session);
+        session.store()
+    }
+
+    /// Records the scripts of the objects in `save` as pushed to the save at `path`.
+    /// If the scripts have been pushed to a different save before, the hashes of that save are discarded.
+    pub fn pushed(path: &Path, save: &Save) -> Result<()> {
+        Session::update(|session| {
+            if session.save.as_deref() != Some(path) {
+                session.save = Some(path.into());
+                session.pushed.clear();
+            }
+            for object in save.objects.iter() {
+                if let Ok(Some(tag)) = object.valid_lua() {
+                    let hash = hash(&object.lua_script);
+                    session.pushed.insert(tag.into_inner(), hash);
+                }
+                if let Ok(Some(tag)) = object.valid_xml() {
+                    let hash = hash(&object.xml_ui);
+                    session.pushed.insert(tag.into_inner(), hash);
+                }
+            }
+        })
+    }
+
+    /// Moves the session from the save at `from` to the save at `to`, which the game has saved a copy of it to.
+    /// The pushed hashes are kept, since both saves contain the same scripts.
+    pub fn moved(from: &Path, to: &Path) -> Result<()> {
+        Session::update(|session| {
+            if session.save.as_deref() == Some(from) {
+                session.save = Some(to.into());
+            }
+        })
+    }
+
+    /// Marks a reload as sent or queued, or as answered by the game.
+    pub fn set_pending(pending: bool) {
+        if let Err(err) = Session::update(|session| session.pending = pending) {
+            warn!("could not update the session file: {}", err);
+        }
+    }
+
+    /// Returns `true` if the file of `tag` is known to be unchanged since it has been pushed to the save at `path`
+    /// as `script`, without reading the file. This is the case if the file has the same modification time and size
+    /// as when it has been read last, the files it includes haven't changed either,
+    /// and the hashes of its content and of the `script` in the save match the pushed hash.
+    pub fn is_unchanged(&self, path: &Path, tag: &Tag, lang: Option<&str>, script: &str) -> bool {
+        let key = tag.clone().into_inner();
+        let (Some(stamp), Some(pushed)) = (self.stamps.get(&key), self.pushed.get(&key)) else {
+            return false;
+        };
+        self.save.as_deref() == Some(path)
+            && stamp.lang.as_deref() == lang
+            && stamp.hash == *pushed
+            // Scripts that have been edited in game differ from the pushed ones
+            && hash(script) == *pushed
+            && stamp.is_current(tag)
+    }
+
+    /// Returns the language the placeholders of the file of `tag` have been substituted with, when it has been read most recently.
+    pub fn lang(&self, tag: &Tag) -> Option<&str> {
+        self.stamps.get(&tag.clone().into_inner())?.lang.as_deref()
+    }
+
+    /// Returns `true` if the file of `tag` includes a file inside of `path`, as it has been read most recently.
+    pub fn includes<P: AsRef<Path>>(&self, tag: &Tag, path: P) -> bool {
+        let Some(stamp) = self.stamps.get(&tag.clone().into_inner()) else {
+            return false;
+        };
+        let path = normalize(path.as_ref());
+        stamp
+            .includes
+            .keys()
+            .any(|file| normalize(file).starts_with(&path))
+    }
+
+    /// Records the `content` that has been read from the file of `tag`, using `lang` to substitute placeholders,
+    /// and the files it `includes`.
+    pub fn stamp(&mut self, tag: &Tag, lang: Option<&str>, content: &str, includes: &[PathBuf]) {
+        self.reads += 1;
+        let key = tag.clone().into_inner();
+        let includes = includes
+            .iter()
+            .map(|path| Stamp::metadata(path).map(|metadata| (path.clone(), metadata)))
+            .collect::<Option<_>>();
+        match (tag.path().ok().and_then(Stamp::metadata), includes) {
+            (Some((modified, size)), Some(includes)) => {
+                let stamp = Stamp {
+                    modified,
+                    size,
+                    lang: lang.map(Into::into),
+                    hash: hash(content),
+                    includes,
+                    transform: tag.is_lua().then(|| transform::fingerprint().into()),
+                };
+                self.stamps.insert(key, stamp);
+            }
+            _ => {
+                self.stamps.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the tags of the objects in the `save`, whose files have changed since they have been pushed.
+    /// Returns an empty list if the previous session used a different save, since its hashes don't apply.
+    ///
+    /// Files are compared as they have been pushed, with their includes expanded, see [`Session::has_changed`].
+    pub fn changed_tags(&self, path: &Path, save: &Save) -> Vec<String> {
+        if self.save.as_deref() != Some(path) {
+            return Vec::new();
+        }
+
+        let tags = save.objects.iter().flat_map(|object| {
+            let lua = object.valid_lua().ok().flatten();
+            let xml = object.valid_xml().ok().flatten();
+            lua.into_iter().chain(xml)
+        });
+        tags.unique_by(|tag| tag.clone().into_inner())
+            .filter(|tag| self.has_changed(tag))
+            .map(Tag::into_inner)
+            .collect()
+    }
+
+    /// Returns `true` if the content of the file of `tag` differs from the pushed one.
+    /// The content that has been read most recently is used if the file hasn't changed since then,
+    /// which includes substituted placeholders. Otherwise the file is read again.
+    fn has_changed(&self, tag: &Tag) -> bool {
+        let key = tag.clone().into_inner();
+        let pushed = self.pushed.get(&key);
+        if let Some(stamp) = self.stamps.get(&key).filter(|stamp| stamp.is_current(tag)) {
+            return pushed != Some(&stamp.hash);
+        }
+        let content = tag
+            .path()
+            .map_err(Into::into)
+            .and_then(|path| match tag.is_lua() {
+                true => transform::read_lua(path),
+                false => include::read_xml(path),
+            });
+        match content {
+            Ok(content) => pushed != Some(&hash(&content.script)),
+            Err(_) => false,
+        }
+    }
+}
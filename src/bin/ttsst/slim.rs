@@ -0,0 +1,250 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use serde_json::Value;
+use ttsst::{Object, Prompter, Save};
+
+use crate::app::SaveFile;
+use crate::lua;
+use crate::session::Session;
+
+/// Matches data that is embedded as base64 instead of being referenced by an url, e.g. images in xml ui.
+const EMBEDDED_DATA: &str = r"data:[\w/+.-]+;base64,[A-Za-z0-9+/=]+";
+
+/// XML UI of at least this many bytes is reported as large.
+const LARGE_XML: usize = 16 * 1024;
+
+/// Number of entries listed in every section of the report.
+const TOP: usize = 5;
+
+/// Reports what the size of the save at `path`, or of the tracked save, is made up of,
+/// and offers to remove scripts and ui that only consist of whitespace and comments.
+///
+/// Objects inside of containers, like the cards of a deck, are included.
+/// Their scripts are reported if they are duplicated, but they are kept,
+/// since every copy needs its own script once it is taken out of its container.
+pub fn run(path: Option<PathBuf>, prompter: &dyn Prompter) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => Session::tracked_save()?,
+    };
+    let mut save_file = SaveFile::read_from_path(&path)?;
+    let save = &save_file.save;
+    let total = serde_json::to_vec(save)?.len();
+    let objects = all_objects(save)?;
+    let nested = objects.len() - save.objects.len();
+
+    #[rustfmt::skip]
+    println!("{} is {} with {} objects, {} of them inside of containers", save.name.blue(), size(total).bold(), objects.len(), nested);
+
+    let scripted =
+        |f: fn(&Object) -> &str| objects.iter().filter(|o| !f(o).is_empty()).collect_vec();
+    let lua = scripted(|object| &object.lua_script);
+    let xml = scripted(|object| &object.xml_ui);
+    let lua_size = save.lua_script.len() + lua.iter().map(|o| o.lua_script.len()).sum::<usize>();
+    let xml_size = save.xml_ui.len() + xml.iter().map(|o| o.xml_ui.len()).sum::<usize>();
+    let embedded = embedded_data(save)?;
+    let embedded_size = embedded.iter().map(|(_, size)| size).sum::<usize>();
+    println!(
+        "  {:<16} {:>10} in {} objects",
+        "Lua scripts",
+        size(lua_size),
+        lua.len()
+    );
+    println!(
+        "  {:<16} {:>10} in {} objects",
+        "XML UI",
+        size(xml_size),
+        xml.len()
+    );
+    println!(
+        "  {:<16} {:>10} in {} values",
+        "Embedded data",
+        size(embedded_size),
+        embedded.len()
+    );
+
+    // Scripts that are shared by multiple objects, by the size of the copies
+    let duplicated = lua
+        .iter()
+        .into_group_map_by(|object| &object.lua_script)
+        .into_iter()
+        .filter(|(_, objects)| objects.len() > 1)
+        .map(|(script, objects)| ((objects.len() - 1) * script.len(), objects))
+        .sorted_by_key(|(copies, _)| std::cmp::Reverse(*copies))
+        .collect_vec();
+    if !duplicated.is_empty() {
+        println!("\nDuplicated scripts:");
+        for (copies, objects) in duplicated.iter().take(TOP) {
+            #[rustfmt::skip]
+            println!("  {:>10} in {} copies of the script of {}", size(*copies), objects.len() - 1, objects[0]);
+        }
+    }
+
+    if !embedded.is_empty() {
+        println!("\nEmbedded data:");
+        for (owner, bytes) in embedded
+            .iter()
+            .sorted_by_key(|(_, bytes)| std::cmp::Reverse(*bytes))
+            .take(TOP)
+        {
+            println!("  {:>10} in {}", size(*bytes), owner);
+        }
+    }
+
+    let large = xml
+        .iter()
+        .filter(|object| object.xml_ui.len() >= LARGE_XML)
+        .sorted_by_key(|object| std::cmp::Reverse(object.xml_ui.len()))
+        .collect_vec();
+    if !large.is_empty() || save.xml_ui.len() >= LARGE_XML {
+        println!("\nLarge XML UI:");
+        if save.xml_ui.len() >= LARGE_XML {
+            println!(
+                "  {:>10} in {}",
+                size(save.xml_ui.len()),
+                "Global".bright_white()
+            );
+        }
+        for object in large.iter().take(TOP) {
+            println!("  {:>10} in {}", size(object.xml_ui.len()), object);
+        }
+    }
+    println!();
+
+    let blank = objects
+        .iter()
+        .map(blank_size)
+        .filter(|bytes| *bytes > 0)
+        .collect_vec();
+    if blank.is_empty() {
+        return Ok(());
+    }
+    #[rustfmt::skip]
+    let message = format!("Remove {} script(s) and UI that only consist of whitespace and comments, saving {}?", blank.len(), size(blank.iter().sum()));
+    if prompter.confirm(&message, false)? {
+        for object in save_file.save.objects.iter_mut() {
+            remove_blank(object)?;
+        }
+        save_file.write()?;
+        #[rustfmt::skip]
+        info!("removed {} blank script(s) and UI from '{}'", blank.len(), path.to_slash_lossy().yellow());
+    }
+    Ok(())
+}
+
+/// Returns the objects of the `save`, and every object inside of them.
+fn all_objects(save: &Save) -> Result<Vec<Object>> {
+    let mut objects = Vec::new();
+    let mut queue = save.objects.iter().cloned().collect_vec();
+    while let Some(object) = queue.pop() {
+        queue.extend(object.contained_objects()?);
+        objects.push(object);
+    }
+    Ok(objects)
+}
+
+/// Returns the size of the data embedded in the save, by the object it belongs to.
+fn embedded_data(save: &Save) -> Result<Vec<(String, usize)>> {
+    let exprs = Regex::new(EMBEDDED_DATA)?;
+    let mut embedded = Vec::new();
+    let mut visit = |owner: &str, string: &str| {
+        let bytes = exprs.find_iter(string).map(|m| m.len()).sum::<usize>();
+        if bytes > 0 {
+            embedded.push((owner.to_string(), bytes));
+        }
+    };
+    walk(
+        &serde_json::to_value(save)?,
+        &save.name.blue().to_string(),
+        &mut visit,
+    );
+    Ok(embedded)
+}
+
+/// Calls `f` with every string in `value`, and the object the string belongs to.
+fn walk<F: FnMut(&str, &str)>(value: &Value, owner: &str, f: &mut F) {
+    match value {
+        Value::String(string) => f(owner, string),
+        Value::Array(values) => values.iter().for_each(|value| walk(value, owner, f)),
+        Value::Object(map) => {
+            let text = |key: &str| map.get(key).and_then(Value::as_str).unwrap_or_default();
+            let owner = match (text("GUID"), text("Nickname"), text("Name")) {
+                ("", _, _) => owner.to_string(),
+                (guid, "", name) => format!("{} ({})", guid.yellow(), name.bright_white()),
+                (guid, nickname, _) => {
+                    format!("{} ({})", guid.yellow(), nickname.bright_white().bold())
+                }
+            };
+            map.values().for_each(|value| walk(value, &owner, f));
+        }
+        _ => (),
+    }
+}
+
+/// Returns the size of the script and ui of the `object` that only consist of whitespace and comments.
+/// Scripts of objects with files attached are kept, since they are replaced on the next reload anyway.
+fn blank_size(object: &Object) -> usize {
+    let lua = match object.valid_lua() {
+        Ok(None) if !object.lua_script.is_empty() && lua::is_blank(&object.lua_script) => {
+            object.lua_script.len()
+        }
+        _ => 0,
+    };
+    let xml = match object.valid_xml() {
+        Ok(None) if !object.xml_ui.is_empty() && is_blank_xml(&object.xml_ui) => {
+            object.xml_ui.len()
+        }
+        _ => 0,
+    };
+    lua + xml
+}
+
+/// Removes the blank script and ui of `object`, and of the objects inside of it, see [`blank_size`].
+fn remove_blank(object: &mut Object) -> Result<()> {
+    let lua = &object.lua_script;
+    if !lua.is_empty() && lua::is_blank(lua) && object.valid_lua()?.is_none() {
+        object.set_lua_script("");
+    }
+    let xml = &object.xml_ui;
+    if !xml.is_empty() && is_blank_xml(xml) && object.valid_xml()?.is_none() {
+        object.set_xml_ui("");
+    }
+
+    let mut contained = object.contained_objects()?;
+    if contained.iter().any(|object| !has_no_blank(object)) {
+        for object in contained.iter_mut() {
+            remove_blank(object)?;
+        }
+        object.set_contained_objects(contained)?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if neither `object` nor the objects inside of it have a blank script or ui.
+fn has_no_blank(object: &Object) -> bool {
+    blank_size(object) == 0
+        && object
+            .contained_objects()
+            .is_ok_and(|objects| objects.iter().all(has_no_blank))
+}
+
+/// Returns `true` if `xml` only consists of whitespace and comments.
+fn is_blank_xml(xml: &str) -> bool {
+    let comments = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    comments.replace_all(xml, "").trim().is_empty()
+}
+
+/// Formats a number of bytes, e.g. `1.5 MiB`.
+fn size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::dispatch::RELOAD_TIMEOUT;
+use crate::progress::ReloadReport;
+
+static STATUS: Mutex<Option<Status>> = Mutex::new(None);
+
+/// The status line shown at the bottom of the terminal while watching.
+///
+/// The rows above it are set as the scrolling region of the terminal,
+/// so that messages, prompts and the output of commands scroll without overwriting it.
+struct Status {
+    save: String,
+    files: usize,
+    /// Number of objects whose scripts have changed in-game since the most recent push, see [`metadata::changed`](crate::metadata::changed).
+    changed: usize,
+    /// Whether a message has been received from the game.
+    connected: bool,
+    /// When the reload in flight has been sent.
+    sent: Option<Instant>,
+    /// The time and result of the most recent reload.
+    reloaded: Option<(String, Color)>,
+    /// Number of rows of the terminal the scrolling region has been set for.
+    rows: u16,
+    /// The line that has been drawn most recently, so that it's only drawn again if it changes.
+    drawn: String,
+}
+
+impl Status {
+    /// Returns the parts of the line, and their colors.
+    fn parts(&self) -> Vec<(String, Color)> {
+        let connection = match (self.sent, self.connected) {
+            (Some(sent), _) if sent.elapsed() >= RELOAD_TIMEOUT => {
+                ("no answer from the game", Color::Red)
+            }
+            (Some(_), _) => ("reloading", Color::Yellow),
+            (None, true) => ("connected", Color::Green),
+            (None, false) => ("waiting for the game", Color::Yellow),
+        };
+        let reloaded = self
+            .reloaded
+            .clone()
+            .unwrap_or(("not reloaded yet".into(), Color::White));
+        let mut parts = vec![
+            (self.save.clone(), Color::Blue),
+            (connection.0.into(), connection.1),
+            reloaded,
+            (format!("watching {} file(s)", self.files), Color::White),
+        ];
+        if self.changed > 0 {
+            #[rustfmt::skip]
+            parts.push((format!("{} object(s) changed in-game", self.changed), Color::Yellow));
+        }
+        parts
+    }
+
+    /// Draws the line, if it or the size of the terminal has changed, or if `force` is set.
+    fn draw(&mut self, force: bool) {
+        let Ok((columns, rows)) = crossterm::terminal::size() else {
+            return;
+        };
+        let line = fit(&self.parts(), columns as usize);
+        if !force && rows == self.rows && line == self.drawn {
+            return;
+        }
+
+        let mut stdout = io::stdout().lock();
+        // Setting the scrolling region moves the cursor, so its position is saved and restored
+        if rows != self.rows {
+            let _ = write!(stdout, "\x1B7\x1B[1;{}r\x1B8", rows.saturating_sub(1));
+            self.rows = rows;
+        }
+        let _ = write!(stdout, "\x1B7\x1B[{rows};1H\x1B[2K{line}\x1B8");
+        let _ = stdout.flush();
+        self.drawn = line;
+    }
+}
+
+/// Joins the `parts` of the status line, truncating them to fit into `width` columns.
+fn fit(parts: &[(String, Color)], width: usize) -> String {
+    let separator = " | ";
+    let mut line = String::new();
+    let mut remaining = width;
+    for (i, (text, color)) in parts.iter().enumerate() {
+        if i > 0 {
+            if remaining <= separator.len() {
+                break;
+            }
+            remaining -= separator.len();
+            line += &separator.dimmed().to_string();
+        }
+        let text = text.chars().take(remaining).collect::<String>();
+        remaining -= text.chars().count();
+        line += &text.color(*color).to_string();
+    }
+    line
+}
+
+/// Applies `f` to the status line and draws it, if it is shown.
+fn update<F: FnOnce(&mut Status)>(f: F) {
+    if let Some(status) = STATUS.lock().unwrap().as_mut() {
+        f(status);
+        status.draw(false);
+    }
+}
+
+/// Shows the status line at the bottom of the terminal, unless stdout isn't a terminal.
+pub fn init(save: &str, files: usize) {
+    if !io::stdout().is_terminal() {
+        return;
+    }
+    // Move the cursor off the last row, which gets reserved for the status line
+    print!("\n\x1B[1A");
+    let mut status = Status {
+        save: save.into(),
+        files,
+        changed: 0,
+        connected: false,
+        sent: None,
+        reloaded: None,
+        rows: 0,
+        drawn: String::new(),
+    };
+    status.draw(true);
+    *STATUS.lock().unwrap() = Some(status);
+}
+
+/// Removes the status line and resets the scrolling region of the terminal.
+pub fn stop() {
+    if let Some(status) = STATUS.lock().unwrap().take() {
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "\x1B7\x1B[r\x1B[{};1H\x1B[2K\x1B8", status.rows);
+        let _ = stdout.flush();
+    }
+}
+
+/// Draws the status line again, e.g. after the screen has been cleared.
+pub fn redraw() {
+    if let Some(status) = STATUS.lock().unwrap().as_mut() {
+        status.draw(true);
+    }
+}
+
+/// Draws the status line if it has changed, e.g. because a reload hasn't been answered in time,
+/// or because the terminal has been resized.
+pub fn refresh() {
+    update(|_| ());
+}
+
+/// Sets the name of the active save.
+pub fn set_save(save: &str) {
+    update(|status| status.save = save.into());
+}
+
+/// Sets the number of files that are watched.
+pub fn set_files(files: usize) {
+    update(|status| status.files = files);
+}
+
+/// Sets the number of objects whose scripts have changed in-game since the most recent push.
+pub fn set_changed(changed: usize) {
+    update(|status| status.changed = changed);
+}
+
+/// Records that a message has been received from the game.
+pub fn received() {
+    update(|status| status.connected = true);
+}
+
+/// Records that a reload has been sent to the game.
+pub fn reload_sent() {
+    update(|status| status.sent = Some(Instant::now()));
+}
+
+/// Records that the game has answered the reload in flight.
+pub fn reload_answered() {
+    update(|status| status.sent = None);
+}
+
+/// Shows the result of the most recent reload.
+pub fn reloaded(result: &Result<ReloadReport>) {
+    let time = chrono::Local::now().format("%H:%M:%S");
+    let Ok(report) = result else {
+        update(|status| status.reloaded = Some((format!("reload failed at {time}"), Color::Red)));
+        return;
+    };
+    let reloaded = match (report.changed, report.errors.len()) {
+        (false, _) => (format!("up to date at {time}"), Color::White),
+        (true, 0) => {
+            #[rustfmt::skip]
+            let text = format!("reloaded {} object(s) at {time} in {}ms", report.objects.len(), report.duration);
+            (text, Color::Green)
+        }
+        (true, errors) => {
+            #[rustfmt::skip]
+            let text = format!("reloaded {} object(s) at {time} with {errors} error(s)", report.objects.len());
+            (text, Color::Red)
+        }
+    };
+    update(|status| status.reloaded = Some(reloaded));
+}
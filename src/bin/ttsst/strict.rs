@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use ttsst::{Save, Violation};
+
+use crate::app::SaveFile;
+use crate::config::config;
+
+/// Logs `problems` that leave the save in an inconsistent state, like a lua script without a tag, as warnings.
+///
+/// If `strict` is set in the config or `--strict` is passed, they are logged as errors instead,
+/// and an error is returned, so that nothing gets written or pushed, e.g. in CI pipelines.
+pub fn report(problems: &[String]) -> Result<()> {
+    if !config().strict {
+        problems.iter().for_each(|problem| warn!("{problem}"));
+        return Ok(());
+    }
+    problems.iter().for_each(|problem| error!("{problem}"));
+    match problems.len() {
+        0 => Ok(()),
+        count => bail!("found {count} problem(s), which are errors in strict mode"),
+    }
+}
+
+/// Reports the violations of the `save` before it gets written, see [`Save::violations`].
+/// Scripts and ui that aren't attached to a file are [reported](report) as problems,
+/// tags without a script and component tags of removed files are only logged as debug messages.
+pub fn violations(save: &Save) -> Result<()> {
+    let (problems, unused): (Vec<_>, Vec<_>) = save
+        .violations()?
+        .into_iter()
+        .partition(Violation::is_inconsistent);
+    for violation in &unused {
+        debug!("{}", describe(save, violation));
+    }
+
+    let reported = report(&problems.iter().map(|v| describe(save, v)).collect_vec());
+    if problems
+        .iter()
+        .any(|v| matches!(v, Violation::ScriptWithoutTag { .. }))
+    {
+        #[rustfmt::skip]
+        warn!("If you manually removed the tag, use the detach command to remove the lua script");
+    }
+    if problems
+        .iter()
+        .any(|v| matches!(v, Violation::UiWithoutTag { .. }))
+    {
+        #[rustfmt::skip]
+        warn!("If you manually removed the tag, use the detach command to remove the xml ui");
+    }
+    reported
+}
+
+/// Reports every violation of the `save` as a problem, including tags left behind, see [`Save::violations`].
+pub fn check(save: &Save) -> Result<()> {
+    let violations = save.violations()?;
+    if violations.is_empty() {
+        info!("all tags, scripts and component tags are in sync");
+        return Ok(());
+    }
+    report(&violations.iter().map(|v| describe(save, v)).collect_vec())
+}
+
+/// Checks the save at `path` like [`check`], without loading it in-game, see [`batch::run`](crate::batch::run).
+/// Every violation is logged as a warning, and an error is returned if there are any.
+pub fn check_file(path: &Path) -> Result<String> {
+    let save = SaveFile::read_from_path(path)?.save;
+    let violations = save.violations()?;
+    for violation in &violations {
+        warn!("{}", describe(&save, violation));
+    }
+    match violations.len() {
+        0 => Ok("all tags, scripts and component tags are in sync".into()),
+        count => bail!("found {count} problem(s)"),
+    }
+}
+
+/// Describes the `violation` using the object it refers to, instead of only its GUID.
+fn describe(save: &Save, violation: &Violation) -> String {
+    let object = |guid: &str| match save.objects.find_object(guid) {
+        Ok(object) => object.to_string(),
+        Err(_) => guid.yellow().to_string(),
+    };
+    match violation {
+        #[rustfmt::skip]
+        Violation::ScriptWithoutTag { guid } => format!("{} has a lua script but no valid lua tag", object(guid)),
+        #[rustfmt::skip]
+        Violation::UiWithoutTag { guid } => format!("{} has a xml ui but no valid xml tag", object(guid)),
+        violation => violation.to_string(),
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use ttsst::{Object, Save};
+
+/// Matches global function definitions like `function name(a, b)`, but not `local function` or methods.
+const FUNCTION: &str = r"(?m)^\s*function\s+([A-Za-z_][A-Za-z0-9_]*)\s*\(([^)]*)\)";
+
+/// An object with a lua script, for which stubs are generated.
+struct Stub<'a> {
+    object: &'a Object,
+    /// Name in `PascalCase`, used for the class.
+    class: String,
+    /// Name in `UPPER_SNAKE_CASE`, used for the guid constant.
+    constant: String,
+}
+
+/// Generates EmmyLua annotations for the objects of `save` that have a lua script attached,
+/// and writes them to `path`.
+pub fn generate(save: &Save, path: &Path) -> Result<()> {
+    let mut stubs = String::new();
+    writeln!(stubs, "---@meta")?;
+    writeln!(stubs, "-- Generated by `ttsst stubs`, do not edit.")?;
+
+    // Functions of the Global script can be called from objects using `Global.call`
+    let global = functions(&save.lua_script);
+    if !global.is_empty() {
+        writeln!(stubs)?;
+        #[rustfmt::skip]
+        writeln!(stubs, "---Functions defined in the Global script, which can be called using `Global.call(name, params)`.")?;
+        writeln!(stubs, "---@alias ttsst.GlobalFunction")?;
+        for (name, _) in &global {
+            writeln!(stubs, "---| \"{name}\"")?;
+        }
+    }
+
+    let objects = attached(save);
+    for stub in &objects {
+        let object = stub.object;
+        writeln!(stubs)?;
+        writeln!(stubs, "---{} ({})", name(object), object.guid)?;
+        writeln!(stubs, "GUID_{} = \"{}\"", stub.constant, object.guid)?;
+        writeln!(stubs)?;
+        writeln!(stubs, "---@class ttsst.{}", stub.class)?;
+        for (name, params) in functions(&object.lua_script) {
+            writeln!(stubs, "---@field {name} fun({params})")?;
+        }
+    }
+
+    if !objects.is_empty() {
+        writeln!(stubs)?;
+        for stub in &objects {
+            #[rustfmt::skip]
+            writeln!(stubs, "---@overload fun(guid: \"{}\"): ttsst.{}", stub.object.guid, stub.class)?;
+        }
+        writeln!(stubs, "---@param guid string")?;
+        writeln!(stubs, "---@return any")?;
+        writeln!(stubs, "function getObjectFromGUID(guid) end")?;
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, stubs)?;
+    #[rustfmt::skip]
+    info!("generated stubs for {} object(s) in '{}'", objects.len(), path.to_slash_lossy().yellow());
+    Ok(())
+}
+
+/// Returns the objects that have a lua script attached, with unique class and constant names.
+fn attached(save: &Save) -> Vec<Stub<'_>> {
+    let mut classes = HashSet::new();
+    save.objects
+        .iter()
+        .filter(|object| matches!(object.valid_lua(), Ok(Some(_))))
+        .map(|object| {
+            let words = words(&name(object));
+            let mut class = words.iter().map(|word| capitalize(word)).join("");
+            let mut constant = words.iter().map(|word| word.to_uppercase()).join("_");
+            // Objects with the same name are distinguished by their guid
+            if class.is_empty() || !classes.insert(class.clone()) {
+                class = format!("{class}_{}", object.guid);
+                constant = format!("{constant}_{}", object.guid.to_uppercase());
+            }
+            Stub {
+                object,
+                class,
+                constant: constant.trim_start_matches('_').into(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the name and parameters of every global function defined in `script`.
+fn functions(script: &str) -> Vec<(String, String)> {
+    let exprs = Regex::new(FUNCTION).unwrap();
+    exprs
+        .captures_iter(script)
+        .map(|caps| {
+            let params = caps[2]
+                .split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(|param| match param {
+                    "..." => "...: any".to_string(),
+                    param => format!("{param}: any"),
+                })
+                .join(", ");
+            (caps[1].to_string(), params)
+        })
+        .unique_by(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Returns the nickname of `object`, the file name of its lua tag, or its name.
+pub fn name(object: &Object) -> String {
+    let file = || {
+        let path = object.valid_lua().ok()??.path().ok()?;
+        Some(path.file_stem()?.to_string_lossy().into_owned())
+    };
+    match object.nickname.is_empty() {
+        false => object.nickname.clone(),
+        true => file().unwrap_or_else(|| object.display_name().into()),
+    }
+}
+
+/// Splits `name` into words, removing every character that can't be used in an identifier.
+fn words(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,278 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Result};
+use colored::*;
+use itertools::Itertools;
+use path_slash::PathExt;
+use regex::Regex;
+
+use crate::app::read_file;
+use crate::config::{config, normalize, ExecTransformer};
+use crate::include::{self, Expanded};
+use crate::{lua, utils};
+
+/// Names of the built-in transformers.
+const BUILT_INS: &[&str] = &["bundle", "constants", "minify"];
+
+/// The file that is being transformed.
+pub struct Context<'a> {
+    /// Path of the file the script has been read from.
+    pub path: &'a Path,
+    /// Files that have been read in addition to the file, e.g. included ones,
+    /// so that the script is read again if one of them changes.
+    pub includes: Vec<PathBuf>,
+}
+
+/// A step of the pipeline that lua scripts pass through when they are read, see [`read_lua`].
+pub trait Transformer {
+    /// Returns `true` if the file at `path` gets transformed, which is the case for every file by default.
+    fn applies_to(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// Returns the transformed `script`, which is the output of the previous transformer.
+    fn transform(&self, script: String, context: &mut Context) -> Result<String>;
+
+    /// Returns `true` if the file can be restored from the transformed script, see [`irreversible`].
+    fn is_reversible(&self) -> bool {
+        false
+    }
+}
+
+/// Expands `#include` directives, see [`include::bundle`].
+struct Bundle;
+
+impl Transformer for Bundle {
+    fn transform(&self, script: String, context: &mut Context) -> Result<String> {
+        let expanded = include::bundle(&script, context.path)?;
+        context.includes.extend(expanded.includes);
+        Ok(expanded.script)
+    }
+
+    fn is_reversible(&self) -> bool {
+        true
+    }
+}
+
+/// Defines the `[transform.constants]` of the config as locals at the start of the script.
+/// They are defined on the first line, so that the line numbers of errors still match the file.
+struct Constants {
+    definition: String,
+}
+
+impl Constants {
+    fn new() -> Result<Self> {
+        let constants = &config().transform.constants;
+        let name = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+        if let Some(invalid) = constants.keys().find(|key| !name.is_match(key)) {
+            bail!(
+                "'{}' in [transform.constants] is not a valid lua name",
+                invalid.yellow()
+            );
+        }
+        let definition = match constants.is_empty() {
+            true => String::new(),
+            false => format!(
+                "local {} = {}; ",
+                constants.keys().join(", "),
+                constants
+                    .values()
+                    .map(literal)
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            ),
+        };
+        Ok(Self { definition })
+    }
+}
+
+impl Transformer for Constants {
+    fn transform(&self, script: String, _context: &mut Context) -> Result<String> {
+        Ok(format!("{}{script}", self.definition))
+    }
+}
+
+/// Returns `value` as a lua literal. Arrays become sequences, and tables become tables with string keys.
+fn literal(value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(string) => {
+            let escaped = string
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            format!("\"{escaped}\"")
+        }
+        toml::Value::Integer(integer) => integer.to_string(),
+        toml::Value::Float(float) => float.to_string(),
+        toml::Value::Boolean(boolean) => boolean.to_string(),
+        toml::Value::Datetime(datetime) => literal(&toml::Value::String(datetime.to_string()))?,
+        toml::Value::Array(values) => {
+            format!(
+                "{{{}}}",
+                values
+                    .iter()
+                    .map(literal)
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            )
+        }
+        toml::Value::Table(table) => {
+            let fields = table
+                .iter()
+                .map(|(key, value)| {
+                    Ok(format!(
+                        "[{}] = {}",
+                        literal(&key.as_str().into())?,
+                        literal(value)?
+                    ))
+                })
+                .collect::<Result<Vec<String>>>()?;
+            format!("{{{}}}", fields.join(", "))
+        }
+    })
+}
+
+/// Removes comments and redundant whitespace, see [`lua::minify`].
+/// This includes the markers of included files, so it isn't reversible even after [`Bundle`].
+struct Minify;
+
+impl Transformer for Minify {
+    fn transform(&self, script: String, _context: &mut Context) -> Result<String> {
+        Ok(lua::minify(&script))
+    }
+}
+
+/// Runs an external command from the `[transform.exec]` table of the config, e.g. a compiler of another language.
+struct Exec<'a> {
+    name: &'a str,
+    exec: &'a ExecTransformer,
+    files: Vec<Regex>,
+}
+
+impl<'a> Exec<'a> {
+    fn new(name: &'a str, exec: &'a ExecTransformer) -> Result<Self> {
+        let files = exec
+            .files
+            .iter()
+            .map(|pattern| utils::glob(pattern))
+            .try_collect()?;
+        Ok(Self { name, exec, files })
+    }
+}
+
+impl Transformer for Exec<'_> {
+    fn applies_to(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        let path = path.to_slash_lossy();
+        self.files.is_empty() || self.files.iter().any(|exprs| exprs.is_match(&path))
+    }
+
+    fn transform(&self, script: String, context: &mut Context) -> Result<String> {
+        let (shell, flag) = match cfg!(windows) {
+            true => ("cmd", "/C"),
+            false => ("sh", "-c"),
+        };
+        let file = format!("\"{}\"", normalize(context.path).display());
+        let command = self.exec.command.replace("{file}", &file);
+
+        let mut child = Command::new(shell)
+            .args([flag, &command])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| anyhow!("could not run '{}': {}", command.yellow(), err))?;
+        // Write on a separate thread, so that a command which doesn't read its input can't block
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || stdin.write_all(script.as_bytes()));
+        let output = child.wait_with_output()?;
+        // A command that exits without reading its input closes the pipe, which is not an error
+        let _ = writer.join();
+
+        if !output.status.success() {
+            #[rustfmt::skip]
+            bail!("the '{}' transformer failed on '{}' with {}: {}", self.name.yellow(), context.path.to_slash_lossy().yellow(), output.status, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+/// The transformers of the pipeline, which are only built once, since the config doesn't change.
+static PIPELINE: OnceLock<Vec<Box<dyn Transformer + Send + Sync>>> = OnceLock::new();
+
+/// Returns the transformers of the `pipeline` in the `[transform]` table of the config.
+fn pipeline() -> Result<&'static [Box<dyn Transformer + Send + Sync>]> {
+    if let Some(pipeline) = PIPELINE.get() {
+        return Ok(pipeline);
+    }
+    let transform = &config().transform;
+    let pipeline = transform
+        .pipeline
+        .iter()
+        .map(|name| -> Result<Box<dyn Transformer + Send + Sync>> {
+            Ok(match (name.as_str(), transform.exec.get(name)) {
+                (_, Some(exec)) => Box::new(Exec::new(name, exec)?),
+                ("bundle", None) => Box::new(Bundle),
+                ("constants", None) => Box::new(Constants::new()?),
+                ("minify", None) => Box::new(Minify),
+                #[rustfmt::skip]
+                _ => bail!("'{}' is neither a built-in transformer ({}) nor set in [transform.exec]", name.yellow(), BUILT_INS.join(", ")),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(PIPELINE.get_or_init(|| pipeline))
+}
+
+/// Reads the lua script at `path`, and passes it through the transformers of the `[transform]` pipeline of the config,
+/// which only expands `#include` directives by default.
+///
+/// Transformers that only apply to some files, like external commands with `files` patterns, skip the others.
+pub fn read_lua<P: AsRef<Path>>(path: P) -> Result<Expanded> {
+    let path = path.as_ref();
+    let mut context = Context {
+        path,
+        includes: Vec::new(),
+    };
+    let mut script = read_file(path)?;
+    for transformer in pipeline()? {
+        if transformer.applies_to(path) {
+            script = transformer.transform(script, &mut context)?;
+        }
+    }
+    Ok(Expanded {
+        script,
+        includes: context.includes,
+    })
+}
+
+/// Returns a hash of the `[transform]` table of the config, so that scripts that have been read with a different pipeline,
+/// different constants or different commands are read again, see [`Stamp`](crate::session::Stamp).
+///
+/// Only the command of an exec transformer is part of the hash. The files the command reads and the program it runs
+/// aren't tracked, so scripts have to be reloaded using `reload-targets` or by touching the file once they change.
+pub fn fingerprint() -> &'static str {
+    static FINGERPRINT: OnceLock<String> = OnceLock::new();
+    FINGERPRINT.get_or_init(|| {
+        let transform = serde_json::to_string(&config().transform).unwrap_or_default();
+        utils::hash(&transform)
+    })
+}
+
+/// Returns the name of the first transformer of the pipeline that changes the file at `path` in a way that can't be reverted,
+/// so that writing a script read from the game into the file would replace its source, e.g. with compiled or minified code.
+/// Xml files are never transformed.
+pub fn irreversible(path: &Path) -> Result<Option<&'static str>> {
+    if !ttsst::tags::extensions().is_lua(path) {
+        return Ok(None);
+    }
+    let names = &config().transform.pipeline;
+    Ok(names
+        .iter()
+        .zip(pipeline()?)
+        .find(|(_, transformer)| transformer.applies_to(path) && !transformer.is_reversible())
+        .map(|(name, _)| name.as_str()))
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use path_slash::PathExt;
+use regex::Regex;
+use ttsst::tags::extensions;
+use ttsst::Save;
+
+use crate::app::{find_files, is_named, read_file};
+use crate::config::{config, normalize};
+use crate::include::{self, INCLUDE, XML_INCLUDE};
+
+/// Matches `require("name")` and `require "name"`.
+const REQUIRE: &str = r#"\brequire\s*\(?\s*["']([^"']+)["']"#;
+
+/// Lists the lua and xml files of the project that aren't referenced by a tag of the `save`, aren't a Global file,
+/// and aren't included or required by another referenced file.
+/// Files that are included by a referenced xml file using `<Include src="path"/>` are referenced as well.
+pub fn report(save: &Save) -> Result<()> {
+    let extensions = extensions();
+    let files = find_files(Path::new("."), &|path| {
+        extensions.is_lua(path) || extensions.is_xml(path)
+    })?;
+
+    let mut queue = roots(save, &files)?;
+    let mut referenced = BTreeSet::new();
+    while let Some(path) = queue.pop() {
+        if !referenced.insert(normalize(&path)) {
+            continue;
+        }
+        // Files that don't exist are reported by `reload` instead
+        if let Ok(script) = read_file(&path) {
+            queue.extend(references(&script, &path));
+        }
+    }
+
+    let unused = files
+        .iter()
+        .filter(|file| !referenced.contains(&normalize(file)))
+        .collect_vec();
+    for file in &unused {
+        println!("{}", normalize(file).to_slash_lossy());
+    }
+    match unused.len() {
+        0 => info!("all {} file(s) are referenced", files.len()),
+        count => info!("found {} unused file(s)", count.to_string().yellow()),
+    }
+    Ok(())
+}
+
+/// Returns the files that are referenced by the tags of the objects in `save`, and the Global files.
+/// If no Global files are attached using tags of the save or set in the config,
+/// every file in `files` named like `Global.<Ext>` is a Global file.
+pub fn roots(save: &Save, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut roots = save
+        .objects
+        .iter()
+        .flat_map(|object| object.tags.iter())
+        .filter_map(|tag| tag.path().ok())
+        .collect_vec();
+
+    let extensions = extensions();
+    let global = |exts: &Vec<String>| exts.iter().map(|ext| format!("Global.{ext}")).collect_vec();
+    let pinned = &config().global;
+    for (tag, pinned, names) in [
+        (save.global_lua()?, &pinned.lua, global(&extensions.lua)),
+        (save.global_xml()?, &pinned.xml, global(&extensions.xml)),
+    ] {
+        match (tag, pinned) {
+            (Some(tag), _) => roots.push(tag.path()?),
+            (None, Some(path)) => roots.push(path.clone()),
+            (None, None) => {
+                roots.extend(files.iter().filter(|file| is_named(file, &names)).cloned())
+            }
+        }
+    }
+    Ok(roots)
+}
+
+/// Returns the files that the lua `script` at `path` includes or requires, or that the xml ui at `path` includes.
+/// Required modules can be separated by dots, e.g. `require("util.math")` refers to `util/math.lua`.
+pub fn references(script: &str, path: &Path) -> Vec<PathBuf> {
+    if extensions().is_xml(path) {
+        let includes = Regex::new(XML_INCLUDE).unwrap();
+        return includes
+            .captures_iter(script)
+            .filter_map(|captures| include::resolve_xml(&captures["src"], path).ok())
+            .collect();
+    }
+
+    let includes = Regex::new(&format!("(?m){INCLUDE}")).unwrap();
+    let requires = Regex::new(REQUIRE).unwrap();
+
+    let included = includes.captures_iter(script).filter_map(|captures| {
+        let name = captures.name("wrapped").or(captures.name("plain"))?;
+        include::resolve(name.as_str(), path).ok()
+    });
+    let required = requires.captures_iter(script).filter_map(|captures| {
+        let name = &captures[1];
+        include::resolve(name, path)
+            .or_else(|_| include::resolve(&name.replace('.', "/"), path))
+            .ok()
+    });
+    included.chain(required).collect()
+}
@@ -1,7 +1,18 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use itertools::Itertools;
+use regex::Regex;
+use similar::{ChangeTag, TextDiff};
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 
+use crate::config::normalize;
+
+/// Prefix of verbatim paths on Windows, which are not limited to 260 characters.
+const VERBATIM: &str = r"\\?\";
+
+/// Paths with at least this many characters have to be verbatim on Windows.
+const MAX_PATH: usize = 260;
+
 pub trait Reduce<P> {
     /// Filters and deduplicates the collection of paths, returning a new collection.
     ///
@@ -32,7 +43,108 @@ pub trait StripCurrentDir {
 
 impl StripCurrentDir for PathBuf {
     fn strip_current_dir(&self) -> Result<PathBuf> {
-        let path = self.strip_prefix(std::env::current_dir()?)?;
+        // Paths of file events can be verbatim, even though the current directory isn't
+        let current_dir = strip_verbatim(&std::env::current_dir()?);
+        let path = strip_verbatim(self);
+        let path = path.strip_prefix(current_dir)?;
         Ok(PathBuf::from(".\\").join(path))
     }
 }
+
+/// Removes the verbatim prefix of `path`, e.g. `\\?\C:\foo` becomes `C:\foo`,
+/// and `\\?\UNC\server\share` becomes `\\server\share`.
+pub fn strip_verbatim(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) if s.starts_with(r"\\?\UNC\") => PathBuf::from(format!(r"\\{}", &s[8..])),
+        Some(s) if s.starts_with(VERBATIM) => PathBuf::from(&s[VERBATIM.len()..]),
+        _ => path.into(),
+    }
+}
+
+/// Returns a path that can be used to access files with paths longer than 260 characters on Windows,
+/// by converting it into an absolute verbatim path. Other paths are returned unchanged.
+pub fn long_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path = path.as_ref();
+    if !cfg!(windows) {
+        return Ok(path.into());
+    }
+
+    // Verbatim paths are used as is, so `.` and `..` have to be removed beforehand
+    let absolute = normalize(&std::env::current_dir()?.join(path));
+    match absolute.to_str() {
+        Some(s) if s.len() >= MAX_PATH && !s.starts_with(VERBATIM) => {
+            Ok(PathBuf::from(format!("{VERBATIM}{}", s.replace('/', "\\"))))
+        }
+        _ => Ok(path.into()),
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path` first, and then replaces `path` with it,
+/// so that a crash or an interruption while writing can't leave a truncated file behind.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Converts a `pattern` with `*` and `?` wildcards into a regex that matches the whole string.
+pub fn glob(pattern: &str) -> Result<Regex> {
+    let exprs = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Ok(Regex::new(&format!("^{}$", exprs))?)
+}
+
+/// Opens `path` in the editor set by `$VISUAL` or `$EDITOR`, and waits until it has been closed.
+/// Falls back to `notepad` on Windows and `vi` on other platforms.
+pub fn edit(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| match cfg!(windows) {
+            true => "notepad".into(),
+            false => "vi".into(),
+        });
+    let (shell, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+
+    // The editor can contain arguments, e.g. `code --wait`
+    let command = format!("{editor} \"{}\"", path.display());
+    let status = std::process::Command::new(shell)
+        .args([flag, &command])
+        .status()?;
+    if !status.success() {
+        bail!("'{}' failed with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// Returns a hash of `content` as a hex string, using FNV-1a,
+/// which unlike the hasher of the standard library is stable across versions, so that it can be persisted.
+pub fn hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Removes the color codes inserted by [`colored`] from `text`.
+pub fn strip_colors(text: &str) -> Cow<'_, str> {
+    let exprs = Regex::new(r"\x1B\[[0-9;]*m").unwrap();
+    exprs.replace_all(text, "")
+}
+
+/// Returns the number of lines that have been inserted and deleted from `old` to `new`.
+pub fn line_count(old: &str, new: &str) -> (usize, usize) {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .fold((0, 0), |acc, change| match change.tag() {
+            ChangeTag::Insert => (acc.0 + 1, acc.1),
+            ChangeTag::Delete => (acc.0, acc.1 + 1),
+            ChangeTag::Equal => acc,
+        })
+}
@@ -8,6 +8,20 @@ pub enum Error {
     SerdeError(#[from] serde_json::Error),
     #[error(transparent)]
     StripPrefixError(#[from] std::path::StripPrefixError),
+    #[error(transparent)]
+    TomlError(#[from] toml::de::Error),
+    #[error(transparent)]
+    TomlEditError(#[from] toml_edit::TomlError),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+    #[error(transparent)]
+    PromptError(#[from] inquire::InquireError),
+    #[error("port {0} is already in use")]
+    PortInUse(u16),
+    #[error("Tabletop Simulator didn't answer within {0:?}, make sure that a save is loaded")]
+    Timeout(std::time::Duration),
+    #[error("the operation has been cancelled")]
+    Cancelled,
     #[error("{0}")]
     Msg(String),
 }
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use colored::*;
+use itertools::Itertools;
+use log::*;
+use regex::{Captures, Regex};
+
+use crate::error::Result;
+
+/// Matches `%key%` placeholders inside of xml ui files.
+const PLACEHOLDER: &str = r"%([A-Za-z0-9_.\-]+)%";
+
+/// Returns the [`PLACEHOLDER`] regex, which is only compiled once.
+fn placeholders() -> &'static Regex {
+    static EXPRS: OnceLock<Regex> = OnceLock::new();
+    EXPRS.get_or_init(|| Regex::new(PLACEHOLDER).unwrap())
+}
+
+/// A set of translated strings for a single language.
+/// Translations can be read from `<lang>.toml` or `<lang>.csv` files.
+#[derive(Default, Clone, Debug)]
+pub struct Translations {
+    pub lang: String,
+    /// File the translations have been read from.
+    pub path: PathBuf,
+    strings: BTreeMap<String, String>,
+}
+
+impl Translations {
+    /// Reads the translations for `lang` from the `dir` directory.
+    /// Looks for `<dir>/<lang>.toml` first, and then for `<dir>/<lang>.csv`.
+    pub fn load<P: AsRef<Path>>(dir: P, lang: &str) -> Result<Self> {
+        let toml_path = dir.as_ref().join(format!("{lang}.toml"));
+        let csv_path = dir.as_ref().join(format!("{lang}.csv"));
+
+        let (strings, path) = match (toml_path.exists(), csv_path.exists()) {
+            (true, _) => (read_toml(&toml_path)?, toml_path),
+            (false, true) => (read_csv(&csv_path)?, csv_path),
+            (false, false) => {
+                #[rustfmt::skip]
+                return Err(format!("no translations found for '{}' in '{}'", lang.yellow(), dir.as_ref().display()).into());
+            }
+        };
+
+        debug!("loaded {} translations for '{}'", strings.len(), lang);
+        Ok(Self {
+            lang: lang.into(),
+            path,
+            strings,
+        })
+    }
+
+    /// Returns the translated string for `key`.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.strings.get(key)
+    }
+
+    /// Replaces every `%key%` placeholder in `xml` with its translation.
+    /// Placeholders without a translation are left untouched.
+    pub fn substitute(&self, xml: &str) -> String {
+        placeholders()
+            .replace_all(xml, |caps: &Captures| match self.get(&caps[1]) {
+                Some(value) => value.clone(),
+                None => {
+                    warn!(
+                        "missing '{}' translation for {}",
+                        self.lang,
+                        caps[1].yellow()
+                    );
+                    caps[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    /// Adds every key of `keys` that has no translation yet to the translation file of `lang`,
+    /// using an empty string as the value. Creates `<dir>/<lang>.toml` if no file exists.
+    /// Keys are added to the top level of toml files, before any tables, and the rest of the file is kept as is.
+    /// Returns the keys that were added.
+    pub fn append_missing<P: AsRef<Path>>(
+        dir: P,
+        lang: &str,
+        keys: &[String],
+    ) -> Result<Vec<String>> {
+        let toml_path = dir.as_ref().join(format!("{lang}.toml"));
+        let csv_path = dir.as_ref().join(format!("{lang}.csv"));
+        let existing = match toml_path.exists() || csv_path.exists() {
+            true => Self::load(&dir, lang)?,
+            false => Self::default(),
+        };
+
+        let missing = keys
+            .iter()
+            .filter(|key| existing.get(key).is_none())
+            .cloned()
+            .collect_vec();
+
+        fs::create_dir_all(&dir)?;
+        if !toml_path.exists() && csv_path.exists() {
+            let lines = missing.iter().map(|key| format!("{key},\n")).join("");
+            let mut file = fs::OpenOptions::new().append(true).open(&csv_path)?;
+            file.write_all(lines.as_bytes())?;
+            return Ok(missing);
+        }
+
+        // Keys appended to the end of the file would belong to its last table
+        let mut document = match toml_path.exists() {
+            true => fs::read_to_string(&toml_path)?.parse::<toml_edit::DocumentMut>()?,
+            false => toml_edit::DocumentMut::new(),
+        };
+        for key in &missing {
+            document.insert(key, toml_edit::value(""));
+        }
+        fs::write(&toml_path, document.to_string())?;
+        Ok(missing)
+    }
+}
+
+/// Returns all unique placeholder keys used in `xml`, in the order they first appear.
+pub fn extract_keys(xml: &str) -> Vec<String> {
+    placeholders()
+        .captures_iter(xml)
+        .map(|caps| caps[1].to_string())
+        .unique()
+        .collect()
+}
+
+/// Reads a toml file, flattening nested tables into dotted keys.
+/// `[menu] start = "Start"` becomes `menu.start`.
+fn read_toml(path: &Path) -> Result<BTreeMap<String, String>> {
+    fn flatten(prefix: &str, table: toml::Table, strings: &mut BTreeMap<String, String>) {
+        for (key, value) in table {
+            let key = match prefix.is_empty() {
+                true => key,
+                false => format!("{prefix}.{key}"),
+            };
+            match value {
+                toml::Value::Table(table) => flatten(&key, table, strings),
+                toml::Value::String(value) => {
+                    strings.insert(key, value);
+                }
+                value => {
+                    strings.insert(key, value.to_string());
+                }
+            }
+        }
+    }
+
+    let table: toml::Table = toml::from_str(&fs::read_to_string(path)?)?;
+    let mut strings = BTreeMap::new();
+    flatten("", table, &mut strings);
+    Ok(strings)
+}
+
+/// Reads a csv file with `key,value` rows. A `key,value` header row is optional.
+fn read_csv(path: &Path) -> Result<BTreeMap<String, String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut strings = BTreeMap::new();
+    for record in reader.records() {
+        let record = record?;
+        match (record.get(0), record.get(1)) {
+            (Some("key"), Some("value")) => continue,
+            (Some(key), Some(value)) => {
+                strings.insert(key.trim().into(), value.into());
+            }
+            _ => continue,
+        }
+    }
+    Ok(strings)
+}
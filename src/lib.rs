@@ -1,8 +1,14 @@
+pub mod api;
 pub mod error;
+pub mod l10n;
 pub mod objects;
+pub mod prompt;
 pub mod save;
 pub mod tags;
+pub mod testing;
 
-pub use crate::objects::{Object, Objects};
-pub use crate::save::Save;
+pub use crate::l10n::Translations;
+pub use crate::objects::{ColorDiffuse, Object, Objects, Transform};
+pub use crate::prompt::Prompter;
+pub use crate::save::{Save, Violation};
 pub use crate::tags::{Tag, Tags};
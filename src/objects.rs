@@ -1,15 +1,41 @@
-use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
 
 use colored::*;
 use derive_more::{Deref, DerefMut, Display, IntoIterator};
 use itertools::Itertools;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::error::Result;
 use crate::tags::{Tag, Tags};
 
-#[derive(Deserialize, Serialize, Clone, Debug, Deref, DerefMut, Display, IntoIterator)]
+/// Names of the objects that are hidden in selection prompts by default,
+/// like `HandTrigger`, `FogOfWar` and `FogOfWarTrigger`.
+pub const HIDDEN: &[&str] = &["HandTrigger", "FogOfWar", "FogOfWarTrigger"];
+
+/// Object name of scripting zones.
+pub const SCRIPTING_ZONE: &str = "ScriptingTrigger";
+
+/// Object names of all zones.
+///
+/// For a list of object names see:
+/// https://kb.tabletopsimulator.com/custom-content/save-file-format/#object-name-list
+pub const ZONES: &[&str] = &[
+    SCRIPTING_ZONE,
+    "FogOfWar",
+    "FogOfWarTrigger",
+    "HandTrigger",
+    "LayoutZone",
+    "RandomizeTrigger",
+];
+
+#[derive(
+    Deserialize, Serialize, JsonSchema, Clone, Debug, Deref, DerefMut, Display, IntoIterator,
+)]
 #[display(fmt = "{}", "self.0.iter().format(\", \")")]
 pub struct Objects(Vec<Object>);
 
@@ -64,14 +90,35 @@ impl Objects {
             .collect() // `Vec<Result<T, E>>` gets turned into `Result<Vec<T>, E>`
     }
 
-    /// Filter out `HandTrigger`, `FogOfWar` and `FogOfWarTrigger` objects.
+    /// Filter out the objects named like one of the default [`HIDDEN`] objects.
+    pub fn filter_hidden(self) -> Self {
+        self.filter_names(HIDDEN)
+    }
+
+    /// Filter out the objects whose name is one of `names`, e.g. `3DText` or `ScriptingTrigger`.
     ///
     /// For a list of object names see:
     /// https://kb.tabletopsimulator.com/custom-content/save-file-format/#object-name-list
-    pub fn filter_hidden(self) -> Self {
-        const HIDDEN: &[&str] = &["HandTrigger", "FogOfWar", "FogOfWarTrigger"];
+    pub fn filter_names<T: AsRef<str>>(self, names: &[T]) -> Self {
         self.into_iter()
-            .filter(|object| !HIDDEN.contains(&object.name.as_str()))
+            .filter(|object| !names.iter().any(|name| name.as_ref() == object.name))
+            .collect()
+    }
+
+    /// Returns all zones, like scripting zones, hand zones and randomize zones.
+    /// See [`Object::is_zone`].
+    pub fn zones(&self) -> Self {
+        self.iter()
+            .filter(|object| object.is_zone())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns all scripting zones. Unlike other zones, scripting zones can have a lua script attached.
+    pub fn scripting_zones(&self) -> Self {
+        self.iter()
+            .filter(|object| object.name == SCRIPTING_ZONE)
+            .cloned()
             .collect()
     }
 
@@ -80,27 +127,246 @@ impl Objects {
     pub fn to_values(&self) -> Vec<Value> {
         self.iter().map(|object| object.to_value()).collect()
     }
+
+    /// Construct a vec of [`serde_json::Value`] from the objects whose scripts have changed.
+    /// See [`Object::is_dirty`].
+    pub fn to_dirty_values(&self) -> Vec<Value> {
+        self.iter()
+            .filter(|object| object.is_dirty())
+            .map(|object| object.to_value())
+            .collect()
+    }
+
+    /// Marks all objects as unchanged.
+    pub fn clear_dirty(&mut self) {
+        self.iter_mut().for_each(|object| object.dirty = false);
+    }
+
+    /// Sorts the objects by their GUID, since the order in which the game saves them changes between saves.
+    pub fn sort_by_guid(&mut self) {
+        self.sort_by(|a, b| a.guid.cmp(&b.guid));
+    }
+
+    /// Rounds the floating point numbers of every object, including the ones of objects inside of it,
+    /// to `decimals` decimal places. See [`round_floats`].
+    pub fn round_floats(&mut self, decimals: u32) {
+        for object in self.iter_mut() {
+            if let Some(transform) = &mut object.transform {
+                transform.round(decimals);
+            }
+            if let Some(color) = &mut object.color_diffuse {
+                for component in [&mut color.r, &mut color.g, &mut color.b] {
+                    *component = round(*component, decimals);
+                }
+                if let Some(a) = &mut color.a {
+                    *a = round(*a, decimals);
+                }
+            }
+            object
+                .extra
+                .values_mut()
+                .for_each(|value| round_floats(value, decimals));
+        }
+    }
+
+    /// Returns the GUIDs of all objects, including the ones of objects inside of containers,
+    /// like the cards of a deck, and of the other states of an object.
+    pub fn guids(&self) -> HashSet<String> {
+        let mut guids = HashSet::new();
+        for object in self.iter() {
+            guids.insert(object.guid.clone());
+            for value in object.extra.values() {
+                nested_guids(value, &mut |guid| {
+                    guids.insert(guid.to_string());
+                });
+            }
+        }
+        guids
+    }
+}
+
+/// Calls `f` with the GUID of every object nested in `value`.
+fn nested_guids<F: FnMut(&str)>(value: &Value, f: &mut F) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(guid)) = map.get("GUID") {
+                f(guid);
+            }
+            map.values().for_each(|value| nested_guids(value, f));
+        }
+        Value::Array(values) => values.iter().for_each(|value| nested_guids(value, f)),
+        _ => (),
+    }
+}
+
+/// Replaces the GUID of every object nested in `value` with the one returned by `f`.
+fn replace_nested_guids<F: FnMut() -> String>(value: &mut Value, f: &mut F) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(guid)) = map.get_mut("GUID") {
+                *guid = f();
+            }
+            map.values_mut()
+                .for_each(|value| replace_nested_guids(value, f));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| replace_nested_guids(value, f)),
+        _ => (),
+    }
+}
+
+/// Rounds `x` to `decimals` decimal places.
+fn round(x: f64, decimals: u32) -> f64 {
+    let factor = 10_f64.powi(decimals as i32);
+    let rounded = (x * factor).round() / factor;
+    // Avoid writing `-0.0` for small negative numbers
+    match rounded == 0.0 {
+        true => 0.0,
+        false => rounded,
+    }
+}
+
+/// Rounds every floating point number in `value` to `decimals` decimal places.
+/// Integers are kept as they are, and numbers in strings, like the `LuaScriptState`, aren't changed.
+pub fn round_floats(value: &mut Value, decimals: u32) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let rounded = number.as_f64().map(|x| round(x, decimals));
+            if let Some(rounded) = rounded.and_then(serde_json::Number::from_f64) {
+                *number = rounded;
+            }
+        }
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| round_floats(value, decimals)),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| round_floats(value, decimals)),
+        _ => (),
+    }
+}
+
+/// Returns a random GUID that isn't included in `used`, and adds it to `used`.
+pub fn new_guid(used: &mut HashSet<String>) -> String {
+    loop {
+        // Every `RandomState` is seeded differently, so hashing nothing results in a random number
+        let random = RandomState::new().build_hasher().finish();
+        let guid = format!("{:06x}", random & 0xffffff);
+        if used.insert(guid.clone()) {
+            return guid;
+        }
+    }
+}
+
+/// Position, rotation and scale of an object.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    #[serde(rename = "posX")]
+    pub pos_x: f64,
+    #[serde(rename = "posY")]
+    pub pos_y: f64,
+    #[serde(rename = "posZ")]
+    pub pos_z: f64,
+    #[serde(rename = "rotX")]
+    pub rot_x: f64,
+    #[serde(rename = "rotY")]
+    pub rot_y: f64,
+    #[serde(rename = "rotZ")]
+    pub rot_z: f64,
+    #[serde(rename = "scaleX")]
+    pub scale_x: f64,
+    #[serde(rename = "scaleY")]
+    pub scale_y: f64,
+    #[serde(rename = "scaleZ")]
+    pub scale_z: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            pos_x: 0.0,
+            pos_y: 0.0,
+            pos_z: 0.0,
+            rot_x: 0.0,
+            rot_y: 0.0,
+            rot_z: 0.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            scale_z: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    /// Rounds the position, rotation and scale to `decimals` decimal places.
+    pub fn round(&mut self, decimals: u32) {
+        for x in [
+            &mut self.pos_x,
+            &mut self.pos_y,
+            &mut self.pos_z,
+            &mut self.rot_x,
+            &mut self.rot_y,
+            &mut self.rot_z,
+            &mut self.scale_x,
+            &mut self.scale_y,
+            &mut self.scale_z,
+        ] {
+            *x = round(*x, decimals);
+        }
+    }
+}
+
+/// Tint of an object. Each component ranges from `0.0` to `1.0`.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+pub struct ColorDiffuse {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub a: Option<f64>,
 }
 
 /// An object loaded in the current save or savestate.
-#[derive(Deserialize, Serialize, Clone, Debug)]
+///
+/// The lua script and xml ui are reference counted, so that cloning an object,
+/// or attaching the same file to multiple objects, doesn't copy the scripts.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct Object {
     #[serde(rename = "GUID")]
     pub guid: String,
     #[serde(rename = "LuaScript", default)]
-    pub lua_script: String,
+    pub lua_script: Arc<str>,
     #[serde(rename = "XmlUI", default)]
-    pub xml_ui: String,
+    pub xml_ui: Arc<str>,
     #[serde(rename = "Name", default)]
     pub name: String,
     #[serde(rename = "Nickname", default)]
     pub nickname: String,
     #[serde(rename = "Tags", default)]
     pub tags: Tags,
+    #[serde(rename = "Transform", default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+    #[serde(
+        rename = "ColorDiffuse",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color_diffuse: Option<ColorDiffuse>,
+    #[serde(rename = "Locked", default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+    #[serde(rename = "Grid", default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<bool>,
+    #[serde(rename = "Snap", default, skip_serializing_if = "Option::is_none")]
+    pub snap: Option<bool>,
 
     // Other fields that are not relevant
     #[serde(flatten)]
     extra: HashMap<String, Value>,
+
+    // Whether the lua script or xml ui has changed since the object was loaded
+    #[serde(skip)]
+    dirty: bool,
 }
 
 impl std::fmt::Display for Object {
@@ -138,6 +404,136 @@ impl Object {
         })
     }
 
+    /// Replaces the lua script of the object and marks it as changed, if it differs.
+    /// Returns `true` if the script has changed.
+    pub fn set_lua_script<T: Into<Arc<str>>>(&mut self, script: T) -> bool {
+        let script = script.into();
+        let changed = *self.lua_script != *script;
+        if changed {
+            self.lua_script = script;
+            self.dirty = true;
+        }
+        changed
+    }
+
+    /// Replaces the xml ui of the object and marks it as changed, if it differs.
+    /// Returns `true` if the ui has changed.
+    pub fn set_xml_ui<T: Into<Arc<str>>>(&mut self, ui: T) -> bool {
+        let ui = ui.into();
+        let changed = *self.xml_ui != *ui;
+        if changed {
+            self.xml_ui = ui;
+            self.dirty = true;
+        }
+        changed
+    }
+
+    /// Returns a copy of the object with a new GUID that isn't included in `used`.
+    /// Objects inside of it, like the cards of a deck, get new GUIDs as well.
+    /// The copy is marked as changed, see [`Object::is_dirty`].
+    pub fn duplicate(&self, used: &mut HashSet<String>) -> Object {
+        let mut object = self.clone();
+        object.guid = new_guid(used);
+        for value in object.extra.values_mut() {
+            replace_nested_guids(value, &mut || new_guid(used));
+        }
+        object.dirty = true;
+        object
+    }
+
+    /// Returns the objects inside of the object, like the cards of a deck or the contents of a bag.
+    pub fn contained_objects(&self) -> Result<Vec<Object>> {
+        match self.extra.get("ContainedObjects") {
+            Some(value) => Ok(serde_json::from_value(value.clone())?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Replaces the objects inside of the object, see [`Object::contained_objects`].
+    pub fn set_contained_objects(&mut self, objects: Vec<Object>) -> Result<()> {
+        let value = serde_json::to_value(objects)?;
+        self.extra.insert("ContainedObjects".into(), value);
+        Ok(())
+    }
+
+    /// Returns `true` if the lua script or xml ui has been changed using
+    /// [`Object::set_lua_script`] or [`Object::set_xml_ui`] since the object was loaded.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Returns the position of the object as `[x, y, z]`.
+    pub fn position(&self) -> Option<[f64; 3]> {
+        self.transform.map(|t| [t.pos_x, t.pos_y, t.pos_z])
+    }
+
+    /// Sets the position of the object. Creates a default transform if the object has none.
+    pub fn set_position(&mut self, [x, y, z]: [f64; 3]) {
+        let transform = self.transform.get_or_insert_with(Transform::default);
+        (transform.pos_x, transform.pos_y, transform.pos_z) = (x, y, z);
+    }
+
+    /// Returns the rotation of the object in degrees as `[x, y, z]`.
+    pub fn rotation(&self) -> Option<[f64; 3]> {
+        self.transform.map(|t| [t.rot_x, t.rot_y, t.rot_z])
+    }
+
+    /// Sets the rotation of the object in degrees. Creates a default transform if the object has none.
+    pub fn set_rotation(&mut self, [x, y, z]: [f64; 3]) {
+        let transform = self.transform.get_or_insert_with(Transform::default);
+        (transform.rot_x, transform.rot_y, transform.rot_z) = (x, y, z);
+    }
+
+    /// Returns the scale of the object as `[x, y, z]`.
+    pub fn scale(&self) -> Option<[f64; 3]> {
+        self.transform.map(|t| [t.scale_x, t.scale_y, t.scale_z])
+    }
+
+    /// Sets the scale of the object. Creates a default transform if the object has none.
+    pub fn set_scale(&mut self, [x, y, z]: [f64; 3]) {
+        let transform = self.transform.get_or_insert_with(Transform::default);
+        (transform.scale_x, transform.scale_y, transform.scale_z) = (x, y, z);
+    }
+
+    /// Returns `true` if the object is a zone. See [`ZONES`].
+    pub fn is_zone(&self) -> bool {
+        ZONES.contains(&self.name.as_str())
+    }
+
+    /// Moves the object by `[x, y, z]`. Creates a default transform if the object has none,
+    /// so that objects without one are placed relative to the origin.
+    pub fn offset(&mut self, [x, y, z]: [f64; 3]) {
+        let transform = self.transform.get_or_insert_with(Transform::default);
+        transform.pos_x += x;
+        transform.pos_y += y;
+        transform.pos_z += z;
+    }
+
+    /// Moves the object onto the nearest point of a grid with cells of `size`, on the surface of the table.
+    /// The height is kept, so that the object doesn't fall through the table or get stacked. Does nothing if `size` isn't positive.
+    pub fn snap_to_grid(&mut self, size: f64) {
+        if size <= 0.0 {
+            return;
+        }
+        if let Some(transform) = &mut self.transform {
+            transform.pos_x = (transform.pos_x / size).round() * size;
+            transform.pos_z = (transform.pos_z / size).round() * size;
+        }
+    }
+
+    /// Returns `true` if the object is locked in place.
+    pub fn is_locked(&self) -> bool {
+        self.locked.unwrap_or(false)
+    }
+
+    /// Returns the nickname of the object, or its name if it has none, the same way the game displays it.
+    pub fn display_name(&self) -> &str {
+        match self.nickname.is_empty() {
+            true => &self.name,
+            false => &self.nickname,
+        }
+    }
+
     /// Returns a valid [`Tag`], if the list only contains a single valid lua tag.
     /// If it contains no valid lua Tags it returns [`None`].
     /// If the list contains multiple valid lua tags, this function returns an [`Error::Msg`].
@@ -0,0 +1,270 @@
+//! Prompts that ask the user for input, e.g. to select the objects a script gets attached to.
+//!
+//! The [`Prompter`] trait allows replacing the interactive terminal prompts with a different selection mechanism,
+//! or with a non-interactive policy like [`NonInteractive`].
+
+use std::path::PathBuf;
+
+use colored::*;
+use derive_more::Display;
+use itertools::Itertools;
+use path_slash::PathExt;
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::error::Result;
+use crate::objects::{Object, Objects};
+
+/// Asks the user for input.
+pub trait Prompter: Sync {
+    /// Asks the user to select any number of `objects`.
+    fn select_objects(&self, message: &str, objects: Objects) -> Result<Objects>;
+
+    /// Asks the user to select a single object of `objects`.
+    fn select_object(&self, message: &str, objects: Objects) -> Result<Object>;
+
+    /// Asks the user to select one of `paths`.
+    fn select_path(&self, message: &str, paths: Vec<PathBuf>) -> Result<PathBuf>;
+
+    /// Asks the user to select one of `options`.
+    fn select(&self, message: &str, options: Vec<String>) -> Result<String>;
+
+    /// Asks the user a yes or no question.
+    fn confirm(&self, message: &str, default: bool) -> Result<bool>;
+}
+
+/// How objects are displayed in the selection prompts of [`InquirePrompter`].
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// `<GUID> (<Name>) using <Tags>`, the same way objects are displayed in messages.
+    #[default]
+    Inline,
+    /// `<GUID> | <Name> | <Tags>`, with the columns aligned.
+    Columns,
+}
+
+/// Prompts the user in the terminal using [`inquire`].
+///
+/// Objects that don't fit into the width of the terminal are truncated, so that the options don't wrap.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct InquirePrompter {
+    layout: Layout,
+    width: Option<usize>,
+}
+
+impl InquirePrompter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Displays objects using `layout`.
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Truncates objects to `width` columns instead of the width of the terminal.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Returns the number of columns available to display an option,
+    /// leaving room for the cursor and checkbox inquire displays in front of it.
+    fn available_width(&self) -> usize {
+        const MARGIN: usize = 6;
+        let width = self
+            .width
+            .unwrap_or_else(|| match crossterm::terminal::size() {
+                Ok((columns, _)) => columns.into(),
+                Err(_) => usize::MAX,
+            });
+        width.saturating_sub(MARGIN)
+    }
+
+    /// Wraps `objects` in [`DisplayObject`], so they are displayed using the layout of the prompter.
+    fn display(&self, objects: Objects) -> Vec<DisplayObject> {
+        let width = self.available_width();
+        let rows = objects.into_inner().into_iter().map(Row::new).collect_vec();
+
+        let guid_width = rows.iter().map(|row| row.guid.width()).max().unwrap_or(0);
+        let name_width = rows.iter().map(|row| row.name.width()).max().unwrap_or(0);
+        let tags_width = rows.iter().map(|row| row.tags.width()).max().unwrap_or(0);
+        // Columns are separated by ` | `
+        let rest = width.saturating_sub(guid_width + 6);
+        let name_column = name_width.min(shared_width(rest, tags_width));
+
+        rows.into_iter()
+            .map(|row| {
+                let text = match self.layout {
+                    Layout::Inline => row.inline(width),
+                    Layout::Columns => row.columns(guid_width, name_column, rest),
+                };
+                DisplayObject(row.object, text)
+            })
+            .collect()
+    }
+}
+
+impl Prompter for InquirePrompter {
+    fn select_objects(&self, message: &str, objects: Objects) -> Result<Objects> {
+        let objects = inquire::MultiSelect::new(message, self.display(objects)).prompt()?;
+        Ok(objects.into_iter().map(|object| object.0).collect())
+    }
+
+    fn select_object(&self, message: &str, objects: Objects) -> Result<Object> {
+        Ok(inquire::Select::new(message, self.display(objects))
+            .prompt()?
+            .0)
+    }
+
+    fn select_path(&self, message: &str, paths: Vec<PathBuf>) -> Result<PathBuf> {
+        #[derive(Display)]
+        #[display(fmt = "'{}'", "self.0.to_slash_lossy().yellow()")]
+        struct DisplayPath(PathBuf);
+
+        // Wrap `paths` in `DisplayPath` so they can be displayed by the inquire prompt
+        let display_paths = paths.into_iter().map(DisplayPath).collect_vec();
+        Ok(inquire::Select::new(message, display_paths).prompt()?.0)
+    }
+
+    fn select(&self, message: &str, options: Vec<String>) -> Result<String> {
+        Ok(inquire::Select::new(message, options).prompt()?)
+    }
+
+    fn confirm(&self, message: &str, default: bool) -> Result<bool> {
+        Ok(inquire::Confirm::new(message)
+            .with_default(default)
+            .prompt()?)
+    }
+}
+
+/// An object with the text it is displayed with in a prompt.
+#[derive(Display)]
+#[display(fmt = "{}", _1)]
+struct DisplayObject(Object, String);
+
+/// The parts an object is displayed with, without colors, so that their width can be measured.
+struct Row {
+    object: Object,
+    guid: String,
+    name: String,
+    tags: String,
+}
+
+impl Row {
+    fn new(object: Object) -> Self {
+        let name = object.display_name().to_string();
+        let tags = [object.valid_lua(), object.valid_xml()]
+            .into_iter()
+            .filter_map(|tag| tag.ok().flatten())
+            .map(|tag| tag.into_inner())
+            .collect_vec();
+        Self {
+            guid: object.guid.clone(),
+            name,
+            tags: tags.join(" and "),
+            object,
+        }
+    }
+
+    fn color_name(&self, name: &str) -> ColoredString {
+        match self.object.nickname.is_empty() {
+            true => name.bright_white(),
+            false => name.bright_white().bold(),
+        }
+    }
+
+    /// `<GUID> (<Name>) using <Tags>`, truncated to `width`.
+    fn inline(&self, width: usize) -> String {
+        let tags = match self.tags.is_empty() {
+            true => String::new(),
+            false => format!(" using {}", self.tags),
+        };
+        // The guid is followed by a space, and the name is enclosed in parentheses
+        let rest = width.saturating_sub(self.guid.width() + 3);
+        let name = truncate(&self.name, shared_width(rest, tags.width()));
+        let tags = truncate(&tags, rest.saturating_sub(name.width()));
+        format!(
+            "{} ({}){}",
+            self.guid.yellow(),
+            self.color_name(&name),
+            tags.yellow()
+        )
+    }
+
+    /// `<GUID> | <Name> | <Tags>`, where the guid and name are padded to `guid_width` and `name_width`,
+    /// and the tags are truncated to the remaining width of `width`.
+    fn columns(&self, guid_width: usize, name_width: usize, width: usize) -> String {
+        let name = truncate(&self.name, name_width);
+        let tags = truncate(&self.tags, width.saturating_sub(name_width));
+        format!(
+            "{}{} | {}{} | {}",
+            self.guid.yellow(),
+            " ".repeat(guid_width.saturating_sub(self.guid.width())),
+            self.color_name(&name),
+            " ".repeat(name_width.saturating_sub(name.width())),
+            tags.yellow()
+        )
+    }
+}
+
+/// Returns the width of the name, if a name and `other_width` wide text share `width` columns.
+/// The name gets at least a third of `width`, or more if `other_width` leaves more room.
+fn shared_width(width: usize, other_width: usize) -> usize {
+    (width / 3).max(width.saturating_sub(other_width))
+}
+
+/// Shortens `text` to `width` columns, ending with an ellipsis if it has been shortened.
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.into();
+    }
+
+    let mut truncated = String::new();
+    for c in text.chars() {
+        let c_width = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+        if truncated.width() + c_width + 1 > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    match width {
+        0 => truncated,
+        _ => truncated + "…",
+    }
+}
+
+/// Never asks the user for input.
+/// Questions are answered with their default, and selections return an [`Error::Msg`](crate::error::Error::Msg).
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NonInteractive;
+
+impl NonInteractive {
+    fn fail<T>(message: &str) -> Result<T> {
+        Err(format!("cannot prompt in non-interactive mode: {}", message).into())
+    }
+}
+
+impl Prompter for NonInteractive {
+    fn select_objects(&self, message: &str, _: Objects) -> Result<Objects> {
+        Self::fail(message)
+    }
+
+    fn select_object(&self, message: &str, _: Objects) -> Result<Object> {
+        Self::fail(message)
+    }
+
+    fn select_path(&self, message: &str, _: Vec<PathBuf>) -> Result<PathBuf> {
+        Self::fail(message)
+    }
+
+    fn select(&self, message: &str, _: Vec<String>) -> Result<String> {
+        Self::fail(message)
+    }
+
+    fn confirm(&self, _: &str, default: bool) -> Result<bool> {
+        Ok(default)
+    }
+}
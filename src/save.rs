@@ -1,23 +1,84 @@
 use std::collections::HashMap;
 
+use colored::*;
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::error::Result;
 use crate::objects::Objects;
 use crate::tags::Label;
-use crate::Tag;
+use crate::{Tag, Tags};
 
-#[derive(Deserialize, Serialize, Debug)]
+/// Prefix of the component tags that attach a file to Global, e.g. `Global:lua/Global.lua`.
+/// Global can't carry tags itself, so they are stored as component tags of the save instead.
+pub const GLOBAL_PREFIX: &str = "Global:";
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct ComponentTags {
     pub labels: Vec<Label>,
 }
 
+/// A mismatch between the tags, scripts and component tags of a save, see [`Save::violations`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// The object with the `guid` has a lua script, but no valid lua tag.
+    ScriptWithoutTag { guid: String },
+    /// The object with the `guid` has a xml ui, but no valid xml tag.
+    UiWithoutTag { guid: String },
+    /// The object with the `guid` has a valid lua `tag`, but no lua script.
+    TagWithoutScript { guid: String, tag: Tag },
+    /// The object with the `guid` has a valid xml `tag`, but no xml ui.
+    TagWithoutUi { guid: String, tag: Tag },
+    /// A component tag of a lua or xml file that no longer exists.
+    MissingFile(Label),
+}
+
+impl Violation {
+    /// Returns `true` if the save is inconsistent with its files, because a script or ui isn't attached to any file.
+    /// The other violations only leave unused tags behind.
+    pub fn is_inconsistent(&self) -> bool {
+        matches!(
+            self,
+            Violation::ScriptWithoutTag { .. } | Violation::UiWithoutTag { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            #[rustfmt::skip]
+            Violation::ScriptWithoutTag { guid } => write!(f, "{} has a lua script but no valid lua tag", guid.yellow()),
+            #[rustfmt::skip]
+            Violation::UiWithoutTag { guid } => write!(f, "{} has a xml ui but no valid xml tag", guid.yellow()),
+            #[rustfmt::skip]
+            Violation::TagWithoutScript { guid, tag } => write!(f, "{} has {} but no lua script", guid.yellow(), tag),
+            #[rustfmt::skip]
+            Violation::TagWithoutUi { guid, tag } => write!(f, "{} has {} but no xml ui", guid.yellow(), tag),
+            #[rustfmt::skip]
+            Violation::MissingFile(label) => write!(f, "the component tag '{}' refers to a file that doesn't exist", label.displayed.yellow()),
+        }
+    }
+}
+
 /// A representation of the Tabletop Simulator [Save File Format](https://kb.tabletopsimulator.com/custom-content/save-file-format/).
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct Save {
     #[serde(rename = "SaveName")]
     pub name: String,
+    #[serde(rename = "GameMode", default, skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<String>,
+    #[serde(
+        rename = "Description",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub description: Option<String>,
+    /// Tags of the save, which are shown in the workshop. Not to be confused with [`Save::tags`].
+    #[serde(rename = "Tags", default, skip_serializing_if = "Option::is_none")]
+    pub save_tags: Option<Vec<String>>,
     #[serde(rename = "LuaScript", default)]
     pub lua_script: String,
     #[serde(rename = "XmlUI", default)]
@@ -33,13 +94,25 @@ pub struct Save {
 }
 
 impl Save {
-    /// Add `tag` to `self`, if it isn't already included in the labels or object tags
+    /// Returns a [JSON Schema](https://json-schema.org/) of the save file format, as it is modeled by [`Save`].
+    /// Fields that aren't modeled are allowed as additional properties.
+    pub fn json_schema() -> Value {
+        let schema = schemars::schema_for!(Save);
+        serde_json::to_value(schema).expect("schema is valid json")
+    }
+
+    /// Add `tag` to `self`, if it isn't already included in the labels or object tags.
+    /// Tags are compared using their normalized form, the same way Tabletop Simulator compares them.
     pub fn push_object_tag(&mut self, tag: Tag) -> bool {
         let label = Label::from(tag.clone());
+        if let Err(err) = label.validate() {
+            warn!("{}", err);
+            return false;
+        }
         let objects_include = self
             .objects
             .iter()
-            .any(|object| object.tags.iter().any(|t| t == &tag));
+            .any(|object| object.tags.iter().any(|t| Label::from(t.clone()) == label));
 
         if !self.tags.labels.contains(&label) && !objects_include {
             self.tags.labels.push(label);
@@ -50,6 +123,145 @@ impl Save {
         }
     }
 
+    /// Replace `old` with `new` in the object tags and component tags.
+    /// Returns the number of objects that used the `old` tag.
+    pub fn rename_tag(&mut self, old: &Tag, new: &Tag) -> usize {
+        let mut count = 0;
+        for object in self.objects.iter_mut() {
+            for tag in object.tags.iter_mut().filter(|tag| *tag == old) {
+                *tag = new.clone();
+                count += 1;
+            }
+        }
+
+        let (old_label, new_label) = (Label::from(old.clone()), Label::from(new.clone()));
+        for label in self
+            .tags
+            .labels
+            .iter_mut()
+            .filter(|label| **label == old_label)
+        {
+            *label = new_label.clone();
+        }
+        // The new label can already exist anywhere in the list
+        let mut seen = Vec::new();
+        self.tags.labels.retain(|label| match seen.contains(label) {
+            true => false,
+            false => {
+                seen.push(label.clone());
+                true
+            }
+        });
+
+        count
+    }
+
+    /// Returns the tags attached to Global, which are stored as component tags prefixed with [`GLOBAL_PREFIX`].
+    pub fn global_tags(&self) -> Tags {
+        self.tags
+            .labels
+            .iter()
+            .filter_map(|label| label.displayed.strip_prefix(GLOBAL_PREFIX))
+            .map(|tag| Tag::from(tag.to_string()))
+            .collect()
+    }
+
+    /// Returns the valid lua [`Tag`] attached to Global, or [`None`] if there is none.
+    /// If Global has multiple valid lua tags, this function returns an [`Error::Msg`](crate::error::Error::Msg).
+    pub fn global_lua(&self) -> Result<Option<Tag>> {
+        let valid: Tags = self.global_tags().into_iter().filter(Tag::is_lua).collect();
+        match valid.len() {
+            0 | 1 => Ok(valid.first().cloned()),
+            _ => Err(format!("Global has multiple valid lua tags: {}", valid).into()),
+        }
+    }
+
+    /// Returns the valid xml [`Tag`] attached to Global, or [`None`] if there is none.
+    /// If Global has multiple valid xml tags, this function returns an [`Error::Msg`](crate::error::Error::Msg).
+    pub fn global_xml(&self) -> Result<Option<Tag>> {
+        let valid: Tags = self.global_tags().into_iter().filter(Tag::is_xml).collect();
+        match valid.len() {
+            0 | 1 => Ok(valid.first().cloned()),
+            _ => Err(format!("Global has multiple valid xml tags: {}", valid).into()),
+        }
+    }
+
+    /// Attaches `tag` to Global, replacing the global tag of the same type.
+    /// Returns `true` if the tags of Global have changed.
+    pub fn set_global_tag(&mut self, tag: Tag) -> bool {
+        let label = Label::from(Tag::from(format!(
+            "{GLOBAL_PREFIX}{}",
+            tag.clone().into_inner()
+        )));
+        if self.tags.labels.contains(&label) {
+            return false;
+        }
+        self.tags.labels.retain(|label| {
+            let global = label.displayed.strip_prefix(GLOBAL_PREFIX);
+            let other = global.map(|other| Tag::from(other.to_string()));
+            !other.is_some_and(|other| other.is_lua() == tag.is_lua() && other.is_valid())
+        });
+        self.tags.labels.push(label);
+        true
+    }
+
+    /// Removes all tags attached to Global, and returns them.
+    pub fn remove_global_tags(&mut self) -> Tags {
+        let tags = self.global_tags();
+        self.tags
+            .labels
+            .retain(|label| !label.displayed.starts_with(GLOBAL_PREFIX));
+        tags
+    }
+
+    /// Sorts the objects by their GUID and rounds their floating point numbers to `decimals` decimal places, if set,
+    /// so that saving the same state twice results in the same file, and diffs only show actual changes.
+    pub fn normalize(&mut self, decimals: Option<u32>) {
+        self.objects.sort_by_guid();
+        if let Some(decimals) = decimals {
+            self.objects.round_floats(decimals);
+        }
+    }
+
+    /// Returns the violations of the invariants between tags, scripts and component tags:
+    /// every object with a lua script or xml ui has a valid tag of that type, and the other way around,
+    /// and every component tag is carried by an object or attached to Global.
+    ///
+    /// Returns an error if an object has multiple valid lua or xml tags.
+    pub fn violations(&self) -> Result<Vec<Violation>> {
+        let mut violations = Vec::new();
+        for object in self.objects.iter() {
+            let guid = || object.guid.clone();
+            match (object.valid_lua()?, object.lua_script.is_empty()) {
+                (None, false) => violations.push(Violation::ScriptWithoutTag { guid: guid() }),
+                (Some(tag), true) => {
+                    violations.push(Violation::TagWithoutScript { guid: guid(), tag })
+                }
+                _ => (),
+            }
+            match (object.valid_xml()?, object.xml_ui.is_empty()) {
+                (None, false) => violations.push(Violation::UiWithoutTag { guid: guid() }),
+                (Some(tag), true) => violations.push(Violation::TagWithoutUi { guid: guid(), tag }),
+                _ => (),
+            }
+        }
+
+        // Component tags are kept for files that aren't attached to any object anymore,
+        // so only the ones whose file has been removed are left behind
+        violations.extend(
+            self.tags
+                .labels
+                .iter()
+                .filter(|label| !label.displayed.starts_with(GLOBAL_PREFIX))
+                .filter(|label| match Tag::from(label.displayed.clone()).path() {
+                    Ok(path) => !path.exists(),
+                    Err(_) => false,
+                })
+                .map(|label| Violation::MissingFile(label.clone())),
+        );
+        Ok(violations)
+    }
+
     /// Remove component tags that exist as object tags
     pub fn remove_object_tags(&mut self) {
         self.tags.labels.retain(|label| {
@@ -2,14 +2,202 @@ use colored::*;
 use derive_more::{Deref, DerefMut, Display, IntoIterator};
 use itertools::Itertools;
 use path_slash::PathExt;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use crate::error::{Error, Result};
 
+static NAMESPACE: OnceLock<String> = OnceLock::new();
+static EXTENSIONS: OnceLock<Extensions> = OnceLock::new();
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+static PATTERNS: OnceLock<(Regex, Regex)> = OnceLock::new();
+
+/// Sets the namespace every tag is prefixed with, e.g. `proj1` for `proj1/lua/<FilePath>.lua`.
+/// Tags of other namespaces are not valid, so that multiple projects can share a save without interfering with each other.
+///
+/// The namespace can only be set once, before any tag is checked. Returns an [`Error::Msg`] if a different namespace has already been set,
+/// if tags have already been checked without a namespace, or if the namespace contains characters other than letters, digits, `_` and `-`.
+pub fn set_namespace(namespace: &str) -> Result<()> {
+    let is_valid = !namespace.is_empty()
+        && namespace
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if !is_valid {
+        return Err(format!("'{}' is not a valid namespace", namespace.yellow()).into());
+    }
+
+    // Tags that have been checked before would belong to no namespace
+    if NAMESPACE.get().is_none() && PATTERNS.get().is_some() {
+        return Err("the namespace has to be set before any tag is checked".into());
+    }
+    match NAMESPACE.get_or_init(|| namespace.into()) == namespace {
+        true => Ok(()),
+        false => Err("a different namespace has already been set".into()),
+    }
+}
+
+/// Returns the namespace set by [`set_namespace`].
+pub fn namespace() -> Option<&'static str> {
+    NAMESPACE.get().map(String::as_str)
+}
+
+/// Returns the prefix of every tag, e.g. `proj1/`, or an empty string if no namespace has been set.
+fn prefix() -> String {
+    namespace()
+        .map(|namespace| format!("{namespace}/"))
+        .unwrap_or_default()
+}
+
+/// File extensions of lua scripts and xml ui files, without the leading dot.
+/// Extensions can consist of multiple parts, e.g. `xml.jinja`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Extensions {
+    pub lua: Vec<String>,
+    pub xml: Vec<String>,
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self {
+            lua: vec!["lua".into(), "ttslua".into()],
+            xml: vec!["xml".into()],
+        }
+    }
+}
+
+impl Extensions {
+    /// Returns the longest extension `file_name` ends with, and whether it is a lua extension.
+    fn find(&self, file_name: &str) -> Option<(&str, bool)> {
+        let lua = self.lua.iter().map(|ext| (ext.as_str(), true));
+        let xml = self.xml.iter().map(|ext| (ext.as_str(), false));
+        lua.chain(xml)
+            .filter(|(ext, _)| {
+                let name = file_name
+                    .strip_suffix(ext)
+                    .and_then(|name| name.strip_suffix('.'));
+                name.is_some_and(|name| !name.is_empty())
+            })
+            .max_by_key(|(ext, _)| ext.len())
+    }
+
+    /// Returns `true` if `path` ends with one of the lua extensions.
+    pub fn is_lua<P: AsRef<Path>>(&self, path: P) -> bool {
+        let file_name = path.as_ref().file_name().and_then(|name| name.to_str());
+        matches!(file_name.and_then(|name| self.find(name)), Some((_, true)))
+    }
+
+    /// Returns `true` if `path` ends with one of the xml extensions.
+    pub fn is_xml<P: AsRef<Path>>(&self, path: P) -> bool {
+        let file_name = path.as_ref().file_name().and_then(|name| name.to_str());
+        matches!(file_name.and_then(|name| self.find(name)), Some((_, false)))
+    }
+
+    /// Returns the path of the xml file with the same name as the lua file at `path`, or vice versa,
+    /// using the first extension of the other kind, e.g. `ui/widget.xml` for `ui/widget.lua`.
+    /// Returns [`None`] if `path` is neither a lua nor a xml file.
+    pub fn counterpart<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        let file_name = path.file_name()?.to_str()?;
+        let (ext, is_lua) = self.find(file_name)?;
+        let name = &file_name[..file_name.len() - ext.len()];
+        let other = match is_lua {
+            true => self.xml.first()?,
+            false => self.lua.first()?,
+        };
+        Some(path.with_file_name(format!("{name}{other}")))
+    }
+
+    /// Returns a regex pattern that matches any of `extensions`, including the leading dot.
+    fn pattern(extensions: &[String]) -> String {
+        let extensions = extensions.iter().map(|ext| regex::escape(ext)).join("|");
+        format!(r"\.({extensions})")
+    }
+}
+
+/// Returns the regexes valid lua and xml tags match, which are compiled once the first tag is checked,
+/// using the [`namespace`] and [`extensions`] that have been set.
+fn patterns() -> &'static (Regex, Regex) {
+    PATTERNS.get_or_init(|| {
+        let prefix = regex::escape(&prefix());
+        let pattern = |kind: &str, extensions: &[String]| {
+            let extensions = Extensions::pattern(extensions);
+            Regex::new(&format!(r"^{prefix}{kind}/.+{extensions}$")).unwrap()
+        };
+        (
+            pattern("lua", &extensions().lua),
+            pattern("xml", &extensions().xml),
+        )
+    })
+}
+
+/// Sets the file extensions that are accepted for lua scripts and xml ui files.
+/// Defaults to `lua` and `ttslua` for lua scripts, and `xml` for xml ui files.
+///
+/// The extensions can only be set once. Returns an [`Error::Msg`] if different extensions have already been set or used,
+/// if an extension is empty, starts or ends with a dot, contains a path separator, or is used for both lua and xml.
+pub fn set_extensions(extensions: Extensions) -> Result<()> {
+    let all = extensions.lua.iter().chain(&extensions.xml);
+    if let Some(ext) = all.clone().find(|ext| {
+        ext.is_empty() || ext.starts_with('.') || ext.ends_with('.') || ext.contains(['/', '\\'])
+    }) {
+        return Err(format!("'{}' is not a valid file extension", ext.yellow()).into());
+    }
+    if let Some(ext) = extensions
+        .lua
+        .iter()
+        .find(|ext| extensions.xml.contains(ext))
+    {
+        return Err(format!("'{}' can't be used for both lua and xml", ext.yellow()).into());
+    }
+    if extensions.lua.is_empty() || extensions.xml.is_empty() {
+        return Err("at least one lua and one xml extension is required".into());
+    }
+
+    match *EXTENSIONS.get_or_init(|| extensions.clone()) == extensions {
+        true => Ok(()),
+        false => Err("different file extensions have already been set".into()),
+    }
+}
+
+/// Returns the extensions set by [`set_extensions`], or the default extensions if none have been set.
+pub fn extensions() -> &'static Extensions {
+    EXTENSIONS.get_or_init(Extensions::default)
+}
+
+/// Sets the directory that absolute paths are relative to when they are converted into tags, e.g. the project root.
+///
+/// The root can only be set once. Returns an [`Error::Msg`] if a different root has already been set.
+pub fn set_root<P: AsRef<Path>>(root: P) -> Result<()> {
+    let root = root.as_ref();
+    match ROOT.get_or_init(|| root.into()) == root {
+        true => Ok(()),
+        false => Err("a different root has already been set".into()),
+    }
+}
+
+/// Returns the root set by [`set_root`].
+pub fn root() -> Option<&'static Path> {
+    ROOT.get().map(PathBuf::as_path)
+}
+
 /// A list of [`Tags`](Tag) associated with an [`Object`](crate::objects::Object).
 /// Tags can be filtered by valid an invalid tags.
-#[derive(Deserialize, Serialize, Default, Clone, Debug, Deref, DerefMut, Display, IntoIterator)]
+#[derive(
+    Deserialize,
+    Serialize,
+    JsonSchema,
+    Default,
+    Clone,
+    Debug,
+    Deref,
+    DerefMut,
+    Display,
+    IntoIterator,
+)]
 #[display(fmt = "{}", "self.0.iter().format(\", \")")]
 pub struct Tags(Vec<Tag>);
 
@@ -33,35 +221,78 @@ impl Tags {
 }
 
 /// A tag associated with an [`Object`](crate::objects::Object).
-#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Display)]
+#[derive(Deserialize, Serialize, JsonSchema, PartialEq, Clone, Debug, Display)]
 #[display(fmt = "{}", "self.0.yellow()")]
 pub struct Tag(String);
 
+/// Wraps `tag` without validating it, like tags that are read from a save.
+impl From<String> for Tag {
+    fn from(tag: String) -> Self {
+        Self(tag)
+    }
+}
+
 impl TryFrom<&Path> for Tag {
     type Error = Error;
 
-    /// Create a new tag from a path, using `scripts/<FilePath>.lua` and `ui/<FilePath>.xml` as a naming convention.
+    /// Create a new tag from a path, using `lua/<FilePath>.lua` and `xml/<FilePath>.xml` as a naming convention,
+    /// prefixed with the [`namespace`] if one has been set.
+    /// Absolute paths are converted into paths relative to the [`root`], or the current directory if no root has been set.
     fn try_from(path: &Path) -> Result<Self> {
+        if path.is_absolute() {
+            return match root() {
+                Some(root) => Tag::from_path_in(path, root),
+                None => Tag::from_path_in(path, std::env::current_dir()?),
+            };
+        }
+
         // Note: `strip_prefix` might not work on linux systems
         let file_path = match path.strip_prefix(".\\") {
-            Ok(file_path) => file_path.to_slash_lossy(), // Replace `\` with `/`
+            Ok(file_path) => file_path.to_slash(), // Replace `\` with `/`
             Err(_) => return Err("Path has to be relative".into()),
         };
-
-        let file_ext = match path.extension() {
-            Some(file_ext) => file_ext.to_str().unwrap(),
-            None => return Err("Path must end in a file extension".into()),
+        let Some(file_path) = file_path else {
+            return Err(
+                format!("'{}' is not valid unicode", path.to_slash_lossy().yellow()).into(),
+            );
         };
 
-        match file_ext {
-            "lua" | "ttslua" => Ok(Self(format!("lua/{}", file_path))),
-            "xml" => Ok(Self(format!("xml/{}", file_path))),
-            _ => Err("Path is not a lua or xml file".into()),
+        // Windows removes trailing dots and spaces, so the file would have a different name than the tag
+        if file_path
+            .split('/')
+            .any(|name| name.ends_with('.') || name.ends_with(' '))
+        {
+            #[rustfmt::skip]
+            return Err(format!("'{}' ends with a dot or space, which is not supported on Windows", file_path.yellow()).into());
+        }
+
+        if path.extension().is_none() {
+            return Err("Path must end in a file extension".into());
+        }
+
+        match extensions().find(&file_path) {
+            Some((_, true)) => Ok(Self(format!("{}lua/{}", prefix(), file_path))),
+            Some((_, false)) => Ok(Self(format!("{}xml/{}", prefix(), file_path))),
+            None => Err("Path is not a lua or xml file".into()),
         }
     }
 }
 
 impl Tag {
+    /// Create a new tag from an absolute `path` inside of the `root` directory.
+    /// Returns an [`Error::Msg`], if the path is outside of `root`.
+    pub fn from_path_in<P: AsRef<Path>, R: AsRef<Path>>(path: P, root: R) -> Result<Self> {
+        match path.as_ref().strip_prefix(root.as_ref()) {
+            Ok(file_path) => Tag::try_from(Path::new(".\\").join(file_path).as_path()),
+            Err(_) => Err(format!(
+                "'{}' is outside of the project root '{}'",
+                path.as_ref().to_slash_lossy().yellow(),
+                root.as_ref().to_slash_lossy().yellow()
+            )
+            .into()),
+        }
+    }
+
     /// Consumes `Tag`, returning the wrapped value.
     pub fn into_inner(self) -> String {
         self.0
@@ -72,16 +303,16 @@ impl Tag {
         self.is_lua() || self.is_xml()
     }
 
-    /// Returns `true` if `self` follows the `lua/<FilePath>.lua` naming convention.
+    /// Returns `true` if `self` follows the `lua/<FilePath>.lua` naming convention,
+    /// ends with one of the lua [`extensions`], and belongs to the [`namespace`].
     pub fn is_lua(&self) -> bool {
-        let exprs = regex::Regex::new(r"^lua/.+(\.lua|\.ttslua)$").unwrap();
-        exprs.is_match(&self.0)
+        patterns().0.is_match(&self.0)
     }
 
-    /// Returns `true` if `self` follows the `xml/<FilePath>.xml` naming convention.
+    /// Returns `true` if `self` follows the `xml/<FilePath>.xml` naming convention,
+    /// ends with one of the xml [`extensions`], and belongs to the [`namespace`].
     pub fn is_xml(&self) -> bool {
-        let exprs = regex::Regex::new(r"^xml/.+(\.xml)$").unwrap();
-        exprs.is_match(&self.0)
+        patterns().1.is_match(&self.0)
     }
 
     /// Returns `self` as a path if it is valid.
@@ -89,15 +320,17 @@ impl Tag {
     pub fn path(&self) -> Result<PathBuf> {
         let path = Path::new(&self.0);
         match self {
-            _ if self.is_lua() => Ok(path.strip_prefix("lua/")?),
-            _ if self.is_xml() => Ok(path.strip_prefix("xml/")?),
+            _ if self.is_lua() => Ok(path.strip_prefix(format!("{}lua/", prefix()))?),
+            _ if self.is_xml() => Ok(path.strip_prefix(format!("{}xml/", prefix()))?),
             _ => Err("{self} is not a valid tag".into()),
         }
         .map(|file| Path::new("./").join(file))
     }
 
     /// Determines whether `base` is a prefix of `self`.
+    /// Both `\` and `/` are separators in `base`, so that paths like `.\lua` match on every platform.
     pub fn starts_with<P: AsRef<Path>>(&self, base: &P) -> bool {
+        let base = PathBuf::from(base.as_ref().to_string_lossy().replace('\\', "/"));
         match self.path() {
             Ok(path) => path.starts_with(base),
             Err(_) => false,
@@ -105,17 +338,66 @@ impl Tag {
     }
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Debug)]
+/// A component tag of the save. Tabletop Simulator compares tags using their `normalized` form,
+/// so labels are equal if their displayed forms are equal after normalizing them.
+///
+/// The normalized form is always derived from `displayed` when a label is serialized,
+/// so that labels added by ttsst behave the same as tags created in-game.
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 pub struct Label {
     pub displayed: String,
     pub normalized: String,
 }
 
-impl From<Tag> for Label {
-    fn from(value: Tag) -> Self {
+impl Label {
+    pub fn new<S: Into<String>>(displayed: S) -> Self {
+        let displayed = displayed.into();
         Label {
-            displayed: value.0.clone(),
-            normalized: value.0.clone(),
+            normalized: Label::normalize(&displayed),
+            displayed,
+        }
+    }
+
+    /// Returns the normalized form of a tag, which Tabletop Simulator creates by lowercasing it.
+    pub fn normalize(displayed: &str) -> String {
+        displayed.to_lowercase()
+    }
+
+    /// Returns an error if the label is empty, or if its normalized form doesn't match the displayed one.
+    pub fn validate(&self) -> Result<()> {
+        if self.displayed.trim().is_empty() {
+            return Err("component tags can't be empty".into());
+        }
+        if self.normalized != Label::normalize(&self.displayed) {
+            return Err(format!(
+                "the component tag '{}' is normalized as '{}' instead of '{}'",
+                self.displayed,
+                self.normalized,
+                Label::normalize(&self.displayed)
+            )
+            .into());
         }
+        Ok(())
+    }
+}
+
+impl From<Tag> for Label {
+    fn from(value: Tag) -> Self {
+        Label::new(value.0)
+    }
+}
+
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        Label::normalize(&self.displayed) == Label::normalize(&other.displayed)
+    }
+}
+
+impl Serialize for Label {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Label", 2)?;
+        state.serialize_field("displayed", &self.displayed)?;
+        state.serialize_field("normalized", &Label::normalize(&self.displayed))?;
+        state.end()
     }
 }
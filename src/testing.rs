@@ -0,0 +1,190 @@
+//! Helpers to build synthetic saves, objects and tags, so that code using them can be tested without a running game.
+//!
+//! The [`fixtures`] contain snippets of real-world saves, and the [`mock`] server answers messages like the game would.
+
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::objects::{Object, Transform};
+use crate::save::Save;
+use crate::tags::{Label, Tag};
+
+pub mod mock;
+
+/// Snippets of real-world saves, as they are written by Tabletop Simulator.
+pub mod fixtures {
+    /// A save with no objects and no scripts.
+    pub const MINIMAL: &str = include_str!("testing/fixtures/minimal.json");
+
+    /// A save with a global script, and objects that have lua scripts, xml ui and nested objects.
+    pub const SCRIPTED: &str = include_str!("testing/fixtures/scripted.json");
+
+    /// A save with a scripting zone, hidden zones and a regular object.
+    pub const ZONES: &str = include_str!("testing/fixtures/zones.json");
+
+    /// Every fixture together with its name.
+    pub const ALL: &[(&str, &str)] = &[
+        ("minimal", MINIMAL),
+        ("scripted", SCRIPTED),
+        ("zones", ZONES),
+    ];
+}
+
+/// Creates a tag from a `path` relative to the project root, e.g. `scripts/foo.lua`.
+///
+/// # Panics
+///
+/// Panics if the path is not a lua or xml file.
+pub fn tag<P: AsRef<Path>>(path: P) -> Tag {
+    Tag::try_from(Path::new(".\\").join(path).as_path()).expect("not a valid tag path")
+}
+
+/// Parses one of the [`fixtures`] or any other save.
+///
+/// # Panics
+///
+/// Panics if `json` is not a valid save.
+pub fn parse_save(json: &str) -> Save {
+    serde_json::from_str(json).expect("not a valid save")
+}
+
+/// Creates an empty save with the `name`, containing `objects`.
+pub fn save<I: IntoIterator<Item = Object>>(name: &str, objects: I) -> Save {
+    let mut save = SaveBuilder::new(name).build();
+    save.objects = objects.into_iter().collect();
+    save
+}
+
+/// Builds a [`Save`] the same way Tabletop Simulator would serialize it.
+#[derive(Clone, Debug)]
+pub struct SaveBuilder {
+    value: Value,
+}
+
+impl SaveBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            value: json!({
+                "SaveName": name,
+                "LuaScript": "",
+                "XmlUI": "",
+                "ObjectStates": [],
+                "ComponentTags": { "labels": [] },
+            }),
+        }
+    }
+
+    /// Sets the global lua script.
+    pub fn lua_script(mut self, script: &str) -> Self {
+        self.value["LuaScript"] = script.into();
+        self
+    }
+
+    /// Sets the global xml ui.
+    pub fn xml_ui(mut self, ui: &str) -> Self {
+        self.value["XmlUI"] = ui.into();
+        self
+    }
+
+    /// Adds an object to the save.
+    pub fn object(mut self, object: Object) -> Self {
+        let object = serde_json::to_value(object).unwrap();
+        self.value["ObjectStates"]
+            .as_array_mut()
+            .unwrap()
+            .push(object);
+        self
+    }
+
+    /// Adds a component tag to the save.
+    pub fn label(mut self, tag: Tag) -> Self {
+        let label = serde_json::to_value(Label::from(tag)).unwrap();
+        self.value["ComponentTags"]["labels"]
+            .as_array_mut()
+            .unwrap()
+            .push(label);
+        self
+    }
+
+    /// Sets any other field of the save.
+    pub fn field<T: Into<Value>>(mut self, key: &str, value: T) -> Self {
+        self.value[key] = value.into();
+        self
+    }
+
+    pub fn build(self) -> Save {
+        serde_json::from_value(self.value).expect("not a valid save")
+    }
+}
+
+/// Creates an object with the `guid` and the name `Custom_Model`.
+pub fn object(guid: &str) -> Object {
+    ObjectBuilder::new(guid).build()
+}
+
+/// Builds an [`Object`] the same way Tabletop Simulator would serialize it.
+#[derive(Clone, Debug)]
+pub struct ObjectBuilder {
+    value: Value,
+}
+
+impl ObjectBuilder {
+    pub fn new(guid: &str) -> Self {
+        Self {
+            value: json!({
+                "GUID": guid,
+                "Name": "Custom_Model",
+                "Nickname": "",
+                "Tags": [],
+                "LuaScript": "",
+                "XmlUI": "",
+            }),
+        }
+    }
+
+    /// Sets the object name, e.g. `ScriptingTrigger`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.value["Name"] = name.into();
+        self
+    }
+
+    pub fn nickname(mut self, nickname: &str) -> Self {
+        self.value["Nickname"] = nickname.into();
+        self
+    }
+
+    /// Sets the lua script, without adding a tag.
+    pub fn lua_script(mut self, script: &str) -> Self {
+        self.value["LuaScript"] = script.into();
+        self
+    }
+
+    /// Sets the xml ui, without adding a tag.
+    pub fn xml_ui(mut self, ui: &str) -> Self {
+        self.value["XmlUI"] = ui.into();
+        self
+    }
+
+    /// Adds a tag to the object.
+    pub fn tag(mut self, tag: Tag) -> Self {
+        let tags = self.value["Tags"].as_array_mut().unwrap();
+        tags.push(tag.into_inner().into());
+        self
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.value["Transform"] = serde_json::to_value(transform).unwrap();
+        self
+    }
+
+    /// Sets any other field of the object.
+    pub fn field<T: Into<Value>>(mut self, key: &str, value: T) -> Self {
+        self.value[key] = value.into();
+        self
+    }
+
+    pub fn build(self) -> Object {
+        serde_json::from_value(self.value).expect("not a valid object")
+    }
+}
@@ -0,0 +1,163 @@
+//! A server that answers messages the same way Tabletop Simulator would, using a save file on disk.
+//!
+//! There is no lua interpreter, so executed lua code is echoed back as a print message instead.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::api::LISTEN_PORT;
+use crate::api::{MessageCustomMessage, MessageExecute, MessageGetScripts, MessageReload};
+use crate::error::{Error, Result};
+
+/// Pretends to be Tabletop Simulator with the save at `save_path` loaded.
+#[derive(Debug)]
+pub struct MockServer {
+    listener: TcpListener,
+    reply_port: u16,
+    save_path: PathBuf,
+}
+
+impl MockServer {
+    /// Listens for messages on `port`, usually [`SEND_PORT`](crate::api::SEND_PORT), and serves the save at `save_path`.
+    /// Use `0` to listen on any free port. Answers are sent to [`LISTEN_PORT`], unless [`reply_to`](Self::reply_to) is used.
+    pub fn bind<P: Into<PathBuf>>(port: u16, save_path: P) -> Result<Self> {
+        match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => Ok(Self {
+                listener,
+                reply_port: LISTEN_PORT,
+                save_path: save_path.into(),
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => Err(Error::PortInUse(port)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sends answers to `port` instead of [`LISTEN_PORT`].
+    pub fn reply_to(mut self, port: u16) -> Self {
+        self.reply_port = port;
+        self
+    }
+
+    /// Returns the port messages are received on, which differs from [`SEND_PORT`](crate::api::SEND_PORT) if the server listens on any free port.
+    pub fn port(&self) -> Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    pub fn save_path(&self) -> &Path {
+        &self.save_path
+    }
+
+    /// Answers incoming messages until an error occurs.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            self.handle()?;
+        }
+    }
+
+    /// Waits for the next message, answers it and returns it.
+    pub fn handle(&self) -> Result<Value> {
+        let (mut stream, _) = self.listener.accept()?;
+        let mut buffer = String::new();
+        stream.read_to_string(&mut buffer)?;
+        let message: Value = serde_json::from_str(&buffer)?;
+
+        let message_id = message["messageID"].as_u64().unwrap_or(u64::MAX);
+        match u8::try_from(message_id).unwrap_or(u8::MAX) {
+            MessageGetScripts::MESSAGE_ID => self.reply(self.answer_reload()?)?,
+            MessageReload::MESSAGE_ID => {
+                self.apply(&message["scriptStates"])?;
+                self.reply(self.answer_reload()?)?;
+            }
+            MessageCustomMessage::MESSAGE_ID => self.reply(json!({
+                "messageID": 4,
+                "customMessage": message["customMessage"],
+            }))?,
+            MessageExecute::MESSAGE_ID => {
+                self.reply(json!({ "messageID": 2, "message": message["script"] }))?;
+                self.reply(json!({
+                    "messageID": 5,
+                    "returnID": message["returnID"],
+                    "returnValue": null,
+                }))?;
+            }
+            _ => return Err(format!("unknown messageID {message_id}").into()),
+        }
+        Ok(message)
+    }
+
+    /// Sends an answer to the port that has been set using [`reply_to`](Self::reply_to).
+    fn reply(&self, answer: Value) -> Result<()> {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.reply_port))?;
+        stream.write_all(answer.to_string().as_bytes())?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Value> {
+        Ok(serde_json::from_str(&fs::read_to_string(&self.save_path)?)?)
+    }
+
+    /// Returns the answer Tabletop Simulator sends after loading the save.
+    /// It contains the Global script and ui, and every object that has a script or ui.
+    fn answer_reload(&self) -> Result<Value> {
+        let save = self.read()?;
+        let global = json!({
+            "name": "Global",
+            "guid": "-1",
+            "script": save["LuaScript"],
+            "ui": save["XmlUI"],
+        });
+
+        let objects = save["ObjectStates"].as_array().into_iter().flatten();
+        let states = objects.filter_map(|object| {
+            let (script, ui) = (text(&object["LuaScript"]), text(&object["XmlUI"]));
+            if script.is_empty() && ui.is_empty() {
+                return None;
+            }
+            let name = match text(&object["Nickname"]) {
+                "" => text(&object["Name"]),
+                nickname => nickname,
+            };
+            Some(json!({ "name": name, "guid": object["GUID"], "script": script, "ui": ui }))
+        });
+
+        Ok(json!({
+            "messageID": 1,
+            "savePath": self.save_path.to_string_lossy(),
+            "scriptStates": std::iter::once(global).chain(states).collect::<Vec<_>>(),
+        }))
+    }
+
+    /// Updates the scripts and ui of the objects in `states`, and writes the save.
+    fn apply(&self, states: &Value) -> Result<()> {
+        let mut save = self.read()?;
+        for state in states.as_array().into_iter().flatten() {
+            let object = match text(&state["guid"]) {
+                "-1" => Some(&mut save),
+                guid => save["ObjectStates"]
+                    .as_array_mut()
+                    .and_then(|objects| objects.iter_mut().find(|o| o["GUID"] == guid)),
+            };
+            let Some(object) = object else {
+                continue;
+            };
+            if let Some(script) = state.get("script") {
+                object["LuaScript"] = script.clone();
+            }
+            if let Some(ui) = state.get("ui") {
+                object["XmlUI"] = ui.clone();
+            }
+        }
+        fs::write(&self.save_path, serde_json::to_string_pretty(&save)?)?;
+        Ok(())
+    }
+}
+
+/// Returns the string of `value`, or an empty string if it is not a string.
+fn text(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use ttsst::api::{BatchOutcome, ExecuteBatch};
+
+fn batch(n: usize) -> ExecuteBatch {
+    let mut batch = ExecuteBatch::new();
+    for i in 0..n {
+        batch.push(format!("op{i}"), format!("return {i}"));
+    }
+    batch
+}
+
+#[test]
+fn chunks_respect_the_operation_limit() {
+    let chunks = batch(120).chunks();
+    let sizes = chunks.iter().map(|chunk| chunk.labels.len());
+    assert!(sizes.eq([50, 50, 20]));
+    assert_eq!(chunks[2].labels[0], "op100");
+}
+
+#[test]
+fn chunks_respect_the_length_limit() {
+    let batch = batch(10).max_length(600);
+    let chunks = batch.chunks();
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|chunk| chunk.script.len() <= 600));
+    assert_eq!(
+        chunks.iter().map(|chunk| chunk.labels.len()).sum::<usize>(),
+        10
+    );
+}
+
+#[test]
+fn large_operations_get_a_chunk_of_their_own() {
+    let mut batch = ExecuteBatch::new().max_length(100);
+    batch.push("large", "x".repeat(200));
+    batch.push("small", "return 1");
+    let chunks = batch.chunks();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].labels, ["large"]);
+}
+
+#[test]
+fn scripts_are_quoted_with_an_unused_level() {
+    let mut batch = ExecuteBatch::new();
+    batch.push("brackets", "return t[a[1]]");
+    let script = &batch.chunks()[0].script;
+    assert!(script.contains("run([=[\nreturn t[a[1]]]=])"));
+}
+
+#[test]
+fn outcomes_are_attributed_to_operations() {
+    let chunk = &batch(2).chunks()[0];
+    let results = json!([{ "ok": true, "value": 1 }, { "ok": false, "error": "boom" }]);
+    let outcomes = chunk.outcomes(Some(results.to_string().into())).unwrap();
+    assert_eq!(
+        outcomes,
+        [
+            BatchOutcome {
+                label: "op0".into(),
+                result: Ok(Some(json!(1)))
+            },
+            BatchOutcome {
+                label: "op1".into(),
+                result: Err("boom".into())
+            },
+        ]
+    );
+}
+
+#[test]
+fn missing_results_are_an_error() {
+    let chunk = &batch(2).chunks()[0];
+    let results = json!([{ "ok": true }]);
+    assert!(chunk.outcomes(Some(results.to_string().into())).is_err());
+    assert!(chunk.outcomes(None).is_err());
+}
+
+#[test]
+fn failing_chunks_dont_abort_the_batch() {
+    let batch = batch(3).max_operations(1).pacing(Duration::from_millis(20));
+    let start = Instant::now();
+    let mut progress = Vec::new();
+    let outcomes = batch
+        .run(
+            |chunk| match chunk.labels[0].as_str() {
+                "op1" => Err("no answer"),
+                _ => Ok(Some(json!([{ "ok": true, "value": 1 }]).to_string().into())),
+            },
+            |done, total| {
+                progress.push((done, total));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    let results = outcomes.into_iter().map(|outcome| outcome.result);
+    assert!(results.eq([
+        Ok(Some(json!(1))),
+        Err("no answer".into()),
+        Ok(Some(json!(1)))
+    ]));
+    assert_eq!(progress, [(1, 3), (2, 3), (3, 3)]);
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn progress_errors_stop_the_batch() {
+    let batch = batch(3).max_operations(1).pacing(Duration::ZERO);
+    let mut executed = 0;
+    let result = batch.run(
+        |_| {
+            executed += 1;
+            Ok(None)
+        },
+        |_, _| Err("cancelled"),
+    );
+    assert_eq!(result, Err("cancelled"));
+    assert_eq!(executed, 1);
+}
@@ -0,0 +1,42 @@
+//! The extensions are global, so these tests run in their own process.
+
+use ttsst::tags::{self, Extensions, Tag};
+use ttsst::testing;
+
+#[test]
+fn custom_extensions_replace_the_default_extensions() {
+    let extensions = Extensions {
+        lua: vec!["tua".into()],
+        xml: vec!["xml.jinja".into(), "xml".into()],
+    };
+    assert!(tags::set_extensions(Extensions {
+        lua: vec!["lua".into()],
+        xml: vec!["lua".into()],
+    })
+    .is_err());
+    tags::set_extensions(extensions.clone()).unwrap();
+    assert!(tags::set_extensions(Extensions::default()).is_err());
+    assert!(tags::set_extensions(extensions).is_ok());
+
+    assert_eq!(testing::tag("foo.tua").into_inner(), "lua/foo.tua");
+    assert_eq!(
+        testing::tag("foo.xml.jinja").into_inner(),
+        "xml/foo.xml.jinja"
+    );
+    assert!(Tag::try_from(std::path::Path::new(".\\foo.lua")).is_err());
+
+    let tag = |tag: &str| Tag::from(tag.to_string());
+    assert!(tag("lua/foo.tua").is_lua());
+    assert!(!tag("lua/foo.lua").is_lua());
+    assert!(tag("xml/foo.xml.jinja").is_xml());
+    assert!(!tag("xml/.xml").is_xml());
+
+    assert!(tags::extensions().is_xml("ui/foo.xml.jinja"));
+    assert!(!tags::extensions().is_lua("ui/foo.xml.jinja"));
+
+    let counterpart = |path: &str| tags::extensions().counterpart(path);
+    assert_eq!(counterpart("ui/foo.tua"), Some("ui/foo.xml.jinja".into()));
+    assert_eq!(counterpart("ui/foo.xml.jinja"), Some("ui/foo.tua".into()));
+    assert_eq!(counterpart("ui/foo.xml"), Some("ui/foo.tua".into()));
+    assert_eq!(counterpart("ui/foo.lua"), None);
+}
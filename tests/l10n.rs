@@ -0,0 +1,22 @@
+use ttsst::l10n::Translations;
+
+#[test]
+fn missing_keys_are_added_to_the_top_level() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("l10n_top_level");
+    std::fs::create_dir_all(&dir).unwrap();
+    let toml = "title = \"Title\"\n\n[menu]\nstart = \"Start\"\n";
+    std::fs::write(dir.join("en.toml"), toml).unwrap();
+
+    let keys = ["title", "menu.start", "score"].map(String::from);
+    let added = Translations::append_missing(&dir, "en", &keys).unwrap();
+    assert_eq!(added, ["score"]);
+
+    let translations = Translations::load(&dir, "en").unwrap();
+    assert_eq!(translations.get("score").unwrap(), "");
+    assert_eq!(translations.get("menu.start").unwrap(), "Start");
+    assert!(translations.get("menu.score").is_none());
+
+    // The rest of the file is kept as is
+    let written = std::fs::read_to_string(dir.join("en.toml")).unwrap();
+    assert!(written.ends_with(&toml["title = \"Title\"\n".len()..]));
+}
@@ -0,0 +1,282 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::json;
+use ttsst::api::{
+    Answer, AnswerCustomMessage, CancellationToken, ExecuteBatch, ExternalEditorApi, Progress,
+};
+use ttsst::error::Error;
+use ttsst::testing::{self, fixtures, mock::MockServer};
+
+/// Writes `json` to a save in the temp directory, and starts a mock server serving it.
+fn serve(name: &str, json: &str) -> (ExternalEditorApi, PathBuf) {
+    let path = std::env::temp_dir().join(format!("ttsst_mock_{}_{name}.json", std::process::id()));
+    std::fs::write(&path, json).unwrap();
+
+    let mock = MockServer::bind(0, &path).unwrap();
+    let api = ExternalEditorApi::with_ports(mock.port().unwrap(), 0).unwrap();
+    let mock = mock.reply_to(api.port().unwrap());
+    std::thread::spawn(move || mock.run());
+    (api, path)
+}
+
+/// A project in the temp directory, whose save is served by a mock server that `ttsst` can be run against.
+struct Project {
+    dir: PathBuf,
+    save: PathBuf,
+    send_port: u16,
+    port: u16,
+}
+
+impl Project {
+    fn serve(name: &str, json: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("ttsst_cli_{}_{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ttsst.toml"), "").unwrap();
+        let save = dir.join("save.json");
+        std::fs::write(&save, json).unwrap();
+
+        // The port is released again, so that ttsst can listen on it
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mock = MockServer::bind(0, &save).unwrap();
+        let send_port = mock.port().unwrap();
+        let mock = mock.reply_to(port);
+        std::thread::spawn(move || mock.run());
+        Self {
+            dir,
+            save,
+            send_port,
+            port,
+        }
+    }
+
+    /// Runs `ttsst` with `args` in the project, and fails if it doesn't succeed.
+    fn run(&self, args: &[&str]) {
+        let output = Command::new(env!("CARGO_BIN_EXE_ttsst"))
+            .arg("--cwd")
+            .arg(&self.dir)
+            .args(["--send-port", &self.send_port.to_string()])
+            .args(["--port", &self.port.to_string()])
+            .args(["--no-input", "--color", "never"])
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "ttsst {args:?} failed: {stderr}");
+    }
+
+    /// Returns the lua script of the object with the `guid` in the save the mock server has written.
+    fn script(&self, guid: &str) -> String {
+        let save = testing::parse_save(&std::fs::read_to_string(&self.save).unwrap());
+        save.objects
+            .find_object(guid)
+            .unwrap()
+            .lua_script
+            .to_string()
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        std::fs::write(self.dir.join(file), contents).unwrap();
+    }
+}
+
+#[test]
+fn attach_reload_and_detach_run_against_the_mock() {
+    let project = Project::serve("commands", fixtures::SCRIPTED);
+    // Reloading reads the files of all objects, including the board
+    project.write("board.lua", "print('board')");
+    project.write("board.xml", "<Panel/>");
+    project.write("cards.lua", "print('cards')");
+
+    project.run(&["attach", "cards.lua", "d4e5f6"]);
+    assert_eq!(project.script("d4e5f6"), "print('cards')");
+
+    project.write("cards.lua", "print('reloaded')");
+    project.run(&["reload"]);
+    assert_eq!(project.script("d4e5f6"), "print('reloaded')");
+
+    project.run(&["detach", "d4e5f6"]);
+    assert_eq!(project.script("d4e5f6"), "");
+}
+
+#[test]
+fn hosts_can_be_names() {
+    let path = std::env::temp_dir().join(format!("ttsst_mock_{}_hosts.json", std::process::id()));
+    std::fs::write(&path, fixtures::SCRIPTED).unwrap();
+
+    let mock = MockServer::bind(0, &path).unwrap();
+    let api =
+        ExternalEditorApi::with_hosts("localhost", mock.port().unwrap(), "0.0.0.0", 0).unwrap();
+    let mock = mock.reply_to(api.port().unwrap());
+    std::thread::spawn(move || mock.run());
+    assert_eq!(PathBuf::from(api.get_scripts().unwrap().save_path), path);
+}
+
+#[test]
+fn get_scripts_returns_objects_with_scripts() {
+    let (api, path) = serve("get_scripts", fixtures::SCRIPTED);
+    let answer = api.get_scripts().unwrap();
+    assert_eq!(PathBuf::from(answer.save_path), path);
+
+    let guids = answer.script_states.iter().map(|s| s.guid.as_str());
+    assert!(guids.eq(["-1", "a1b2c3"]));
+    assert_eq!(answer.script_states[1].name, "Board");
+}
+
+#[test]
+fn reload_updates_the_save() {
+    let (api, path) = serve("reload", fixtures::SCRIPTED);
+    let states = json!([{ "guid": "d4e5f6", "script": "print('deck')" }]);
+    let answer = api.reload(states).unwrap();
+    assert!(answer.script_states.iter().any(|s| s.guid == "d4e5f6"));
+
+    let save = testing::parse_save(&std::fs::read_to_string(path).unwrap());
+    let deck = save.objects.find_object("d4e5f6").unwrap();
+    assert_eq!(&*deck.lua_script, "print('deck')");
+}
+
+#[test]
+fn messages_are_echoed() {
+    let (api, _) = serve("echo", fixtures::MINIMAL);
+    let answer = api.execute("return 1".into()).unwrap();
+    assert_eq!(answer.return_value, None);
+
+    api.custom_message(json!({ "foo": "bar" })).unwrap();
+    let answer: AnswerCustomMessage = api.wait().unwrap();
+    assert_eq!(answer.custom_message, json!({ "foo": "bar" }));
+}
+
+#[test]
+fn chunks_without_results_fail_their_operations() {
+    let (api, _) = serve("batch", fixtures::MINIMAL);
+    let mut batch = ExecuteBatch::new().max_operations(1);
+    batch.push("one", "return 1");
+    batch.push("two", "return 2");
+    let outcomes = api.execute_batch(&batch).unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes.iter().all(|outcome| outcome.result.is_err()));
+}
+
+#[test]
+fn read_timeout_returns_none_without_messages() {
+    let (api, _) = serve("timeout", fixtures::MINIMAL);
+    assert!(api
+        .read_timeout(Duration::from_millis(50))
+        .unwrap()
+        .is_none());
+
+    api.custom_message(json!({ "foo": "bar" })).unwrap();
+    let answer = api.read_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(answer, Some(Answer::AnswerCustomMessage(_))));
+}
+
+#[test]
+fn game_saved_includes_the_save_path() {
+    let answer =
+        serde_json::from_value(json!({ "messageID": 6, "savePath": "Saves/TS_Save_1.json" }));
+    let Ok(Answer::AnswerGameSaved(answer)) = answer else {
+        panic!("expected AnswerGameSaved, got {answer:?}");
+    };
+    assert_eq!(answer.save_path.as_deref(), Some("Saves/TS_Save_1.json"));
+
+    // Older versions of the game don't send the path
+    let answer = serde_json::from_value(json!({ "messageID": 6 }));
+    assert!(matches!(answer, Ok(Answer::AnswerGameSaved(answer)) if answer.save_path.is_none()));
+}
+
+#[test]
+fn unanswered_messages_time_out() {
+    // The mock server doesn't run, so the message is never answered
+    let mock = MockServer::bind(0, std::env::temp_dir()).unwrap();
+    let api = ExternalEditorApi::with_ports(mock.port().unwrap(), 0)
+        .unwrap()
+        .timeout(Duration::from_millis(50))
+        .retries(1);
+    assert!(matches!(api.get_scripts(), Err(Error::Timeout(_))));
+}
+
+#[test]
+fn only_messages_that_dont_change_the_game_are_sent_again() {
+    // Nothing answers, the connections are only counted
+    let game = TcpListener::bind("127.0.0.1:0").unwrap();
+    let api = ExternalEditorApi::with_ports(game.local_addr().unwrap().port(), 0)
+        .unwrap()
+        .timeout(Duration::from_millis(50))
+        .retries(2);
+    game.set_nonblocking(true).unwrap();
+    let sent = || std::iter::from_fn(|| game.accept().ok()).count();
+
+    assert!(matches!(api.get_scripts(), Err(Error::Timeout(_))));
+    assert_eq!(sent(), 3);
+    assert!(matches!(
+        api.execute("return 1".into()),
+        Err(Error::Timeout(_))
+    ));
+    assert_eq!(sent(), 1);
+    assert!(matches!(api.reload(json!([])), Err(Error::Timeout(_))));
+    assert_eq!(sent(), 1);
+}
+
+#[test]
+fn answers_to_other_scripts_are_ignored() {
+    let game = TcpListener::bind("127.0.0.1:0").unwrap();
+    let api = ExternalEditorApi::with_ports(game.local_addr().unwrap().port(), 0).unwrap();
+    let port = api.port().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = game.accept().unwrap();
+        let mut buffer = String::new();
+        stream.read_to_string(&mut buffer).unwrap();
+        let message: serde_json::Value = serde_json::from_str(&buffer).unwrap();
+        let return_id = message["returnID"].as_u64().unwrap();
+
+        // A late answer to a script that has been executed before arrives first
+        for (return_id, value) in [(return_id + 1, "stale"), (return_id, "fresh")] {
+            let answer = json!({ "messageID": 5, "returnID": return_id, "returnValue": value });
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.write_all(answer.to_string().as_bytes()).unwrap();
+        }
+    });
+
+    let answer = api.execute("return 'fresh'".into()).unwrap();
+    assert_eq!(answer.return_value, Some(json!("fresh")));
+}
+
+#[test]
+fn cancelled_waits_return_an_error() {
+    // The mock server doesn't run, so the message is never answered
+    let mock = MockServer::bind(0, std::env::temp_dir()).unwrap();
+    let token = CancellationToken::new();
+    let api = ExternalEditorApi::with_ports(mock.port().unwrap(), 0)
+        .unwrap()
+        .cancellation(token.clone());
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    assert!(matches!(api.get_scripts(), Err(Error::Cancelled)));
+    // Operations started after cancelling are cancelled right away
+    assert!(matches!(
+        api.execute("return 1".into()),
+        Err(Error::Cancelled)
+    ));
+}
+
+#[test]
+fn progress_is_reported() {
+    let (api, _) = serve("progress", fixtures::SCRIPTED);
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let api = api.on_progress({
+        let reported = reported.clone();
+        move |progress| reported.lock().unwrap().push(progress.clone())
+    });
+    api.get_scripts().unwrap();
+    assert_eq!(*reported.lock().unwrap(), [Progress::Sent { attempt: 0 }]);
+}
@@ -0,0 +1,29 @@
+//! The namespace is global, so these tests run in their own process.
+
+use ttsst::tags::{self, Tag};
+use ttsst::testing::{self, ObjectBuilder};
+
+#[test]
+fn tags_of_other_namespaces_are_not_valid() {
+    tags::set_namespace("proj1").unwrap();
+    assert!(tags::set_namespace("proj2").is_err());
+    assert!(tags::set_namespace("proj1").is_ok());
+
+    let tag = testing::tag("scripts/foo.lua");
+    assert_eq!(tag.clone().into_inner(), "proj1/lua/scripts/foo.lua");
+    assert!(tag.is_lua());
+    assert!(tag.starts_with(&"./scripts"));
+
+    let other = ["lua/foo.lua", "proj2/lua/foo.lua", "proj2/xml/foo.xml"];
+    let object = other
+        .iter()
+        .map(|tag| serde_json::from_value::<Tag>(tag.to_owned().into()).unwrap())
+        .fold(
+            ObjectBuilder::new("aaaaaa").tag(tag.clone()),
+            |object, tag| object.tag(tag),
+        )
+        .build();
+    assert!(object.tags.iter().filter(|tag| tag.is_valid()).eq([&tag]));
+    assert_eq!(object.valid_lua().unwrap(), Some(tag));
+    assert_eq!(object.valid_xml().unwrap(), None);
+}
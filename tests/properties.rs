@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use proptest::prelude::*;
+use serde_json::Value;
+use ttsst::testing::{self, ObjectBuilder, SaveBuilder};
+use ttsst::Tag;
+
+/// Relative paths like `foo/bar_1`, without an extension.
+fn file_path() -> impl Strategy<Value = String> {
+    proptest::collection::vec("[a-zA-Z0-9_-]{1,8}", 1..4).prop_map(|parts| parts.join("/"))
+}
+
+fn guid() -> impl Strategy<Value = String> {
+    "[a-f0-9]{6}"
+}
+
+proptest! {
+    #[test]
+    fn tag_path_roundtrip(path in file_path(), ext in prop_oneof!["lua", "ttslua", "xml"]) {
+        let file = format!("{path}.{ext}");
+        let tag = testing::tag(&file);
+        prop_assert!(tag.is_valid());
+        prop_assert_eq!(tag.is_xml(), ext == "xml");
+        prop_assert_eq!(tag.path().unwrap(), Path::new("./").join(&file));
+    }
+
+    #[test]
+    fn other_extensions_are_rejected(path in file_path(), ext in "[a-z]{1,4}") {
+        prop_assume!(!["lua", "ttslua", "xml"].contains(&ext.as_str()));
+        let file = Path::new(".\\").join(format!("{path}.{ext}"));
+        prop_assert!(Tag::try_from(file.as_path()).is_err());
+    }
+
+    #[test]
+    fn unicode_file_names_are_accepted(name in "[\\p{L}\\p{N}_ -]{0,8}[\\p{L}\\p{N}_-]") {
+        let tag = testing::tag(format!("{name}.lua"));
+        prop_assert_eq!(tag.path().unwrap(), Path::new("./").join(format!("{name}.lua")));
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_are_rejected(path in file_path(), suffix in "[. ]{1,3}") {
+        let file = Path::new(".\\").join(format!("{path}{suffix}/file.lua"));
+        prop_assert!(Tag::try_from(file.as_path()).is_err());
+    }
+
+    #[test]
+    fn saves_survive_a_roundtrip(
+        guids in proptest::collection::hash_set(guid(), 0..8),
+        script in ".*",
+        extra in any::<i64>(),
+    ) {
+        let mut builder = SaveBuilder::new("Property").lua_script(&script).field("EpochTime", extra);
+        for guid in &guids {
+            let object = ObjectBuilder::new(guid).lua_script(&script).field("CardID", extra).build();
+            builder = builder.object(object);
+        }
+        let save = builder.build();
+
+        let json = serde_json::to_string(&save).unwrap();
+        let roundtrip = testing::parse_save(&json);
+        prop_assert_eq!(serde_json::to_value(&save).unwrap(), serde_json::to_value(&roundtrip).unwrap());
+        prop_assert_eq!(roundtrip.objects.len(), guids.len());
+    }
+
+    #[test]
+    fn setting_the_same_script_is_not_a_change(script in ".*", other in ".*") {
+        let mut object = ObjectBuilder::new("aaaaaa").lua_script(&script).build();
+        prop_assert!(!object.set_lua_script(script.as_str()));
+        prop_assert!(!object.is_dirty());
+        prop_assert_eq!(object.set_lua_script(other.as_str()), script != other);
+        prop_assert_eq!(object.is_dirty(), script != other);
+        prop_assert_eq!(object.to_value()["script"].clone(), Value::from(other));
+    }
+}
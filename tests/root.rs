@@ -0,0 +1,18 @@
+//! The root is global, so these tests run in their own process.
+
+use ttsst::tags::{self, Tag};
+use ttsst::testing;
+
+#[test]
+fn absolute_paths_are_relative_to_the_root() {
+    let root = std::env::temp_dir().join("ttsst_root");
+    tags::set_root(&root).unwrap();
+    assert!(tags::set_root(std::env::temp_dir()).is_err());
+    assert_eq!(tags::root(), Some(root.as_path()));
+
+    let tag = Tag::try_from(root.join("cards").join("deck.lua").as_path()).unwrap();
+    assert_eq!(tag, testing::tag("cards/deck.lua"));
+    // The current directory is not the root
+    let outside = std::env::current_dir().unwrap().join("deck.lua");
+    assert!(Tag::try_from(outside.as_path()).is_err());
+}
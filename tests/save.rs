@@ -0,0 +1,270 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+use ttsst::tags::{Label, Tag};
+use ttsst::testing::{self, fixtures, ObjectBuilder, SaveBuilder};
+use ttsst::Violation;
+
+#[test]
+fn fixtures_survive_a_roundtrip() {
+    for (name, json) in fixtures::ALL {
+        let original: Value = serde_json::from_str(json).unwrap();
+        let roundtrip = serde_json::to_value(testing::parse_save(json)).unwrap();
+        assert_eq!(original, roundtrip, "{name} changed after a round-trip");
+    }
+}
+
+#[test]
+fn fixture_objects_have_valid_tags() {
+    let save = testing::parse_save(fixtures::SCRIPTED);
+    let board = save.objects.find_object("a1b2c3").unwrap();
+    assert_eq!(board.valid_lua().unwrap(), Some(testing::tag("board.lua")));
+    assert_eq!(board.valid_xml().unwrap(), Some(testing::tag("board.xml")));
+    assert!(board.is_locked());
+}
+
+#[test]
+fn zones_are_hidden() {
+    let save = testing::parse_save(fixtures::ZONES);
+    assert_eq!(save.objects.zones().len(), 3);
+    assert_eq!(save.objects.scripting_zones().len(), 1);
+
+    let visible = save.objects.clone().filter_hidden();
+    assert!(visible.iter().all(|object| object.name != "HandTrigger"));
+    assert!(visible.find_object("5c7a21").is_ok());
+
+    let visible = save.objects.filter_names(&["ScriptingTrigger"]);
+    assert!(visible.iter().any(|object| object.name == "HandTrigger"));
+    assert!(visible.find_object("5c7a21").is_err());
+}
+
+#[test]
+fn rename_tag_replaces_object_tags_and_labels() {
+    let (old, new) = (testing::tag("old.lua"), testing::tag("new.lua"));
+    let mut save = SaveBuilder::new("Rename")
+        .object(ObjectBuilder::new("aaaaaa").tag(old.clone()).build())
+        .object(ObjectBuilder::new("bbbbbb").tag(old.clone()).build())
+        .object(testing::object("cccccc"))
+        .label(old.clone())
+        .build();
+
+    assert_eq!(save.rename_tag(&old, &new), 2);
+    assert!(save
+        .objects
+        .iter()
+        .all(|object| !object.tags.contains(&old)));
+    assert!(save.push_object_tag(old));
+    assert!(!save.push_object_tag(new));
+}
+
+#[test]
+fn rename_tag_removes_duplicate_labels() {
+    let (old, new) = (testing::tag("old.lua"), testing::tag("new.lua"));
+    let mut save = SaveBuilder::new("Rename")
+        .label(old.clone())
+        .label(testing::tag("other.lua"))
+        .label(new.clone())
+        .build();
+
+    save.rename_tag(&old, &new);
+    assert_eq!(save.tags.labels.len(), 2);
+    assert!(save.push_object_tag(old));
+}
+
+#[test]
+fn global_tags_are_stored_as_component_tags() {
+    let (lua, xml) = (testing::tag("a/Global.lua"), testing::tag("a/Global.xml"));
+    let mut save = SaveBuilder::new("Global").label(lua.clone()).build();
+    assert_eq!(save.global_lua().unwrap(), None);
+
+    assert!(save.set_global_tag(lua.clone()));
+    assert!(save.set_global_tag(xml.clone()));
+    assert!(!save.set_global_tag(xml.clone()));
+    assert_eq!(save.global_lua().unwrap(), Some(lua.clone()));
+    assert_eq!(save.global_xml().unwrap(), Some(xml));
+
+    // Attaching a different file replaces the tag of the same type
+    let other = testing::tag("b/Global.lua");
+    assert!(save.set_global_tag(other.clone()));
+    assert_eq!(save.global_lua().unwrap(), Some(other));
+    assert_eq!(save.remove_global_tags().len(), 2);
+    assert!(save.global_tags().is_empty());
+    assert!(!save.push_object_tag(lua));
+}
+
+#[test]
+fn only_changed_objects_are_dirty() {
+    let mut save = testing::save(
+        "Dirty",
+        [
+            ObjectBuilder::new("aaaaaa").lua_script("print(1)").build(),
+            ObjectBuilder::new("bbbbbb").lua_script("print(2)").build(),
+        ],
+    );
+
+    let object = save.objects.find_object_mut("aaaaaa").unwrap();
+    assert!(!object.set_lua_script("print(1)"));
+    assert!(object.set_lua_script("print(3)"));
+    assert_eq!(save.objects.to_dirty_values().len(), 1);
+
+    save.objects.clear_dirty();
+    assert!(save.objects.to_dirty_values().is_empty());
+}
+
+#[test]
+fn schema_describes_the_modeled_fields() {
+    let schema = ttsst::Save::json_schema();
+    let properties = schema["properties"].as_object().unwrap();
+    for (_, json) in fixtures::ALL {
+        let save: Value = serde_json::from_str(json).unwrap();
+        for key in schema["required"].as_array().unwrap() {
+            assert!(save.get(key.as_str().unwrap()).is_some());
+        }
+    }
+    assert!(properties.contains_key("ObjectStates"));
+    assert!(schema["definitions"]["Object"]["properties"]
+        .get("GUID")
+        .is_some());
+}
+
+#[test]
+fn labels_are_normalized_like_in_game() {
+    let tag = testing::tag("UI/Board.lua");
+    let mut save = SaveBuilder::new("Labels").build();
+    assert!(save.push_object_tag(tag.clone()));
+    assert!(!save.push_object_tag(testing::tag("ui/board.lua")));
+
+    let label = &save.tags.labels[0];
+    assert_eq!(label.displayed, tag.into_inner());
+    assert_eq!(label.normalized, "lua/ui/board.lua");
+    assert!(label.validate().is_ok());
+}
+
+#[test]
+fn labels_are_normalized_on_write() {
+    let json = serde_json::json!({ "displayed": "lua/Deck.lua", "normalized": "lua/Deck.lua" });
+    let label: Label = serde_json::from_value(json).unwrap();
+    assert!(label.validate().is_err());
+
+    let written = serde_json::to_value(&label).unwrap();
+    assert_eq!(written["normalized"], "lua/deck.lua");
+    assert!(Label::new("").validate().is_err());
+}
+
+#[test]
+fn duplicates_get_new_guids() {
+    let save = testing::parse_save(fixtures::SCRIPTED);
+    let mut used = save.objects.guids();
+    assert!(used.contains("0a0b0c"));
+
+    let deck = save.objects.find_object("d4e5f6").unwrap();
+    let copy = deck.duplicate(&mut used);
+    assert_ne!(copy.guid, deck.guid);
+    assert!(copy.is_dirty());
+    assert_eq!(copy.valid_lua().unwrap(), deck.valid_lua().unwrap());
+
+    let json = serde_json::to_value(&copy).unwrap();
+    let card = json["ContainedObjects"][0]["GUID"].as_str().unwrap();
+    assert_ne!(card, "0a0b0c");
+    assert!(used.contains(card) && used.contains(&copy.guid));
+}
+
+#[test]
+fn contained_objects_are_parsed() {
+    let save = testing::parse_save(fixtures::SCRIPTED);
+    let deck = save.objects.find_object("d4e5f6").unwrap();
+    let cards = deck.contained_objects().unwrap();
+    assert_eq!(cards.len(), 1);
+    assert_eq!(
+        (cards[0].guid.as_str(), cards[0].name.as_str()),
+        ("0a0b0c", "Card")
+    );
+
+    let board = save.objects.find_object("a1b2c3").unwrap();
+    assert!(board.contained_objects().unwrap().is_empty());
+
+    let mut deck = deck.clone();
+    let mut cards = cards;
+    cards[0].set_lua_script("print('card')");
+    deck.set_contained_objects(cards).unwrap();
+    let cards = deck.contained_objects().unwrap();
+    assert_eq!(&*cards[0].lua_script, "print('card')");
+}
+
+#[test]
+fn normalize_sorts_objects_and_rounds_floats() {
+    let mut save = SaveBuilder::new("Normalize")
+        .object(testing::object("bbbbbb"))
+        .object(
+            ObjectBuilder::new("aaaaaa")
+                .field("AltLookAngle", json!({ "x": 0.333_333, "y": 1 }))
+                .build(),
+        )
+        .build();
+    save.normalize(Some(4));
+
+    let guids = save.objects.iter().map(|object| object.guid.as_str());
+    assert!(guids.eq(["aaaaaa", "bbbbbb"]));
+    let mut object = save.objects.find_object("aaaaaa").unwrap().clone();
+    object.set_position([1.000_000_12, -0.000_01, 2.5]);
+    save.objects = vec![object].into();
+    save.normalize(Some(4));
+
+    let object = &save.objects[0];
+    assert_eq!(object.position(), Some([1.0, 0.0, 2.5]));
+    let json = serde_json::to_value(object).unwrap();
+    assert_eq!(json["AltLookAngle"], json!({ "x": 0.3333, "y": 1 }));
+}
+
+#[test]
+fn objects_are_offset_and_snapped_to_the_grid() {
+    let mut object = testing::object("aaaaaa");
+    object.offset([2.0, 1.0, -0.5]);
+    object.offset([2.0, 0.0, 0.0]);
+    assert_eq!(object.position(), Some([4.0, 1.0, -0.5]));
+
+    object.offset([0.4, 0.3, 0.0]);
+    object.snap_to_grid(2.0);
+    assert_eq!(object.position(), Some([4.0, 1.3, 0.0]));
+    object.snap_to_grid(0.0);
+    assert_eq!(object.position(), Some([4.0, 1.3, 0.0]));
+
+    assert!(!object.is_locked());
+}
+
+#[test]
+fn violations_report_mismatched_tags_and_missing_files() {
+    let (lua, xml) = (testing::tag("board.lua"), testing::tag("board.xml"));
+    let existing = Path::new(env!("CARGO_TARGET_TMPDIR")).join("violations.lua");
+    std::fs::write(&existing, "").unwrap();
+    let missing = testing::tag("missing.lua");
+    let save = SaveBuilder::new("Violations")
+        .object(
+            ObjectBuilder::new("aaaaaa")
+                .tag(lua)
+                .lua_script("print('board')")
+                .xml_ui("<Panel/>")
+                .build(),
+        )
+        .object(ObjectBuilder::new("bbbbbb").tag(xml.clone()).build())
+        .label(Tag::try_from(existing.as_path()).unwrap())
+        .label(missing.clone())
+        .build();
+
+    let violations = save.violations().unwrap();
+    assert_eq!(
+        violations,
+        [
+            Violation::UiWithoutTag {
+                guid: "aaaaaa".into()
+            },
+            Violation::TagWithoutUi {
+                guid: "bbbbbb".into(),
+                tag: xml
+            },
+            Violation::MissingFile(Label::from(missing)),
+        ]
+    );
+    assert!(violations[0].is_inconsistent());
+    assert!(!violations[2].is_inconsistent());
+}